@@ -0,0 +1,57 @@
+use std::fmt::Display;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    UnexpectedCharacter(char),
+    InvalidNumber(String),
+    InvalidEscape(char),
+    UnterminatedString,
+    UnexpectedToken(String),
+    UnknownDirective(String),
+    UnknownInstruction(String),
+    UnknownConstant(String),
+    UndefinedLabel(String),
+    DuplicateLabel(String),
+    /// The instruction has no encoding for the given operands, e.g. `st ra, 0x10`.
+    InvalidOperands(String),
+    ValueOutOfRange(u32),
+    /// Two parts of the program were placed on the same address.
+    Overlap(u32),
+}
+
+/// An error in a `.8do` source, `line` is 1 based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub kind: AsmErrorKind,
+}
+
+impl AsmError {
+    pub fn new(line: usize, kind: AsmErrorKind) -> Self {
+        Self { line, kind }
+    }
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Line {}: ", self.line)?;
+
+        match &self.kind {
+            AsmErrorKind::UnexpectedCharacter(c) => write!(f, "Unexpected character '{c}'"),
+            AsmErrorKind::InvalidNumber(digits) => write!(f, "Invalid number '{digits}'"),
+            AsmErrorKind::InvalidEscape(c) => write!(f, "Unknown escape sequence '\\{c}'"),
+            AsmErrorKind::UnterminatedString => write!(f, "Missing closing '\"'"),
+            AsmErrorKind::UnexpectedToken(token) => write!(f, "Unexpected {token}"),
+            AsmErrorKind::UnknownDirective(name) => write!(f, "Unknown directive '.{name}'"),
+            AsmErrorKind::UnknownInstruction(name) => write!(f, "Unknown instruction '{name}'"),
+            AsmErrorKind::UnknownConstant(name) => write!(f, "Constant '@{name}' is not defined"),
+            AsmErrorKind::UndefinedLabel(name) => write!(f, "Label '{name}' is not defined"),
+            AsmErrorKind::DuplicateLabel(name) => write!(f, "Label '{name}' is already defined"),
+            AsmErrorKind::InvalidOperands(name) => write!(f, "Invalid operands for '{name}'"),
+            AsmErrorKind::ValueOutOfRange(value) => write!(f, "Value {value:#x} does not fit"),
+            AsmErrorKind::Overlap(address) => write!(f, "Output overlaps at address {address:#08x}"),
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
@@ -0,0 +1,197 @@
+use super::error::{AsmError, AsmErrorKind};
+
+/// How a number was written. `$` marks addresses, everything else (`0x`, decimal) is an immediate value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberKind {
+    Immediate,
+    Address,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    Identifier(String),
+    Number(u32, NumberKind),
+    Constant(String),
+    Directive(String),
+    String(Vec<u8>),
+    Colon,
+    Comma,
+    Plus,
+    Minus,
+    Newline,
+}
+
+/// Splits `.8do` source into tokens, each tagged with the (1 based) line it came from. Comments start with `;`
+/// and run to the end of the line.
+pub fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, AsmError> {
+    let mut tokens = Vec::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let mut chars = line.chars().peekable();
+
+        while let Some(&c) = chars.peek() {
+            let token = match c {
+                ';' => break,
+                c if c.is_whitespace() => {
+                    chars.next();
+                    continue;
+                }
+                ':' | ',' | '+' | '-' => {
+                    chars.next();
+
+                    match c {
+                        ':' => Token::Colon,
+                        ',' => Token::Comma,
+                        '+' => Token::Plus,
+                        _ => Token::Minus,
+                    }
+                }
+                '$' => {
+                    chars.next();
+                    let digits = take_while(&mut chars, |c| c.is_ascii_alphanumeric());
+
+                    Token::Number(parse_number(&digits, 16, line_number)?, NumberKind::Address)
+                }
+                '0'..='9' => {
+                    let word = take_while(&mut chars, |c| c.is_ascii_alphanumeric());
+
+                    let value = match word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+                        Some(digits) => parse_number(digits, 16, line_number)?,
+                        None => parse_number(&word, 10, line_number)?,
+                    };
+
+                    Token::Number(value, NumberKind::Immediate)
+                }
+                '@' | '.' => {
+                    chars.next();
+                    let name = take_while(&mut chars, is_identifier_char);
+
+                    if name.is_empty() {
+                        return Err(AsmError::new(line_number, AsmErrorKind::UnexpectedCharacter(c)));
+                    }
+
+                    if c == '@' {
+                        Token::Constant(name)
+                    } else {
+                        Token::Directive(name.to_lowercase())
+                    }
+                }
+                '"' => {
+                    chars.next();
+                    Token::String(parse_string(&mut chars, line_number)?)
+                }
+                c if c.is_ascii_alphabetic() || c == '_' => Token::Identifier(take_while(&mut chars, is_identifier_char)),
+                c => return Err(AsmError::new(line_number, AsmErrorKind::UnexpectedCharacter(c))),
+            };
+
+            tokens.push((token, line_number));
+        }
+
+        tokens.push((Token::Newline, line_number));
+    }
+
+    Ok(tokens)
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn take_while<I: Iterator<Item = char>>(chars: &mut std::iter::Peekable<I>, predicate: fn(char) -> bool) -> String {
+    let mut word = String::new();
+
+    while let Some(&c) = chars.peek() {
+        if !predicate(c) {
+            break;
+        }
+
+        word.push(c);
+        chars.next();
+    }
+
+    word
+}
+
+fn parse_number(digits: &str, radix: u32, line: usize) -> Result<u32, AsmError> {
+    u32::from_str_radix(digits, radix).map_err(|_| AsmError::new(line, AsmErrorKind::InvalidNumber(digits.into())))
+}
+
+fn parse_string<I: Iterator<Item = char>>(chars: &mut I, line: usize) -> Result<Vec<u8>, AsmError> {
+    let mut bytes = Vec::new();
+
+    loop {
+        let c = match chars.next() {
+            Some('"') => return Ok(bytes),
+            Some('\\') => match chars.next() {
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                Some('0') => '\0',
+                Some(c @ ('\\' | '"')) => c,
+                Some(c) => return Err(AsmError::new(line, AsmErrorKind::InvalidEscape(c))),
+                None => break,
+            },
+            Some(c) => c,
+            None => break,
+        };
+
+        if !c.is_ascii() {
+            return Err(AsmError::new(line, AsmErrorKind::UnexpectedCharacter(c)));
+        }
+
+        bytes.push(c as u8);
+    }
+
+    Err(AsmError::new(line, AsmErrorKind::UnterminatedString))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        let tokens: Vec<Token> = tokenize("main: mov rc, str + rb ; comment\n.byte 0x1F, $0200 @out 12 \"a\\n\"")
+            .unwrap()
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Identifier("main".into()),
+                Token::Colon,
+                Token::Identifier("mov".into()),
+                Token::Identifier("rc".into()),
+                Token::Comma,
+                Token::Identifier("str".into()),
+                Token::Plus,
+                Token::Identifier("rb".into()),
+                Token::Newline,
+                Token::Directive("byte".into()),
+                Token::Number(0x1F, NumberKind::Immediate),
+                Token::Comma,
+                Token::Number(0x200, NumberKind::Address),
+                Token::Constant("out".into()),
+                Token::Number(12, NumberKind::Immediate),
+                Token::String(b"a\n".to_vec()),
+                Token::Newline,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_errors() {
+        assert_eq!(
+            tokenize("\n.ascii \"abc").unwrap_err(),
+            AsmError::new(2, AsmErrorKind::UnterminatedString)
+        );
+        assert_eq!(
+            tokenize("mov ra, 0xZZ").unwrap_err(),
+            AsmError::new(1, AsmErrorKind::InvalidNumber("ZZ".into()))
+        );
+        assert_eq!(tokenize("mov ra, #1").unwrap_err(), AsmError::new(1, AsmErrorKind::UnexpectedCharacter('#')));
+    }
+}
@@ -0,0 +1,533 @@
+//! Assembler for `.8do` sources. Opcodes and operand sizes come from the same [`InstructionInfoFile`] the CPU
+//! builds its dispatch table from, so the two can not disagree about encodings.
+
+mod error;
+mod lexer;
+mod parser;
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::info::{InstructionInfo, InstructionInfoFile};
+pub use error::{AsmError, AsmErrorKind};
+use parser::{AddressExpr, Expr, Operand, Statement};
+
+/// Register names by their code in the instruction metadata. Codes 4-7 are the full registers again, the CPU
+/// uses them for register offsets.
+pub const REGISTER_NAMES: [&str; 16] = [
+    "ra", "rb", "rc", "rd", "ra", "rb", "rc", "rd", "rah", "ral", "rbh", "rbl", "rch", "rcl", "rdh", "rdl",
+];
+
+pub(crate) fn register_code(name: &str) -> Option<u8> {
+    let name = name.to_lowercase();
+
+    (0..4).chain(8..16).find(|&code| REGISTER_NAMES[code as usize] == name)
+}
+
+/// An assembled program. `image` starts at address 0 and can be loaded with
+/// [`ROM::new_from_file`](crate::device::ROM::new_from_file), addresses nothing was placed on are zero.
+#[derive(Debug, Default)]
+pub struct Assembly {
+    pub image: Vec<u8>,
+    pub labels: BTreeMap<String, u32>,
+    /// Every instruction and data directive in source order.
+    pub listing: Vec<ListingLine>,
+}
+
+/// The bytes one source line produced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListingLine {
+    pub address: u32,
+    pub bytes: Vec<u8>,
+    /// The 1 based source line.
+    pub line: usize,
+    /// Cycles the instruction takes including the fetch, from [`InstructionInfo::cycles`]. `None` for data.
+    pub cycles: Option<u8>,
+}
+
+impl Assembly {
+    /// Formats the listing next to the lines of `source`, which has to be the source that was assembled. Lines
+    /// with more than 6 bytes continue on the following lines.
+    pub fn listing_text(&self, source: &str) -> String {
+        let lines: Vec<&str> = source.lines().collect();
+        let mut text = String::new();
+
+        for entry in &self.listing {
+            let cycles = entry.cycles.map(|cycles| cycles.to_string()).unwrap_or_default();
+            let source = lines.get(entry.line - 1).map_or("", |line| line.trim());
+
+            for (index, chunk) in entry.bytes.chunks(6).enumerate() {
+                let bytes: Vec<String> = chunk.iter().map(|byte| format!("{byte:02X}")).collect();
+                let address = entry.address + index as u32 * 6;
+
+                match index {
+                    0 => text += &format!("{address:06X}: {:<17} {cycles:>3}  {:>4}  {source}\n", bytes.join(" "), entry.line),
+                    _ => text += &format!("{address:06X}: {}\n", bytes.join(" ")),
+                }
+            }
+        }
+
+        text
+    }
+}
+
+/// The operand bytes following the opcode and metadata.
+enum OperandBytes {
+    None,
+    Immediate { value: u32, width: u8 },
+    Address(AddressExpr),
+}
+
+struct Encoded {
+    opcode: u8,
+    metadata: u16,
+    operand: OperandBytes,
+    cycles: Option<u8>,
+}
+
+impl Encoded {
+    fn size(&self) -> u32 {
+        3 + match self.operand {
+            OperandBytes::None => 0,
+            OperandBytes::Immediate { width, .. } => width as u32,
+            OperandBytes::Address(_) => 3,
+        }
+    }
+}
+
+pub fn assemble(source: &str, inst_info: &InstructionInfoFile) -> Result<Assembly, AsmError> {
+    let statements = parser::parse(lexer::tokenize(source)?)?;
+    let instructions: HashMap<&str, &InstructionInfo> =
+        inst_info.info.iter().map(|info| (info.name.as_str(), info)).collect();
+
+    // First pass: place everything, which only needs the size of every statement.
+    let mut assembly = Assembly::default();
+    let mut placed: Vec<(u32, Placed, usize)> = Vec::new();
+    let mut address = 0;
+
+    for (statement, line) in statements {
+        let size = match &statement {
+            Statement::Label(name) => {
+                if assembly.labels.insert(name.clone(), address).is_some() {
+                    return Err(AsmError::new(line, AsmErrorKind::DuplicateLabel(name.clone())));
+                }
+
+                continue;
+            }
+            Statement::Org(org) => {
+                if *org > 0xFF_FFFF {
+                    return Err(AsmError::new(line, AsmErrorKind::ValueOutOfRange(*org)));
+                }
+
+                address = *org;
+                continue;
+            }
+            Statement::Data { width, values } => *width as u32 * values.len() as u32,
+            Statement::Bytes(bytes) => bytes.len() as u32,
+            Statement::Instruction { name, operands } => {
+                let info = instructions
+                    .get(name.as_str())
+                    .ok_or_else(|| AsmError::new(line, AsmErrorKind::UnknownInstruction(name.clone())))?;
+                let encoded = encode(info, operands).ok_or_else(|| AsmError::new(line, AsmErrorKind::InvalidOperands(name.clone())))?;
+                let size = encoded.size();
+
+                placed.push((address, Placed::Instruction(encoded), line));
+                address = advance(address, size, line)?;
+                continue;
+            }
+        };
+
+        placed.push((address, Placed::Data(statement), line));
+        address = advance(address, size, line)?;
+    }
+
+    // Second pass: all labels are known, write the bytes.
+    let mut written = Vec::new();
+
+    for (address, encoded, line) in placed {
+        let cycles = match &encoded {
+            Placed::Instruction(encoded) => encoded.cycles,
+            Placed::Data(_) => None,
+        };
+
+        let bytes = match encoded {
+            Placed::Data(Statement::Data { width, values }) => {
+                let mut bytes = Vec::new();
+
+                for value in values {
+                    let value = resolve(&assembly.labels, &value, line)?;
+                    check_width(value, width, line)?;
+
+                    bytes.extend_from_slice(&value.to_be_bytes()[4 - width as usize..]);
+                }
+
+                bytes
+            }
+            Placed::Data(Statement::Bytes(bytes)) => bytes,
+            Placed::Data(_) => unreachable!("Only data statements are placed as data"),
+            Placed::Instruction(encoded) => {
+                let mut bytes = vec![encoded.opcode];
+                bytes.extend_from_slice(&encoded.metadata.to_be_bytes());
+
+                match encoded.operand {
+                    OperandBytes::None => {}
+                    OperandBytes::Immediate { value, width } => {
+                        check_width(value, width, line)?;
+                        bytes.extend_from_slice(&value.to_be_bytes()[4 - width as usize..]);
+                    }
+                    OperandBytes::Address(expr) => {
+                        let value = resolve(&assembly.labels, &expr.base, line)? as i64 + expr.offset;
+
+                        if !(0..=0xFF_FFFF).contains(&value) {
+                            return Err(AsmError::new(line, AsmErrorKind::ValueOutOfRange(value as u32)));
+                        }
+
+                        bytes.extend_from_slice(&(value as u32).to_be_bytes()[1..]);
+                    }
+                }
+
+                bytes
+            }
+        };
+
+        let end = address as usize + bytes.len();
+
+        if end > 0x100_0000 {
+            return Err(AsmError::new(line, AsmErrorKind::ValueOutOfRange(end as u32 - 1)));
+        }
+
+        if assembly.image.len() < end {
+            assembly.image.resize(end, 0);
+            written.resize(end, false);
+        }
+
+        for (offset, byte) in bytes.into_iter().enumerate() {
+            let index = address as usize + offset;
+
+            if written[index] {
+                return Err(AsmError::new(line, AsmErrorKind::Overlap(index as u32)));
+            }
+
+            assembly.image[index] = byte;
+            written[index] = true;
+        }
+
+        assembly.listing.push(ListingLine {
+            address,
+            bytes: assembly.image[address as usize..end].to_vec(),
+            line,
+            cycles,
+        });
+    }
+
+    Ok(assembly)
+}
+
+enum Placed {
+    Data(Statement),
+    Instruction(Encoded),
+}
+
+fn resolve(labels: &BTreeMap<String, u32>, expr: &Expr, line: usize) -> Result<u32, AsmError> {
+    match expr {
+        Expr::Number(value) => Ok(*value),
+        Expr::Label(name) => labels
+            .get(name)
+            .copied()
+            .ok_or_else(|| AsmError::new(line, AsmErrorKind::UndefinedLabel(name.clone()))),
+    }
+}
+
+// The address after `size` bytes placed at `address`, the end of memory is checked once the bytes are written.
+fn advance(address: u32, size: u32, line: usize) -> Result<u32, AsmError> {
+    address
+        .checked_add(size)
+        .ok_or_else(|| AsmError::new(line, AsmErrorKind::ValueOutOfRange(address)))
+}
+
+fn check_width(value: u32, width: u8, line: usize) -> Result<(), AsmError> {
+    if width < 4 && value >> (width * 8) != 0 {
+        return Err(AsmError::new(line, AsmErrorKind::ValueOutOfRange(value)));
+    }
+
+    Ok(())
+}
+
+// Picks the addressing mode from the operands and builds the metadata word, see `CPUMetadata`. Returns `None`
+// if the instruction has no encoding for these operands.
+fn encode(info: &InstructionInfo, operands: &[Operand]) -> Option<Encoded> {
+    let mut metadata = 0u16;
+    let mut operand = OperandBytes::None;
+
+    let mode = match (info.size, operands) {
+        (0, []) => String::from("M"),
+        (1, [single]) => String::from(operand_mode(single)),
+        (2, [Operand::Register(reg0), second]) => {
+            metadata |= *reg0 as u16;
+
+            if let Operand::Register(reg1) = second {
+                metadata |= (*reg1 as u16) << 4;
+            }
+
+            String::from("R") + operand_mode(second)
+        }
+        _ => return None,
+    };
+
+    match operands.last() {
+        Some(Operand::Register(reg)) if info.size == 1 => metadata |= *reg as u16,
+        Some(Operand::Immediate(value)) => {
+            operand = OperandBytes::Immediate {
+                value: *value,
+                width: if info.byte { 1 } else { 2 },
+            }
+        }
+        Some(Operand::Address(expr)) => {
+            if let Some(reg) = expr.register {
+                metadata |= 1 << 15 | (0b100 | reg as u16) << 4;
+            }
+
+            operand = OperandBytes::Address(expr.clone());
+        }
+        _ => {}
+    }
+
+    Some(Encoded {
+        opcode: *info.opcode.get(&mode)?,
+        metadata,
+        operand,
+        cycles: info.cycles.get(&mode).copied(),
+    })
+}
+
+fn operand_mode(operand: &Operand) -> &'static str {
+    match operand {
+        Operand::Register(_) => "R",
+        Operand::Immediate(_) => "I",
+        Operand::Address(_) => "A",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cpu::{
+            instructions::testing::{setup, try_step_instruction},
+            CycleStatus, EmuOptions, ExtendedAddress, CPU,
+        },
+        device::{DeviceResult, IOMappedDevice, Out, RAM, ROM},
+        info::get_instructions,
+        machine::Machine,
+    };
+
+    fn assemble_ok(source: &str) -> Assembly {
+        assemble(source, &get_instructions()).unwrap()
+    }
+
+    #[test]
+    fn test_encodings() {
+        let assembly = assemble_ok(
+            "@port 0xA0
+            start:
+                mov rb, 0x1234
+                movb rah, 0x12
+                mov rc, str + rb
+                add ra, rd
+                out ra, @port
+                psh 0xBEEF
+                jmp start
+                hlt
+            str: .asciiz \"hi\"",
+        );
+
+        assert_eq!(
+            assembly.image,
+            [
+                59, 0x00, 0x01, 0x12, 0x34,       // mov rb, 0x1234
+                125, 0x00, 0x08, 0x12,            // movb rah, 0x12
+                51, 0x80, 0x52, 0x00, 0x00, 0x24, // mov rc, str + rb
+                27, 0x00, 0x30,                   // add ra, rd
+                65, 0x00, 0x00, 0xA0,             // out ra, 0xA0
+                52, 0x00, 0x00, 0xBE, 0xEF,       // psh 0xBEEF
+                72, 0x00, 0x00, 0x00, 0x00, 0x00, // jmp start
+                8, 0x00, 0x00,                    // hlt
+                b'h', b'i', 0x00,
+            ]
+        );
+        assert_eq!(assembly.labels["str"], 0x24);
+    }
+
+    #[test]
+    fn test_directives() {
+        let assembly = assemble_ok(
+            "table: .byte 0x00 .word $0010
+            .org $0008
+            .address end, $123456
+            .ascii \"ok\"
+            end:",
+        );
+
+        assert_eq!(
+            assembly.image,
+            [
+                0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, // table, reset vector $000010
+                0x00, 0x00, 0x10, 0x12, 0x34, 0x56, b'o', b'k',
+            ]
+        );
+        assert_eq!(assembly.labels["end"], 0x10);
+    }
+
+    #[test]
+    fn test_listing() {
+        let source = "start: mov ra, 0x0001\n    jmp start\n.ascii \"Hello!!\"";
+        let assembly = assemble_ok(source);
+
+        assert_eq!(
+            assembly.listing,
+            [
+                ListingLine { address: 0x00, bytes: vec![59, 0x00, 0x00, 0x00, 0x01], line: 1, cycles: Some(5) },
+                ListingLine { address: 0x05, bytes: vec![72, 0x00, 0x00, 0x00, 0x00, 0x00], line: 2, cycles: Some(6) },
+                ListingLine { address: 0x0B, bytes: b"Hello!!".to_vec(), line: 3, cycles: None },
+            ]
+        );
+        assert_eq!(
+            assembly.listing_text(source),
+            "000000: 3B 00 00 00 01      5     1  start: mov ra, 0x0001\n\
+             000005: 48 00 00 00 00 00   6     2  jmp start\n\
+             00000B: 48 65 6C 6C 6F 21         3  .ascii \"Hello!!\"\n\
+             000011: 21\n"
+        );
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        let inst_info = get_instructions();
+        let error = |source| assemble(source, &inst_info).unwrap_err();
+
+        assert_eq!(error("jmp nowhere"), AsmError::new(1, AsmErrorKind::UndefinedLabel("nowhere".into())));
+        assert_eq!(error("a:\na:"), AsmError::new(2, AsmErrorKind::DuplicateLabel("a".into())));
+        assert_eq!(error("nop"), AsmError::new(1, AsmErrorKind::UnknownInstruction("nop".into())));
+        assert_eq!(error("st ra, 0x10"), AsmError::new(1, AsmErrorKind::InvalidOperands("st".into())));
+        assert_eq!(error("hlt ra"), AsmError::new(1, AsmErrorKind::InvalidOperands("hlt".into())));
+        assert_eq!(error("movb ra, 0x100"), AsmError::new(1, AsmErrorKind::ValueOutOfRange(0x100)));
+        assert_eq!(error("jmp $000001 - 2"), AsmError::new(1, AsmErrorKind::ValueOutOfRange(-1i64 as u32)));
+        assert_eq!(error(".byte 1, 2\n.org $0001\n.byte 3"), AsmError::new(3, AsmErrorKind::Overlap(1)));
+        assert_eq!(error(".org $FFFFFFFF\n.byte 1"), AsmError::new(1, AsmErrorKind::ValueOutOfRange(0xFFFF_FFFF)));
+        assert_eq!(error(".org $1000000"), AsmError::new(1, AsmErrorKind::ValueOutOfRange(0x100_0000)));
+        assert_eq!(error(".org $FFFFFF\n.byte 1, 2"), AsmError::new(2, AsmErrorKind::ValueOutOfRange(0x100_0000)));
+    }
+
+    #[test]
+    fn test_register_operand_order() {
+        let image = assemble_ok("mov rb, 0x1234\nmov ra, rb\nsub rb, ra").image;
+        let (mut cpu, mut pins) = setup(&image);
+
+        for _ in 0..3 {
+            try_step_instruction(&mut cpu, &mut pins).unwrap();
+        }
+
+        assert_eq!(cpu.state().ra, 0x1234);
+        assert_eq!(cpu.state().rb, 0x0000);
+    }
+
+    struct Port;
+
+    impl IOMappedDevice for Port {
+        fn io_address(&self) -> u8 {
+            0x12
+        }
+
+        fn io_read(&mut self) -> DeviceResult {
+            DeviceResult::Ok8(0)
+        }
+
+        fn io_write(&mut self, _data: u8) -> DeviceResult {
+            DeviceResult::Ok
+        }
+
+        fn io_name(&self) -> &str {
+            "Port"
+        }
+    }
+
+    // Every opcode in the table is assembled and executed once, the PC has to end up right behind the encoded
+    // instruction (or at the target for jumps). This keeps the operand sizes of the CPU and assembler in sync,
+    // and checks the cycle table measured with zero operands against real ones.
+    #[test]
+    fn test_every_encoding_runs() {
+        let inst_info = get_instructions();
+        let mut failures = Vec::new();
+
+        for info in &inst_info.info {
+            for mode in info.opcode.keys() {
+                let operands = match mode.as_str() {
+                    "M" => "",
+                    "R" => "rb",
+                    "I" => "0x12",
+                    "A" => "$018000",
+                    "RR" => "rb, rc",
+                    "RI" => "rb, 0x12",
+                    "RA" => "rb, $018000",
+                    _ => unreachable!("Unknown mode {mode}"),
+                };
+                let source = format!("{} {operands}", info.name);
+
+                // Returns and HLT don't continue behind the instruction.
+                if ["rts", "rti", "hlt"].contains(&info.name.as_str()) {
+                    continue;
+                }
+
+                let image = assemble(&source, &inst_info).unwrap().image;
+                let (mut cpu, mut pins) = setup(&image);
+                cpu.add_io_device(Port);
+
+                let cycles = match try_step_instruction(&mut cpu, &mut pins) {
+                    Ok(cycles) => cycles,
+                    Err(err) => {
+                        failures.push(format!("{source}: {err}"));
+                        continue;
+                    }
+                };
+
+                if info.cycles.get(mode) != Some(&(cycles as u8)) {
+                    failures.push(format!("{source}: took {cycles} cycles, the table has {:?}", info.cycles.get(mode)));
+                }
+
+                // Flags are clear after reset, so only the "not" branches are taken.
+                let expected = match info.name.as_str() {
+                    "jmp" | "jsr" => 0x01_8000,
+                    name if name.starts_with("bn") => 0x01_8000,
+                    _ => 0x00_0100 + image.len() as u32,
+                };
+
+                if cpu.pc() != expected {
+                    failures.push(format!("{source}: PC is {}, expected {expected:#08x}", cpu.pc()));
+                }
+            }
+        }
+
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn test_hello_world_runs() {
+        let assembly = assemble_ok(include_str!("../../gen/hello_world.8do"));
+
+        let mut machine = Machine::new(CPU::new(get_instructions(), Some(EmuOptions::new_value(1))));
+        machine.add_device(ROM::new(
+            ExtendedAddress::new_ext_address(0x00_0000),
+            ExtendedAddress::new_ext_address(assembly.image.len() as u32 - 1),
+            assembly.image,
+        ));
+        machine.add_device(RAM::new(
+            ExtendedAddress::new_ext_address(0x01_0000),
+            ExtendedAddress::new_ext_address(0x01_FFFF),
+        ));
+        let (out, output) = Out::with_buffer(0xA0);
+        machine.add_io_device(out);
+
+        assert_eq!(machine.run_for(10_000), Ok(CycleStatus::Halted(0x01)));
+        assert_eq!(machine.cpu().state().rb, "Hello, world!\n".len() as u16);
+        assert_eq!(output.text(), "Hello, world!\n");
+    }
+}
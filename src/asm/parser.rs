@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+
+use super::{
+    error::{AsmError, AsmErrorKind},
+    lexer::{NumberKind, Token},
+    register_code,
+};
+
+/// A value that might only be known once all labels are placed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Number(u32),
+    Label(String),
+}
+
+/// `base (+|-) constant ... (+ register)`, the register is added by the CPU at runtime.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressExpr {
+    pub base: Expr,
+    pub offset: i64,
+    pub register: Option<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    Register(u8),
+    Immediate(u32),
+    Address(AddressExpr),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    Label(String),
+    Org(u32),
+    /// `.byte`, `.word` and `.address`, `width` is the size of every value in bytes.
+    Data { width: u8, values: Vec<Expr> },
+    Bytes(Vec<u8>),
+    Instruction { name: String, operands: Vec<Operand> },
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    constants: HashMap<String, (u32, NumberKind)>,
+}
+
+/// Turns tokens into statements, each tagged with its line. Constants (`@name value`) are replaced while parsing,
+/// so they have to be defined before they are used.
+pub fn parse(tokens: Vec<(Token, usize)>) -> Result<Vec<(Statement, usize)>, AsmError> {
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        constants: HashMap::new(),
+    };
+    let mut statements = Vec::new();
+
+    while let Some((token, line)) = parser.next() {
+        let statement = match token {
+            Token::Newline => continue,
+            Token::Identifier(name) if parser.peek() == Some(&Token::Colon) => {
+                parser.next();
+                Statement::Label(name)
+            }
+            Token::Identifier(name) => parser.instruction(name.to_lowercase(), line)?,
+            Token::Constant(name) => {
+                let value = parser.number(line)?;
+                parser.constants.insert(name, value);
+                continue;
+            }
+            Token::Directive(name) => parser.directive(&name, line)?,
+            token => return Err(unexpected(&token, line)),
+        };
+
+        statements.push((statement, line));
+    }
+
+    Ok(statements)
+}
+
+fn unexpected(token: &Token, line: usize) -> AsmError {
+    let description = match token {
+        Token::Identifier(name) => format!("'{name}'"),
+        Token::Number(value, _) => format!("number {value:#x}"),
+        Token::Constant(name) => format!("'@{name}'"),
+        Token::Directive(name) => format!("'.{name}'"),
+        Token::String(_) => "string".into(),
+        Token::Colon => "':'".into(),
+        Token::Comma => "','".into(),
+        Token::Plus => "'+'".into(),
+        Token::Minus => "'-'".into(),
+        Token::Newline => "end of line".into(),
+    };
+
+    AsmError::new(line, AsmErrorKind::UnexpectedToken(description))
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<(Token, usize)> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn expect_next(&mut self, line: usize) -> Result<Token, AsmError> {
+        match self.next() {
+            Some((token, _)) => Ok(token),
+            None => Err(unexpected(&Token::Newline, line)),
+        }
+    }
+
+    // A number or a constant, constants keep the kind of the number they were defined with.
+    fn number(&mut self, line: usize) -> Result<(u32, NumberKind), AsmError> {
+        match self.expect_next(line)? {
+            Token::Number(value, kind) => Ok((value, kind)),
+            Token::Constant(name) => self.constant(&name, line),
+            token => Err(unexpected(&token, line)),
+        }
+    }
+
+    fn constant(&self, name: &str, line: usize) -> Result<(u32, NumberKind), AsmError> {
+        self.constants
+            .get(name)
+            .copied()
+            .ok_or_else(|| AsmError::new(line, AsmErrorKind::UnknownConstant(name.into())))
+    }
+
+    fn expr(&mut self, line: usize) -> Result<Expr, AsmError> {
+        match self.expect_next(line)? {
+            Token::Number(value, _) => Ok(Expr::Number(value)),
+            Token::Constant(name) => Ok(Expr::Number(self.constant(&name, line)?.0)),
+            Token::Identifier(name) if register_code(&name).is_none() => Ok(Expr::Label(name)),
+            token => Err(unexpected(&token, line)),
+        }
+    }
+
+    fn directive(&mut self, name: &str, line: usize) -> Result<Statement, AsmError> {
+        let width = match name {
+            "org" => return Ok(Statement::Org(self.number(line)?.0)),
+            "ascii" | "asciiz" => {
+                let mut bytes = match self.expect_next(line)? {
+                    Token::String(bytes) => bytes,
+                    token => return Err(unexpected(&token, line)),
+                };
+
+                if name == "asciiz" {
+                    bytes.push(0);
+                }
+
+                return Ok(Statement::Bytes(bytes));
+            }
+            "byte" => 1,
+            "word" => 2,
+            "address" => 3,
+            _ => return Err(AsmError::new(line, AsmErrorKind::UnknownDirective(name.into()))),
+        };
+
+        let mut values = vec![self.expr(line)?];
+
+        while self.peek() == Some(&Token::Comma) {
+            self.next();
+            values.push(self.expr(line)?);
+        }
+
+        Ok(Statement::Data { width, values })
+    }
+
+    fn instruction(&mut self, name: String, line: usize) -> Result<Statement, AsmError> {
+        let mut operands = Vec::new();
+
+        if self.peek() != Some(&Token::Newline) {
+            operands.push(self.operand(line)?);
+
+            while self.peek() == Some(&Token::Comma) {
+                self.next();
+                operands.push(self.operand(line)?);
+            }
+        }
+
+        match self.next() {
+            Some((Token::Newline, _)) | None => Ok(Statement::Instruction { name, operands }),
+            Some((token, _)) => Err(unexpected(&token, line)),
+        }
+    }
+
+    fn operand(&mut self, line: usize) -> Result<Operand, AsmError> {
+        let base = match self.expect_next(line)? {
+            Token::Identifier(name) => match register_code(&name) {
+                Some(code) => return Ok(Operand::Register(code)),
+                None => Expr::Label(name),
+            },
+            Token::Number(value, NumberKind::Address) => Expr::Number(value),
+            Token::Number(value, NumberKind::Immediate) => return Ok(Operand::Immediate(value)),
+            Token::Constant(name) => match self.constant(&name, line)? {
+                (value, NumberKind::Address) => Expr::Number(value),
+                (value, NumberKind::Immediate) => return Ok(Operand::Immediate(value)),
+            },
+            token => return Err(unexpected(&token, line)),
+        };
+
+        let mut address = AddressExpr {
+            base,
+            offset: 0,
+            register: None,
+        };
+
+        while let Some(sign @ (Token::Plus | Token::Minus)) = self.peek().cloned() {
+            self.next();
+
+            match self.expect_next(line)? {
+                // Only the full registers can be used as an offset.
+                Token::Identifier(name)
+                    if sign == Token::Plus && address.register.is_none() && register_code(&name).is_some_and(|code| code < 4) =>
+                {
+                    address.register = register_code(&name);
+                }
+                token => {
+                    let value = match token {
+                        Token::Number(value, _) => value,
+                        Token::Constant(name) => self.constant(&name, line)?.0,
+                        token => return Err(unexpected(&token, line)),
+                    } as i64;
+
+                    address.offset += if sign == Token::Plus { value } else { -value };
+                }
+            }
+        }
+
+        Ok(Operand::Address(address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::lexer::tokenize;
+
+    fn parse_source(source: &str) -> Result<Vec<Statement>, AsmError> {
+        Ok(parse(tokenize(source)?)?.into_iter().map(|(statement, _)| statement).collect())
+    }
+
+    #[test]
+    fn test_parse_operands() {
+        let statements = parse_source("@port 0xA0\n@table $0300\nout ra, @port\nmov rc, str + rb - 2\nmov rah, @table + 1").unwrap();
+
+        assert_eq!(
+            statements,
+            [
+                Statement::Instruction {
+                    name: "out".into(),
+                    operands: vec![Operand::Register(0), Operand::Immediate(0xA0)],
+                },
+                Statement::Instruction {
+                    name: "mov".into(),
+                    operands: vec![
+                        Operand::Register(2),
+                        Operand::Address(AddressExpr {
+                            base: Expr::Label("str".into()),
+                            offset: -2,
+                            register: Some(1),
+                        }),
+                    ],
+                },
+                Statement::Instruction {
+                    name: "mov".into(),
+                    operands: vec![
+                        Operand::Register(8),
+                        Operand::Address(AddressExpr {
+                            base: Expr::Number(0x300),
+                            offset: 1,
+                            register: None,
+                        }),
+                    ],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_directives() {
+        let statements = parse_source("start: .byte 0x00 .word $0200, start\n.asciiz \"hi\"").unwrap();
+
+        assert_eq!(
+            statements,
+            [
+                Statement::Label("start".into()),
+                Statement::Data {
+                    width: 1,
+                    values: vec![Expr::Number(0)],
+                },
+                Statement::Data {
+                    width: 2,
+                    values: vec![Expr::Number(0x200), Expr::Label("start".into())],
+                },
+                Statement::Bytes(b"hi\0".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse_source("mov ra, @missing").unwrap_err(),
+            AsmError::new(1, AsmErrorKind::UnknownConstant("missing".into()))
+        );
+        assert_eq!(
+            parse_source("\n.bytes 1").unwrap_err(),
+            AsmError::new(2, AsmErrorKind::UnknownDirective("bytes".into()))
+        );
+        assert_eq!(
+            parse_source("mov ra, label + rah").unwrap_err(),
+            AsmError::new(1, AsmErrorKind::UnexpectedToken("'rah'".into()))
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use std::fmt::Display;
+
+use super::ExtendedAddress;
+use crate::device::DeviceResult;
+
+/// Where the CPU was when an [`EmuError`] happened.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FaultInfo {
+    /// Address of the instruction that was executing.
+    pub pc: ExtendedAddress,
+    pub opcode: u8,
+    pub cycle: u8,
+    /// Address on the bus when the fault happened. For IO faults this is the IO port.
+    pub address: ExtendedAddress,
+}
+
+/// Faults that can be delivered to the guest instead of stopping the host, the value is the jump table vector
+/// they go through. IRQs use the vectors from [`FIRST_IRQ_VECTOR`] on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exception {
+    IllegalInstruction = 2,
+    BusError = 3,
+    RomWrite = 4,
+}
+
+/// The first vector free for IRQs, the ones before it belong to the reset, the NMI and the [`Exception`]s.
+pub const FIRST_IRQ_VECTOR: u8 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmuError {
+    UnknownOpcode(FaultInfo),
+    InvalidCycle(FaultInfo),
+    InvalidAddressingMode(FaultInfo),
+    Bus(FaultInfo, DeviceResult),
+    IO(FaultInfo, DeviceResult),
+}
+
+impl EmuError {
+    pub fn info(&self) -> &FaultInfo {
+        match self {
+            EmuError::UnknownOpcode(info)
+            | EmuError::InvalidCycle(info)
+            | EmuError::InvalidAddressingMode(info)
+            | EmuError::Bus(info, _)
+            | EmuError::IO(info, _) => info,
+        }
+    }
+
+    /// The exception this error is delivered as when faults trap to the guest, together with the faulting
+    /// address. IO faults are bus errors on the port. Invalid cycles are emulator bugs and always stop the host.
+    pub fn exception(&self) -> Option<(Exception, ExtendedAddress)> {
+        match self {
+            EmuError::UnknownOpcode(info) | EmuError::InvalidAddressingMode(info) => {
+                Some((Exception::IllegalInstruction, info.address))
+            }
+            EmuError::Bus(info, DeviceResult::ReadOnly) => Some((Exception::RomWrite, info.address)),
+            EmuError::Bus(info, _) | EmuError::IO(info, _) => Some((Exception::BusError, info.address)),
+            EmuError::InvalidCycle(_) => None,
+        }
+    }
+}
+
+impl Display for EmuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let info = self.info();
+
+        match self {
+            EmuError::UnknownOpcode(_) => write!(f, "Unknown opcode {:#04x}", info.opcode)?,
+            EmuError::InvalidCycle(_) => write!(f, "Opcode {:#04x} has no cycle {}", info.opcode, info.cycle)?,
+            EmuError::InvalidAddressingMode(_) => write!(f, "Invalid addressing mode for opcode {:#04x}", info.opcode)?,
+            EmuError::Bus(_, res) => write!(f, "Device error {:?} for address {}", res, info.address)?,
+            EmuError::IO(_, res) => write!(f, "IO device error {:?} for port {:#04x}", res, info.address.get_low_byte())?,
+        }
+
+        write!(f, " (PC: {}, opcode: {:#04x}, cycle: {})", info.pc, info.opcode, info.cycle)
+    }
+}
+
+impl std::error::Error for EmuError {}
@@ -1,7 +1,7 @@
-use crate::cpu::{AddressingMode, Flag, Pins, ReadWrite::Read, CPU};
+use crate::cpu::{AddressingMode, EmuError, Flag, Pins, ReadWrite::Read, CPU};
 
 impl CPU {
-    pub fn ADD(&mut self, pins: &mut Pins, mode: AddressingMode) {
+    pub fn ADD(&mut self, pins: &mut Pins, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
             AddressingMode::Immediate => match self.cycle {
                 1 => self.mode_immediate(pins, Some(false)),
@@ -23,7 +23,7 @@ impl CPU {
                     self.pc.increment_amount(2);
                     self.finish(pins);
                 }
-                _ => panic!("ADD(I) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
             AddressingMode::Register => {
                 let val1 = super::get_register(self.decode_register(self.instruction.metadata.reg0()));
@@ -39,10 +39,8 @@ impl CPU {
                 self.set_flag(Flag::Z, temp as u16, false, None);
                 self.set_flag(Flag::N, temp as u16, false, None);
 
-                let temp16 = self.temp16;
-                    super::set_register(self.decode_register(self.instruction.metadata.reg0()), temp16);
+                super::set_register(self.decode_register(self.instruction.metadata.reg0()), temp as u16);
 
-                self.pc.increment();
                 self.finish(pins);
             }
             AddressingMode::Absolute => match self.cycle {
@@ -96,16 +94,17 @@ impl CPU {
 
                     super::set_register(self.decode_register(self.instruction.metadata.reg0()), temp as u16);
 
-                    self.pc.increment();
                     self.finish(pins);
                 }
-                _ => panic!("ADD(A) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
-            _ => panic!()
+            _ => return Err(self.fault(EmuError::InvalidAddressingMode)),
         }
+
+        Ok(())
     }
 
-    pub fn ADDB(&mut self, pins: &mut Pins, mode: AddressingMode) {
+    pub fn ADDB(&mut self, pins: &mut Pins, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
             AddressingMode::Immediate => match self.cycle {
                 1 => self.mode_immediate(pins, Some(true)),
@@ -127,7 +126,7 @@ impl CPU {
                     self.pc.increment();
                     self.finish(pins);
                 }
-                _ => panic!("ADD(I) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
             AddressingMode::Register => {
                 let val1 = super::get_register(self.decode_register(self.instruction.metadata.reg0()));
@@ -144,7 +143,6 @@ impl CPU {
 
                 super::set_register(self.decode_register(self.instruction.metadata.reg0()), temp & 0xFF);
 
-                self.pc.increment();
                 self.finish(pins);
             }
             AddressingMode::Absolute => match self.cycle {
@@ -199,12 +197,56 @@ impl CPU {
 
                     super::set_register(self.decode_register(self.instruction.metadata.reg0()), temp & 0xFF);
 
-                    self.pc.increment();
                     self.finish(pins);
                 }
-                _ => panic!("ADD(A) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
-            _ => panic!()
+            _ => return Err(self.fault(EmuError::InvalidAddressingMode)),
         }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::instructions::testing::{setup, step_instruction, with_data};
+
+    #[test]
+    fn test_add_register_and_absolute() {
+        let program = with_data(
+            &[
+                210, 0x00, 0x00,                  // clc
+                59, 0x00, 0x00, 0x00, 0x05,       // mov ra, 0x0005
+                59, 0x00, 0x01, 0x00, 0x03,       // mov rb, 0x0003
+                27, 0x00, 0x10,                   // add ra, rb
+                10, 0x00, 0x00, 0x00, 0x02, 0x00, // add ra, [$000200]
+                93, 0x00, 0xB9,                   // addb ral, rbl
+                76, 0x00, 0x09, 0x00, 0x02, 0x02, // addb ral, [$000202]
+                59, 0x00, 0x03, 0x00, 0x2A,       // mov rd, 0x002A
+            ],
+            &[0x01, 0x00, 0x10],
+        );
+        let (mut cpu, mut pins) = setup(&program);
+
+        for _ in 0..4 {
+            step_instruction(&mut cpu, &mut pins);
+        }
+
+        // The sum is stored and the PC points right after the 3 byte instruction.
+        assert_eq!(cpu.ra.get_word(), 0x0008);
+        assert_eq!(cpu.pc, 0x00_0110);
+
+        step_instruction(&mut cpu, &mut pins);
+        assert_eq!(cpu.ra.get_word(), 0x0108);
+        assert_eq!(cpu.pc, 0x00_0116);
+
+        for _ in 0..3 {
+            step_instruction(&mut cpu, &mut pins);
+        }
+
+        assert_eq!(cpu.ra.get_low(), 0x1B);
+        assert_eq!(cpu.rd.get_word(), 0x002A);
+        assert_eq!(cpu.pc, 0x00_0124);
     }
 }
@@ -1,7 +1,7 @@
-use crate::cpu::{AddressingMode, Flag, Pins, ReadWrite::Read, CPU};
+use crate::cpu::{AddressingMode, EmuError, Flag, Pins, CPU};
 
 impl CPU {
-    pub fn CMP(&mut self, pins: &mut Pins, mode: AddressingMode) {
+    pub fn CMP(&mut self, pins: &mut Pins, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
             AddressingMode::Immediate => match self.cycle {
                 1 => self.mode_immediate(pins, Some(false)),
@@ -18,7 +18,7 @@ impl CPU {
                     self.pc.increment_amount(2);
                     self.finish(pins);
                 }
-                _ => panic!("CMP(I) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
             AddressingMode::Register => {
                 let reg0 = super::get_register(self.decode_register(self.instruction.metadata.reg0()));
@@ -34,7 +34,7 @@ impl CPU {
                 self.finish(pins);
             }
             AddressingMode::Absolute => match self.cycle {
-                1..=3 => self.mode_absolute(pins, Some(false), Some(CPU::mode_absolute_data)),
+                1..=3 => self.mode_absolute(pins, Some(false), Some(CPU::mode_absolute_data))?,
                 4 => {
                     let reg = super::get_register(self.decode_register(self.instruction.metadata.reg0()));
                     let (reg_n_flag, _) = reg.overflowing_sub(pins.data);
@@ -45,16 +45,17 @@ impl CPU {
                     self.flags.set(Flag::L, reg < pins.data);
 
                     self.word = false;
-                    self.pc.increment();
                     self.finish(pins);
                 }
-                _ => panic!("CMP(A) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
-            _ => panic!()
+            _ => return Err(self.fault(EmuError::InvalidAddressingMode)),
         }
+
+        Ok(())
     }
 
-    pub fn CMPB(&mut self, pins: &mut Pins, mode: AddressingMode) {
+    pub fn CMPB(&mut self, pins: &mut Pins, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
             AddressingMode::Immediate => match self.cycle {
                 1 => self.mode_immediate(pins, Some(true)),
@@ -71,7 +72,7 @@ impl CPU {
                     self.pc.increment();
                     self.finish(pins);
                 }
-                _ => panic!("CMPB(I) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
             AddressingMode::Register => {
                 let reg0 = super::get_register(self.decode_register(self.instruction.metadata.reg0())) as u8;
@@ -87,7 +88,7 @@ impl CPU {
                 self.finish(pins);
             }
             AddressingMode::Absolute => match self.cycle {
-                1..=3 => self.mode_absolute(pins, Some(true), Some(CPU::mode_absolute_data)),
+                1..=3 => self.mode_absolute(pins, Some(true), Some(CPU::mode_absolute_data))?,
                 4 => {
                     let reg = super::get_register(self.decode_register(self.instruction.metadata.reg0())) as u8;
                     let data = pins.data as u8;
@@ -99,12 +100,47 @@ impl CPU {
                     self.flags.set(Flag::L, reg < data);
 
                     self.word = false;
-                    self.pc.increment();
                     self.finish(pins);
                 }
-                _ => panic!("CMPB(A) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
-            _ => panic!()
+            _ => return Err(self.fault(EmuError::InvalidAddressingMode)),
         }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::{
+        instructions::testing::{setup, step_instruction, with_data},
+        Flag,
+    };
+
+    #[test]
+    fn test_cmp_absolute() {
+        let program = with_data(
+            &[
+                59, 0x00, 0x00, 0x00, 0x05,       // mov ra, 0x0005
+                33, 0x00, 0x00, 0x00, 0x02, 0x00, // cmp ra, [$000200]
+                99, 0x00, 0x09, 0x00, 0x02, 0x02, // cmpb ral, [$000202]
+                59, 0x00, 0x03, 0x00, 0x2A,       // mov rd, 0x002A
+            ],
+            &[0x00, 0x05, 0x10],
+        );
+        let (mut cpu, mut pins) = setup(&program);
+
+        step_instruction(&mut cpu, &mut pins);
+        step_instruction(&mut cpu, &mut pins);
+        assert!(cpu.flags.contains(Flag::Z));
+        assert_eq!(cpu.pc, 0x00_010B);
+
+        step_instruction(&mut cpu, &mut pins);
+        assert!(cpu.flags.contains(Flag::L) && !cpu.flags.contains(Flag::Z));
+        assert_eq!(cpu.pc, 0x00_0111);
+
+        step_instruction(&mut cpu, &mut pins);
+        assert_eq!(cpu.rd.get_word(), 0x002A);
     }
 }
@@ -1,23 +1,134 @@
-use crate::cpu::{AddressingMode, Flag, Pins, CPU};
+use crate::cpu::{AddressingMode, EmuError, Flag, Pins, CPU};
 
 impl CPU {
-    pub fn CLC(&mut self, pins: &mut Pins, _mode: AddressingMode) {
+    pub fn CLC(&mut self, pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
         self.flags.set(Flag::C, false);
         self.finish(pins);
+
+        Ok(())
     }
 
-    pub fn CLI(&mut self, pins: &mut Pins, _mode: AddressingMode) {
+    pub fn CLI(&mut self, pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
         self.flags.set(Flag::I, false);
         self.finish(pins);
+
+        Ok(())
     }
 
-    pub fn CLV(&mut self, pins: &mut Pins, _mode: AddressingMode) {
+    pub fn CLV(&mut self, pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
         self.flags.set(Flag::O, false);
         self.finish(pins);
+
+        Ok(())
     }
 
-    pub fn SEI(&mut self, pins: &mut Pins, _mode: AddressingMode) {
+    pub fn SEI(&mut self, pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
         self.flags.set(Flag::I, true);
         self.finish(pins);
+
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::cpu::{
+        instructions::testing::{setup_with_vectors, step_instruction},
+        Flag,
+    };
+
+    const IRQ_HANDLER: u32 = 0x00_0200;
+    const NMI_HANDLER: u32 = 0x00_0300;
+
+    // sei, clc, cli, clc with an rti at both handlers.
+    fn setup() -> (crate::cpu::CPU, crate::cpu::Pins) {
+        let mut program = vec![
+            225, 0x00, 0x00, // sei
+            210, 0x00, 0x00, // clc
+            216, 0x00, 0x00, // cli
+            210, 0x00, 0x00, // clc
+        ];
+        program.resize(0x100, 0);
+        program.extend_from_slice(&[92, 0x00, 0x00]);
+        program.resize(0x200, 0);
+        program.extend_from_slice(&[92, 0x00, 0x00]);
+
+        let (cpu, mut pins) = setup_with_vectors(&program, &[(0x1, NMI_HANDLER), (0x5, IRQ_HANDLER)]);
+        pins.irq.data = 0x5;
+
+        (cpu, pins)
+    }
+
+    #[test]
+    fn test_sei_masks_irq() {
+        let (mut cpu, mut pins) = setup();
+
+        step_instruction(&mut cpu, &mut pins);
+        pins.irq.req = true;
+
+        step_instruction(&mut cpu, &mut pins);
+        assert_eq!(cpu.pc, 0x00_0106);
+
+        // Interrupts are latched at the start of a cycle, so the request is taken after the instruction
+        // following cli. The I flag is set again on entry.
+        step_instruction(&mut cpu, &mut pins);
+        assert_eq!(cpu.pc, 0x00_0109);
+
+        step_instruction(&mut cpu, &mut pins);
+        assert_eq!(cpu.pc, IRQ_HANDLER);
+        assert!(cpu.flags.contains(Flag::I));
+
+        pins.irq.req = false;
+
+        step_instruction(&mut cpu, &mut pins);
+        assert_eq!(cpu.pc, 0x00_010C);
+        assert!(!cpu.flags.contains(Flag::I));
+    }
+
+    #[test]
+    fn test_sei_drops_latched_irq() {
+        let (mut cpu, mut pins) = setup();
+
+        // Latched while sei is being fetched, but sei masks it before it can be taken.
+        pins.irq.req = true;
+
+        step_instruction(&mut cpu, &mut pins);
+        assert_eq!(cpu.pc, 0x00_0103);
+        assert!(!cpu.irq_in_service);
+    }
+
+    #[test]
+    fn test_nmi_ignores_i_flag() {
+        let (mut cpu, mut pins) = setup();
+
+        step_instruction(&mut cpu, &mut pins);
+        pins.irq.nmi = true;
+
+        step_instruction(&mut cpu, &mut pins);
+        assert_eq!(cpu.pc, NMI_HANDLER);
+        assert!(cpu.nmi_in_service);
+    }
+
+    #[test]
+    fn test_nmi_wins_over_irq() {
+        let (mut cpu, mut pins) = setup();
+
+        step_instruction(&mut cpu, &mut pins);
+        step_instruction(&mut cpu, &mut pins);
+        pins.irq.req = true;
+        pins.irq.nmi = true;
+
+        step_instruction(&mut cpu, &mut pins);
+        assert_eq!(cpu.pc, NMI_HANDLER);
+
+        // The IRQ stays pending while the NMI is in service and is taken after its rti restores I.
+        pins.irq.nmi = false;
+        step_instruction(&mut cpu, &mut pins);
+        assert_eq!(cpu.pc, 0x00_0109);
+        assert!(!cpu.nmi_in_service);
+
+        step_instruction(&mut cpu, &mut pins);
+        assert_eq!(cpu.pc, IRQ_HANDLER);
+        assert!(!cpu.nmi_in_service);
+        assert!(cpu.irq_in_service);
     }
-}
\ No newline at end of file
+}
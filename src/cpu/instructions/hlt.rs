@@ -1,7 +1,9 @@
-use crate::cpu::{AddressingMode, Pins, CPU};
+use crate::cpu::{AddressingMode, EmuError, Pins, CPU};
 
 impl CPU {
-    pub fn HLT(&mut self, _pins: &mut Pins, _mode: AddressingMode) {
-        self.state = crate::cpu::CPUState::Halt;
+    pub fn HLT(&mut self, _pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
+        self.state = crate::cpu::CPUPhase::Halt;
+
+        Ok(())
     }
 }
@@ -1,11 +1,11 @@
 use crate::cpu::{
-    AddressingMode, Flag, Pins,
-    ReadWrite::{Read, Write},
+    AddressingMode, EmuError, Flag, Pins,
+    ReadWrite::Write,
     CPU,
 };
 
 impl CPU {
-    pub fn INC(&mut self, pins: &mut Pins, mode: AddressingMode) {
+    pub fn INC(&mut self, pins: &mut Pins, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
             AddressingMode::Register => {
                 let reg_num = self.instruction.metadata.reg0();
@@ -21,7 +21,7 @@ impl CPU {
                 self.finish(pins);
             }
             AddressingMode::Absolute => match self.cycle {
-                1..=3 => self.mode_absolute(pins, Some(false), Some(CPU::mode_absolute_data)),
+                1..=3 => self.mode_absolute(pins, Some(false), Some(CPU::mode_absolute_data))?,
                 4 => {
                     (pins.data, _) = pins.data.overflowing_add(1);
 
@@ -34,13 +34,15 @@ impl CPU {
                 5 => {
                     self.finish(pins);
                 }
-                _ => panic!("INC(A) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
-            _ => panic!("Invalid addressing mode for INC instruction"),
+            _ => return Err(self.fault(EmuError::InvalidAddressingMode)),
         }
+
+        Ok(())
     }
 
-    pub fn INCB(&mut self, pins: &mut Pins, mode: AddressingMode) {
+    pub fn INCB(&mut self, pins: &mut Pins, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
             AddressingMode::Register => {
                 let reg_num = self.instruction.metadata.reg0();
@@ -56,7 +58,7 @@ impl CPU {
                 self.finish(pins);
             }
             AddressingMode::Absolute => match self.cycle {
-                1..=3 => self.mode_absolute(pins, Some(true), Some(CPU::mode_absolute_data)),
+                1..=3 => self.mode_absolute(pins, Some(true), Some(CPU::mode_absolute_data))?,
                 4 => {
                     let mut val = pins.data as u8;
                     (val, _) = val.overflowing_add(1);
@@ -70,9 +72,11 @@ impl CPU {
                 5 => {
                     self.finish(pins);
                 }
-                _ => panic!("INCB(A) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
-            _ => panic!("Invalid addressing mode for INCB instruction"),
+            _ => return Err(self.fault(EmuError::InvalidAddressingMode)),
         }
+
+        Ok(())
     }
 }
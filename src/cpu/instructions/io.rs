@@ -1,7 +1,7 @@
-use crate::cpu::{AddressingMode, Pins, ReadWrite::{Read, Write}, CPU};
+use crate::cpu::{AddressingMode, EmuError, Pins, ReadWrite::{Read, Write}, CPU};
 
 impl CPU {
-    pub fn IN(&mut self, pins: &mut Pins, _mode: AddressingMode) {
+    pub fn IN(&mut self, pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
         match self.cycle {
             1 => {
                 pins.address = self.pc;
@@ -19,11 +19,13 @@ impl CPU {
                 pins.io_enable = false;
                 self.finish(pins);
             },
-            _ => panic!("IN tried to execute non-existent cycle {}", self.cycle),
+            _ => return Err(self.fault(EmuError::InvalidCycle)),
         }
+
+        Ok(())
     }
 
-    pub fn OUT(&mut self, pins: &mut Pins, _mode: AddressingMode) {
+    pub fn OUT(&mut self, pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
         match self.cycle {
             1 => {
                 pins.address = self.pc;
@@ -41,7 +43,9 @@ impl CPU {
                 pins.io_enable = false;
                 self.finish(pins);
             },
-            _ => panic!("OUT tried to execute non-existent cycle {}", self.cycle),
+            _ => return Err(self.fault(EmuError::InvalidCycle)),
         }
+
+        Ok(())
     }
 }
\ No newline at end of file
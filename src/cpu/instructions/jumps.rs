@@ -1,64 +1,66 @@
-use crate::cpu::{Flag, Pins, ReadWrite::{Read, Write}, CPU, AddressingMode};
+use crate::cpu::{EmuError, Flag, Pins, ReadWrite::Write, CPU, AddressingMode};
 
 impl CPU {
-    pub fn JMP(&mut self, pins: &mut Pins, _mode: AddressingMode) {
+    pub fn JMP(&mut self, pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
         match self.cycle {
-            1..=3 => self.mode_absolute(pins, Some(false), Some(CPU::mode_absolute_jmp)),
-            _ => panic!("JMP tried to execute non-existent cycle {}", self.cycle),
+            1..=3 => self.mode_absolute(pins, Some(false), Some(CPU::mode_absolute_jmp))?,
+            _ => return Err(self.fault(EmuError::InvalidCycle)),
         }
+
+        Ok(())
     }
 
-    pub fn BIZ(&mut self, pins: &mut Pins, _mode: AddressingMode) {
-        self.jump_if_flag(pins, Flag::Z, "BIZ");
+    pub fn BIZ(&mut self, pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
+        self.jump_if_flag(pins, Flag::Z)
     }
 
-    pub fn BIN(&mut self, pins: &mut Pins, _mode: AddressingMode) {
-        self.jump_if_flag(pins, Flag::N, "BIN");
+    pub fn BIN(&mut self, pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
+        self.jump_if_flag(pins, Flag::N)
     }
 
-    pub fn BIC(&mut self, pins: &mut Pins, _mode: AddressingMode) {
-        self.jump_if_flag(pins, Flag::C, "BIC");
+    pub fn BIC(&mut self, pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
+        self.jump_if_flag(pins, Flag::C)
     }
 
-    pub fn BIO(&mut self, pins: &mut Pins, _mode: AddressingMode) {
-        self.jump_if_flag(pins, Flag::O, "BIO");
+    pub fn BIO(&mut self, pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
+        self.jump_if_flag(pins, Flag::O)
     }
 
-    pub fn BIL(&mut self, pins: &mut Pins, _mode: AddressingMode) {
-        self.jump_if_flag(pins, Flag::L, "BIL");
+    pub fn BIL(&mut self, pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
+        self.jump_if_flag(pins, Flag::L)
     }
 
-    pub fn BIG(&mut self, pins: &mut Pins, _mode: AddressingMode) {
-        self.jump_if_flag(pins, Flag::G, "BIG");
+    pub fn BIG(&mut self, pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
+        self.jump_if_flag(pins, Flag::G)
     }
 
-    pub fn BNZ(&mut self, pins: &mut Pins, _mode: AddressingMode) {
-        self.jump_not_flag(pins, Flag::Z, "BNZ");
+    pub fn BNZ(&mut self, pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
+        self.jump_not_flag(pins, Flag::Z)
     }
 
-    pub fn BNN(&mut self, pins: &mut Pins, _mode: AddressingMode) {
-        self.jump_not_flag(pins, Flag::N, "BNN");
+    pub fn BNN(&mut self, pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
+        self.jump_not_flag(pins, Flag::N)
     }
 
-    pub fn BNC(&mut self, pins: &mut Pins, _mode: AddressingMode) {
-        self.jump_not_flag(pins, Flag::C, "BNC");
+    pub fn BNC(&mut self, pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
+        self.jump_not_flag(pins, Flag::C)
     }
 
-    pub fn BNO(&mut self, pins: &mut Pins, _mode: AddressingMode) {
-        self.jump_not_flag(pins, Flag::O, "BNO");
+    pub fn BNO(&mut self, pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
+        self.jump_not_flag(pins, Flag::O)
     }
 
-    pub fn BNL(&mut self, pins: &mut Pins, _mode: AddressingMode) {
-        self.jump_not_flag(pins, Flag::L, "BNL");
+    pub fn BNL(&mut self, pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
+        self.jump_not_flag(pins, Flag::L)
     }
 
-    pub fn BNG(&mut self, pins: &mut Pins, _mode: AddressingMode) {
-        self.jump_not_flag(pins, Flag::G, "BNG");
+    pub fn BNG(&mut self, pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
+        self.jump_not_flag(pins, Flag::G)
     }
 
-    pub fn JSR(&mut self, pins: &mut Pins, _mode: AddressingMode) {
+    pub fn JSR(&mut self, pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
         match self.cycle {
-            1..=3 => self.mode_absolute(pins, Some(false), Some(CPU::mode_absolute_jsr)),
+            1..=3 => self.mode_absolute(pins, Some(true), Some(CPU::mode_absolute_jsr))?,
             4 => {
                 self.sp.increment();
                 pins.address = self.sp.into();
@@ -78,47 +80,208 @@ impl CPU {
                 self.pc = self.temp_addr;
                 self.finish(pins);
             }
-            _ => panic!("JSR tried to execute non-existent cycle {}", self.cycle),
+            _ => return Err(self.fault(EmuError::InvalidCycle)),
         }
+
+        Ok(())
     }
 
-    pub fn RTS(&mut self, pins: &mut Pins, _mode: AddressingMode) {
-        match self.cycle {
-            1 => {
-                self.sp.decrement();
-                pins.address = self.sp.into();
-                pins.rw = Read;
-                self.word = false;
-            },
-            2 => {
-                self.sp.decrement();
-                self.temp_addr.set_low_byte(pins.data as u8);
+    pub fn RTS(&mut self, pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
+        self.pop_return_frame(pins)
+    }
 
-                pins.address = self.sp.into();
-                pins.rw = Read;
-            }
-            3 => {
-                self.sp.decrement();
-                self.temp_addr.set_hi_byte(pins.data as u8);
+    pub fn RTI(&mut self, pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
+        if self.cycle == 5 {
+            self.end_of_interrupt();
+        }
 
-                pins.address = self.sp.into();
-                pins.rw = Read;
-            }
-            4 => {
-                self.sp.decrement();
-                self.temp_addr.set_extended_value(pins.data as u8);
+        self.pop_return_frame(pins)
+    }
+}
 
-                pins.address = self.sp.into();
-                pins.rw = Read;
-            },
-            5 => {
-                self.sp.decrement();
-                self.flags.0.0 = pins.data;
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cpu::{
+            instructions::testing::{setup, setup_with_vectors, step_instruction},
+            ExtendedAddress, Flag,
+        },
+        device::DeviceResult,
+    };
 
-                self.pc = self.temp_addr;
-                self.finish(pins);
-            }
-            _ => panic!("RTS tried to execute non-existent cycle {}", self.cycle),
-        }
+    // Places `routine` at 0x000200, right after a 0x100 byte main program.
+    fn with_routine(main: &[u8], routine: &[u8]) -> Vec<u8> {
+        let mut program = main.to_vec();
+        program.resize(0x100, 0);
+        program.extend_from_slice(routine);
+        program
+    }
+
+    fn read_frame(cpu: &mut crate::cpu::CPU, start: u32) -> Vec<DeviceResult> {
+        cpu.word = false;
+        (0..4).map(|i| cpu.read(ExtendedAddress::new_ext_address(start + i))).collect()
+    }
+
+    #[test]
+    fn test_conditional_branches() {
+        let (mut cpu, mut pins) = setup(&[
+            59, 0x00, 0x00, 0x00, 0x00,        // mov ra, 0x0000
+            41, 0x00, 0x00, 0x00, 0x01,        // cmp ra, 0x0001
+            38, 0x00, 0x00, 0x00, 0x02, 0x00,  // biz $000200
+            139, 0x00, 0x00, 0x00, 0x02, 0x00, // bnz $000200
+        ]);
+
+        step_instruction(&mut cpu, &mut pins);
+        step_instruction(&mut cpu, &mut pins);
+
+        // Not taken, the next instruction follows right after the address.
+        step_instruction(&mut cpu, &mut pins);
+        assert_eq!(cpu.pc, 0x00_0110);
+
+        step_instruction(&mut cpu, &mut pins);
+        assert_eq!(cpu.pc, 0x00_0200);
+    }
+
+    #[test]
+    fn test_jsr_rts_frame() {
+        let program = with_routine(
+            &[
+                41, 0x00, 0x00, 0x00, 0x00,       // cmp ra, 0x0000
+                48, 0x00, 0x00, 0x00, 0x02, 0x00, // jsr $000200
+            ],
+            &[
+                210, 0x00, 0x00, // clc
+                249, 0x00, 0x00, // rts
+            ],
+        );
+        let (mut cpu, mut pins) = setup(&program);
+
+        step_instruction(&mut cpu, &mut pins);
+        assert!(cpu.flags.contains(Flag::Z));
+
+        assert_eq!(step_instruction(&mut cpu, &mut pins), 9);
+        assert_eq!(cpu.pc, 0x00_0200);
+        assert_eq!(u32::from(cpu.sp), 0x01_0004);
+        assert_eq!(
+            read_frame(&mut cpu, 0x01_0000),
+            [
+                DeviceResult::Ok8(Flag::Z.bits() as u8),
+                DeviceResult::Ok8(0x00),
+                DeviceResult::Ok8(0x01),
+                DeviceResult::Ok8(0x0B),
+            ]
+        );
+
+        step_instruction(&mut cpu, &mut pins);
+
+        assert_eq!(step_instruction(&mut cpu, &mut pins), 8);
+        assert_eq!(cpu.pc, 0x00_010B);
+        assert_eq!(u32::from(cpu.sp), 0x01_0000);
+        assert!(cpu.flags.contains(Flag::Z));
+    }
+
+    #[test]
+    fn test_interrupt_rti() {
+        let program = with_routine(
+            &[
+                41, 0x00, 0x00, 0x00, 0x00, // cmp ra, 0x0000
+                210, 0x00, 0x00,            // clc
+            ],
+            &[
+                59, 0x00, 0x01, 0x12, 0x34, // mov rb, 0x1234
+                92, 0x00, 0x00,             // rti
+            ],
+        );
+        let (mut cpu, mut pins) = setup_with_vectors(&program, &[(0x5, 0x00_0200)]);
+
+        step_instruction(&mut cpu, &mut pins);
+
+        pins.irq.req = true;
+        pins.irq.data = 0x5;
+
+        // The request is latched during clc and taken once it finishes, 4 cycles for clc and 9 for the
+        // interrupt sequence.
+        assert_eq!(step_instruction(&mut cpu, &mut pins), 13);
+        pins.irq.req = false;
+
+        assert_eq!(cpu.pc, 0x00_0200);
+        assert!(cpu.irq_in_service);
+        assert_eq!(
+            read_frame(&mut cpu, 0x01_0000),
+            [
+                DeviceResult::Ok8(Flag::Z.bits() as u8),
+                DeviceResult::Ok8(0x00),
+                DeviceResult::Ok8(0x01),
+                DeviceResult::Ok8(0x08),
+            ]
+        );
+
+        step_instruction(&mut cpu, &mut pins);
+        cpu.flags = Flag::empty();
+
+        assert_eq!(step_instruction(&mut cpu, &mut pins), 8);
+        assert_eq!(cpu.pc, 0x00_0108);
+        assert_eq!(u32::from(cpu.sp), 0x01_0000);
+        assert_eq!(cpu.rb.get_word(), 0x1234);
+        assert!(cpu.flags.contains(Flag::Z));
+        assert!(!cpu.irq_in_service);
+    }
+
+    #[test]
+    fn test_nmi_rti() {
+        let program = with_routine(
+            &[
+                210, 0x00, 0x00, // clc
+                210, 0x00, 0x00, // clc
+            ],
+            &[
+                92, 0x00, 0x00, // rti
+            ],
+        );
+        let (mut cpu, mut pins) = setup_with_vectors(&program, &[(0x1, 0x00_0200)]);
+
+        pins.irq.nmi = true;
+
+        assert_eq!(step_instruction(&mut cpu, &mut pins), 13);
+        assert_eq!(cpu.pc, 0x00_0200);
+        assert!(cpu.nmi_in_service);
+
+        // A still asserted NMI must not re-enter its own handler.
+        assert_eq!(step_instruction(&mut cpu, &mut pins), 8);
+        assert_eq!(cpu.pc, 0x00_0103);
+        assert!(!cpu.nmi_in_service);
+    }
+
+    #[test]
+    fn test_reset_in_handler() {
+        let program = with_routine(
+            &[
+                210, 0x00, 0x00, // clc
+                210, 0x00, 0x00, // clc
+            ],
+            &[
+                210, 0x00, 0x00, // clc
+            ],
+        );
+        let (mut cpu, mut pins) = setup_with_vectors(&program, &[(0x1, 0x00_0200), (0x5, 0x00_0200)]);
+
+        pins.irq.nmi = true;
+        step_instruction(&mut cpu, &mut pins);
+        pins.irq.nmi = false;
+        assert_eq!(cpu.pc, 0x00_0200);
+        assert!(cpu.nmi_in_service);
+
+        // The reset leaves the NMI handler without an RTI, an IRQ is taken again afterwards.
+        cpu.reset(&mut pins);
+        step_instruction(&mut cpu, &mut pins);
+        assert_eq!(cpu.pc, 0x00_0100);
+        assert!(!cpu.nmi_in_service);
+
+        pins.irq.req = true;
+        pins.irq.data = 0x5;
+
+        assert_eq!(step_instruction(&mut cpu, &mut pins), 13);
+        assert_eq!(cpu.pc, 0x00_0200);
+        assert!(cpu.irq_in_service);
     }
-}
\ No newline at end of file
+}
@@ -19,6 +19,7 @@ mod stack;
 mod flags;
 mod jumps;
 mod io;
+mod trap;
 
 pub(crate) fn get_register(register_ret: super::RegisterReturn) -> u16 {
     match register_ret {
@@ -36,4 +37,85 @@ pub(crate) fn set_register(register_ret: super::RegisterReturn, data: u16) {
             *reg8 = data as u8;
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+pub(crate) mod testing {
+    use crate::{
+        cpu::{CPUPhase, EmuError, ExtendedAddress, Pins, CPU},
+        device::{RAM, ROM},
+        info::get_instructions,
+    };
+
+    /// Builds a CPU with `program` loaded at 0x000100 of a 64KB ROM, a RAM page at 0x010000 for the stack
+    /// and runs it through the reset sequence.
+    pub(crate) fn setup(program: &[u8]) -> (CPU, Pins) {
+        setup_with_vectors(program, &[])
+    }
+
+    /// Same as [`setup`], but also fills in jump table entries as `(interrupt number, address)` pairs.
+    pub(crate) fn setup_with_vectors(program: &[u8], vectors: &[(u8, u32)]) -> (CPU, Pins) {
+        let mut data = vec![0x00, 0x01, 0x00];
+        data.resize(0x100, 0);
+        data.extend_from_slice(program);
+
+        for &(num, address) in vectors {
+            let entry = num as usize * 3;
+            data[entry] = (address >> 16) as u8;
+            data[entry + 1] = (address >> 8) as u8;
+            data[entry + 2] = address as u8;
+        }
+
+        let mut cpu = CPU::new(get_instructions(), None);
+        let mut pins = Pins::default();
+
+        cpu.add_device(ROM::new(
+            ExtendedAddress::new_ext_address(0x00_0000),
+            ExtendedAddress::new_ext_address(0x00_FFFF),
+            data,
+        ));
+        cpu.add_device(RAM::new(
+            ExtendedAddress::new_ext_address(0x01_0000),
+            ExtendedAddress::new_ext_address(0x01_FFFF),
+        ));
+
+        cpu.reset(&mut pins);
+
+        while cpu.state == CPUPhase::Reset {
+            step(&mut cpu, &mut pins).unwrap();
+        }
+
+        (cpu, pins)
+    }
+
+    /// Runs a single cycle and services the bus the same way the binary does.
+    pub(crate) fn step(cpu: &mut CPU, pins: &mut Pins) -> Result<(), EmuError> {
+        cpu.cycle(pins)?;
+        cpu.service_bus(pins)
+    }
+
+    /// Runs one whole instruction (fetch included) and returns the number of cycles it took.
+    pub(crate) fn try_step_instruction(cpu: &mut CPU, pins: &mut Pins) -> Result<usize, EmuError> {
+        let mut cycles = 0;
+
+        loop {
+            step(cpu, pins)?;
+            cycles += 1;
+
+            if cpu.state == CPUPhase::Fetch && cpu.cycle == 1 {
+                return Ok(cycles);
+            }
+        }
+    }
+
+    pub(crate) fn step_instruction(cpu: &mut CPU, pins: &mut Pins) -> usize {
+        try_step_instruction(cpu, pins).unwrap()
+    }
+
+    /// Places `data` at 0x000200, right after a 0x100 byte program.
+    pub(crate) fn with_data(program: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut program = program.to_vec();
+        program.resize(0x100, 0);
+        program.extend_from_slice(data);
+        program
+    }
+}
@@ -1,7 +1,7 @@
-use crate::cpu::{AddressingMode, Flag, Pins, ReadWrite::Read, CPU};
+use crate::cpu::{AddressingMode, EmuError, Flag, Pins, CPU};
 
 impl CPU {
-    pub fn MOV(&mut self, pins: &mut Pins, mode: AddressingMode) {
+    pub fn MOV(&mut self, pins: &mut Pins, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
             AddressingMode::Immediate => match self.cycle {
                 1 => self.mode_immediate(pins, Some(false)),
@@ -14,14 +14,14 @@ impl CPU {
                     self.pc.increment_amount(2);
                     self.finish(pins);
                 },
-                _ => panic!("MOV(I) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
             AddressingMode::Register => {
                 let r0 = self.instruction.metadata.reg0();
                 let r1 = self.instruction.metadata.reg1();
-                let val = super::get_register(self.decode_register(r0));
+                let val = super::get_register(self.decode_register(r1));
 
-                super::set_register(self.decode_register(r1), val);
+                super::set_register(self.decode_register(r0), val);
 
                 self.set_flag(Flag::Z, val, false, None);
                 self.set_flag(Flag::N, val, false, None);
@@ -29,7 +29,7 @@ impl CPU {
                 self.finish(pins);
             },
             AddressingMode::Absolute => match self.cycle {
-                1..=3 => self.mode_absolute(pins, Some(false), Some(CPU::mode_absolute_data)),
+                1..=3 => self.mode_absolute(pins, Some(false), Some(CPU::mode_absolute_data))?,
                 4 => {
                     super::set_register(self.decode_register(self.instruction.metadata.reg0()), pins.data);
 
@@ -38,13 +38,15 @@ impl CPU {
 
                     self.finish(pins);
                 }
-                _ => panic!("MOV(A) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
-            _ => panic!()
+            _ => return Err(self.fault(EmuError::InvalidAddressingMode)),
         }
+
+        Ok(())
     }
 
-    pub fn MOVB(&mut self, pins: &mut Pins, mode: AddressingMode) {
+    pub fn MOVB(&mut self, pins: &mut Pins, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
             AddressingMode::Immediate => match self.cycle {
                 1 => self.mode_immediate(pins, Some(true)),
@@ -58,14 +60,14 @@ impl CPU {
                     self.pc.increment();
                     self.finish(pins);
                 },
-                _ => panic!("MOVB(I) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
             AddressingMode::Register => {
                 let r0 = self.instruction.metadata.reg0();
                 let r1 = self.instruction.metadata.reg1();
-                let val = super::get_register(self.decode_register(r0));
+                let val = super::get_register(self.decode_register(r1));
 
-                super::set_register(self.decode_register(r1), val);
+                super::set_register(self.decode_register(r0), val);
                 self.update_regs();
 
                 self.set_flag(Flag::Z, val, true, None);
@@ -74,7 +76,7 @@ impl CPU {
                 self.finish(pins);
             },
             AddressingMode::Absolute => match self.cycle {
-                1..=3 => self.mode_absolute(pins, Some(true), Some(CPU::mode_absolute_data)),
+                1..=3 => self.mode_absolute(pins, Some(true), Some(CPU::mode_absolute_data))?,
                 4 => {
                     super::set_register(self.decode_register(self.instruction.metadata.reg0()), pins.data);
                     self.update_regs();
@@ -84,9 +86,37 @@ impl CPU {
 
                     self.finish(pins);
                 }
-                _ => panic!("MOVB(A) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
-            _ => panic!()
+            _ => return Err(self.fault(EmuError::InvalidAddressingMode)),
         }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::instructions::testing::{setup, step_instruction};
+
+    #[test]
+    fn test_mov_register() {
+        let (mut cpu, mut pins) = setup(&[
+            59, 0x00, 0x01, 0x12, 0x34, // mov rb, 0x1234
+            68, 0x00, 0x10,             // mov ra, rb
+            59, 0x00, 0x02, 0xAB, 0xCD, // mov rc, 0xABCD
+            134, 0x00, 0xD9,            // movb ral, rcl
+        ]);
+
+        step_instruction(&mut cpu, &mut pins);
+        step_instruction(&mut cpu, &mut pins);
+        assert_eq!(cpu.ra.get_word(), 0x1234);
+        assert_eq!(cpu.rb.get_word(), 0x1234);
+
+        step_instruction(&mut cpu, &mut pins);
+        step_instruction(&mut cpu, &mut pins);
+        assert_eq!(cpu.ra.get_word(), 0x12CD);
+        assert_eq!(cpu.rc.get_word(), 0xABCD);
+        assert_eq!(cpu.pc, 0x00_0110);
     }
 }
@@ -1,7 +1,7 @@
-use crate::cpu::{AddressingMode, Flag, Pins, ReadWrite::Read, CPU};
+use crate::cpu::{AddressingMode, EmuError, Flag, Pins, CPU};
 
 impl CPU {
-    pub fn OR(&mut self, pins: &mut Pins, mode: AddressingMode) {
+    pub fn OR(&mut self, pins: &mut Pins, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
             AddressingMode::Immediate => match self.cycle {
                 1 => self.mode_immediate(pins, Some(false)),
@@ -18,7 +18,7 @@ impl CPU {
                     self.word = false;
                     self.finish(pins);
                 },
-                _ => panic!("OR(I) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
             AddressingMode::Register => {
                 let res = super::get_register(
@@ -33,7 +33,7 @@ impl CPU {
                 self.finish(pins);
             },
             AddressingMode::Absolute => match self.cycle {
-                1..=3 => self.mode_absolute(pins, Some(false), Some(CPU::mode_absolute_data)),
+                1..=3 => self.mode_absolute(pins, Some(false), Some(CPU::mode_absolute_data))?,
                 4 => {
                     let reg_value = super::get_register(self.decode_register(self.instruction.metadata.reg0()));
                     let res = reg_value | pins.data;
@@ -42,18 +42,19 @@ impl CPU {
 
                     self.set_flag(Flag::Z, res, false, None);
                     self.set_flag(Flag::N, res, false, None);
-                    self.pc.increment_amount(2);
 
                     self.word = false;
                     self.finish(pins);
                 }
-                _ => panic!("OR(A) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
-            _ => panic!()
+            _ => return Err(self.fault(EmuError::InvalidAddressingMode)),
         }
+
+        Ok(())
     }
 
-    pub fn ORB(&mut self, pins: &mut Pins, mode: AddressingMode) {
+    pub fn ORB(&mut self, pins: &mut Pins, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
             AddressingMode::Immediate => match self.cycle {
                 1 => self.mode_immediate(pins, Some(true)),
@@ -69,7 +70,7 @@ impl CPU {
 
                     self.finish(pins);
                 },
-                _ => panic!("ORB(I) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
             AddressingMode::Register => {
                 let res = super::get_register(
@@ -84,7 +85,7 @@ impl CPU {
                 self.finish(pins);
             },
             AddressingMode::Absolute => match self.cycle {
-                1..=3 => self.mode_absolute(pins, Some(true), Some(CPU::mode_absolute_data)),
+                1..=3 => self.mode_absolute(pins, Some(true), Some(CPU::mode_absolute_data))?,
                 4 => {
                     let reg_value = super::get_register(self.decode_register(self.instruction.metadata.reg0()));
                     let res = reg_value & pins.data;
@@ -93,14 +94,44 @@ impl CPU {
 
                     self.set_flag(Flag::Z, res, true, None);
                     self.set_flag(Flag::N, res, true, None);
-                    self.pc.increment();
 
                     self.word = false;
                     self.finish(pins);
                 }
-                _ => panic!("ORB(A) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
-            _ => panic!()
+            _ => return Err(self.fault(EmuError::InvalidAddressingMode)),
         }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::instructions::testing::{setup, step_instruction, with_data};
+
+    #[test]
+    fn test_or_absolute() {
+        let program = with_data(
+            &[
+                59, 0x00, 0x00, 0x0F, 0xF0,        // mov ra, 0x0FF0
+                226, 0x00, 0x00, 0x00, 0x02, 0x00, // or ra, [$000200]
+                36, 0x00, 0x09, 0x00, 0x02, 0x02,  // orb ral, [$000202]
+                59, 0x00, 0x03, 0x00, 0x2A,        // mov rd, 0x002A
+            ],
+            &[0x3C, 0x3C, 0x0F],
+        );
+        let (mut cpu, mut pins) = setup(&program);
+
+        step_instruction(&mut cpu, &mut pins);
+        step_instruction(&mut cpu, &mut pins);
+        assert_eq!(cpu.ra.get_word(), 0x3FFC);
+        assert_eq!(cpu.pc, 0x00_010B);
+
+        step_instruction(&mut cpu, &mut pins);
+        step_instruction(&mut cpu, &mut pins);
+        assert_eq!(cpu.rd.get_word(), 0x002A);
+        assert_eq!(cpu.pc, 0x00_0116);
     }
 }
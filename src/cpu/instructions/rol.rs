@@ -1,11 +1,11 @@
 use crate::cpu::{
-    AddressingMode, Flag, Pins,
-    ReadWrite::{Read, Write},
+    AddressingMode, EmuError, Flag, Pins,
+    ReadWrite::Write,
     CPU,
 };
 
 impl CPU {
-    pub fn ROL(&mut self, pins: &mut Pins, mode: AddressingMode) {
+    pub fn ROL(&mut self, pins: &mut Pins, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
             AddressingMode::Register => {
                 let reg_num = self.instruction.metadata.reg0();
@@ -23,7 +23,7 @@ impl CPU {
                 self.finish(pins);
             }
             AddressingMode::Absolute => match self.cycle {
-                1..=3 => self.mode_absolute(pins, Some(false), Some(CPU::mode_absolute_data)),
+                1..=3 => self.mode_absolute(pins, Some(false), Some(CPU::mode_absolute_data))?,
                 4 => {
                     self.flags.set(Flag::C, (pins.data & 0x8000) > 0);
 
@@ -38,13 +38,15 @@ impl CPU {
                 5 => {
                     self.finish(pins);
                 }
-                _ => panic!("ROL(A) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
-            _ => panic!("Invalid addressing mode for ROL instruction"),
+            _ => return Err(self.fault(EmuError::InvalidAddressingMode)),
         }
+
+        Ok(())
     }
 
-    pub fn ROLB(&mut self, pins: &mut Pins, mode: AddressingMode) {
+    pub fn ROLB(&mut self, pins: &mut Pins, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
             AddressingMode::Register => {
                 let reg_num = self.instruction.metadata.reg0();
@@ -62,7 +64,7 @@ impl CPU {
                 self.finish(pins);
             }
             AddressingMode::Absolute => match self.cycle {
-                1..=3 => self.mode_absolute(pins, Some(true), Some(CPU::mode_absolute_data)),
+                1..=3 => self.mode_absolute(pins, Some(true), Some(CPU::mode_absolute_data))?,
                 4 => {
                     self.flags.set(Flag::C, (pins.data & 0x80) > 0);
 
@@ -77,9 +79,11 @@ impl CPU {
                 5 => {
                     self.finish(pins);
                 }
-                _ => panic!("ROLB(A) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
-            _ => panic!("Invalid addressing mode for ROLB instruction"),
+            _ => return Err(self.fault(EmuError::InvalidAddressingMode)),
         }
+
+        Ok(())
     }
 }
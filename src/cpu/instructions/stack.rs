@@ -1,11 +1,11 @@
 use crate::cpu::{
-    AddressingMode, Pins,
+    AddressingMode, EmuError, Flag, Pins,
     ReadWrite::{Read, Write},
     CPU,
 };
 
 impl CPU {
-    pub fn PSH(&mut self, pins: &mut Pins, mode: AddressingMode) {
+    pub fn PSH(&mut self, pins: &mut Pins, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
             AddressingMode::Immediate => match self.cycle {
                 1 => self.mode_immediate(pins, Some(false)),
@@ -20,25 +20,28 @@ impl CPU {
 
                     self.finish(pins);
                 }
-                _ => panic!("PSH(I) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
             AddressingMode::Register => match self.cycle {
-                0 => {
+                1 => {
                     pins.address = self.sp.into();
                     pins.data = super::get_register(self.decode_register(self.instruction.metadata.reg0()));
                     pins.rw = Write;
+                    self.word = true;
                 }
-                1 => {
+                2 => {
                     self.sp.increment_amount(2);
                     self.finish(pins);
                 }
-                _ => panic!("PSH(R) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
-            _ => panic!("Invalid addressing mode for PSH instruction"),
+            _ => return Err(self.fault(EmuError::InvalidAddressingMode)),
         }
+
+        Ok(())
     }
 
-    pub fn PSHB(&mut self, pins: &mut Pins, mode: AddressingMode) {
+    pub fn PSHB(&mut self, pins: &mut Pins, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
             AddressingMode::Immediate => match self.cycle {
                 1 => self.mode_immediate(pins, Some(true)),
@@ -52,38 +55,99 @@ impl CPU {
 
                     self.finish(pins);
                 }
-                _ => panic!("PSH(I) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
             AddressingMode::Register => match self.cycle {
-                0 => {
+                1 => {
                     pins.address = self.sp.into();
                     pins.data = super::get_register(self.decode_register(self.instruction.metadata.reg0()));
                     pins.rw = Write;
+                    self.word = false;
                 }
-                1 => {
+                2 => {
                     self.sp.increment();
                     self.finish(pins);
                 }
-                _ => panic!("PSH(R) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
-            _ => panic!("Invalid addressing mode for PSH instruction"),
+            _ => return Err(self.fault(EmuError::InvalidAddressingMode)),
         }
+
+        Ok(())
     }
 
-    // pub fn POP(&mut self, pins: &mut Pins) {
-    //     match self.cycle {
-    //         1 => {
-    //             self.sp.decrement();
-    //             pins.address = self.sp.into();
-    //             pins.rw = Read;
-    //         }
-    //         2 => {
-    //             *self.decode_register(self.instruction.metadata.reg0()) = pins.data;
-    //             self.finish(pins);
-    //         }
-    //         _ => panic!("POP tried to execute non-existent cycle {}", self.cycle),
-    //     }
-    // }
+    pub fn POP(&mut self, pins: &mut Pins, mode: AddressingMode) -> Result<(), EmuError> {
+        match mode {
+            AddressingMode::Register => match self.cycle {
+                1 => {
+                    self.sp.decrement_amount(2);
+                    pins.address = self.sp.into();
+                    pins.rw = Read;
+                    self.word = true;
+                }
+                2 => {
+                    super::set_register(self.decode_register(self.instruction.metadata.reg0()), pins.data);
+
+                    self.set_flag(Flag::Z, pins.data, false, None);
+                    self.set_flag(Flag::N, pins.data, false, None);
+
+                    self.finish(pins);
+                }
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
+            },
+            AddressingMode::Absolute => match self.cycle {
+                1..=3 => self.mode_absolute(pins, Some(false), Some(CPU::mode_absolute_pop))?,
+                4 => {
+                    pins.address = self.temp_addr;
+                    pins.rw = Write;
+                }
+                5 => {
+                    self.finish(pins);
+                }
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
+            },
+            _ => return Err(self.fault(EmuError::InvalidAddressingMode)),
+        }
+
+        Ok(())
+    }
+
+    pub fn POPB(&mut self, pins: &mut Pins, mode: AddressingMode) -> Result<(), EmuError> {
+        match mode {
+            AddressingMode::Register => match self.cycle {
+                1 => {
+                    self.sp.decrement();
+                    pins.address = self.sp.into();
+                    pins.rw = Read;
+                    self.word = false;
+                }
+                2 => {
+                    super::set_register(self.decode_register(self.instruction.metadata.reg0()), pins.data);
+                    self.update_regs();
+
+                    self.set_flag(Flag::Z, pins.data, true, None);
+                    self.set_flag(Flag::N, pins.data, true, None);
+
+                    self.finish(pins);
+                }
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
+            },
+            AddressingMode::Absolute => match self.cycle {
+                1..=3 => self.mode_absolute(pins, Some(true), Some(CPU::mode_absolute_pop))?,
+                4 => {
+                    pins.address = self.temp_addr;
+                    pins.rw = Write;
+                }
+                5 => {
+                    self.finish(pins);
+                }
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
+            },
+            _ => return Err(self.fault(EmuError::InvalidAddressingMode)),
+        }
+
+        Ok(())
+    }
 
     // pub fn CSK(&mut self, pins: &mut Pins) {
     //     match self.cycle {
@@ -91,3 +155,100 @@ impl CPU {
     //     }
     // }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cpu::{instructions::testing::{setup, step_instruction}, ExtendedAddress, Flag},
+        device::DeviceResult,
+    };
+
+    #[test]
+    fn test_psh_pop_register() {
+        let (mut cpu, mut pins) = setup(&[
+            59, 0x00, 0x00, 0x12, 0x34, // mov ra, 0x1234
+            61, 0x00, 0x00,             // psh ra
+            97, 0x00, 0x01,             // pop rb
+        ]);
+
+        step_instruction(&mut cpu, &mut pins);
+
+        assert_eq!(step_instruction(&mut cpu, &mut pins), 5);
+        assert_eq!(u32::from(cpu.sp), 0x01_0002);
+
+        assert_eq!(step_instruction(&mut cpu, &mut pins), 5);
+        assert_eq!(u32::from(cpu.sp), 0x01_0000);
+        assert_eq!(cpu.rb.get_word(), 0x1234);
+    }
+
+    #[test]
+    fn test_pshb_popb_register() {
+        let (mut cpu, mut pins) = setup(&[
+            59, 0x00, 0x00, 0x12, 0x34, // mov ra, 0x1234
+            127, 0x00, 0x00,            // pshb ra
+            131, 0x00, 0x01,            // popb rb
+        ]);
+
+        step_instruction(&mut cpu, &mut pins);
+
+        assert_eq!(step_instruction(&mut cpu, &mut pins), 5);
+        assert_eq!(u32::from(cpu.sp), 0x01_0001);
+
+        assert_eq!(step_instruction(&mut cpu, &mut pins), 5);
+        assert_eq!(u32::from(cpu.sp), 0x01_0000);
+        assert_eq!(cpu.rb.get_word(), 0x0034);
+    }
+
+    #[test]
+    fn test_pop_absolute() {
+        let (mut cpu, mut pins) = setup(&[
+            52, 0x00, 0x00, 0xBE, 0xEF,       // psh 0xBEEF
+            80, 0x00, 0x00, 0x01, 0x01, 0x00, // pop $010100
+        ]);
+
+        step_instruction(&mut cpu, &mut pins);
+
+        assert_eq!(step_instruction(&mut cpu, &mut pins), 8);
+        assert_eq!(u32::from(cpu.sp), 0x01_0000);
+        assert_eq!(cpu.pc, 0x00_010B);
+
+        cpu.word = true;
+        assert_eq!(cpu.read(ExtendedAddress::new_ext_address(0x01_0100)), DeviceResult::Ok16(0xBEEF));
+    }
+
+    #[test]
+    fn test_popb_absolute() {
+        let (mut cpu, mut pins) = setup(&[
+            118, 0x00, 0x00, 0xCA,            // pshb 0xCA
+            114, 0x00, 0x00, 0x01, 0x01, 0x00, // popb $010100
+        ]);
+
+        step_instruction(&mut cpu, &mut pins);
+
+        assert_eq!(step_instruction(&mut cpu, &mut pins), 8);
+        assert_eq!(u32::from(cpu.sp), 0x01_0000);
+
+        cpu.word = false;
+        assert_eq!(cpu.read(ExtendedAddress::new_ext_address(0x01_0100)), DeviceResult::Ok8(0xCA));
+        assert_eq!(cpu.read(ExtendedAddress::new_ext_address(0x01_0101)), DeviceResult::Ok8(0x00));
+    }
+
+    #[test]
+    fn test_pop_sets_flags() {
+        let (mut cpu, mut pins) = setup(&[
+            52, 0x00, 0x00, 0x00, 0x00, // psh 0x0000
+            97, 0x00, 0x02,             // pop rc
+            52, 0x00, 0x00, 0x80, 0x00, // psh 0x8000
+            97, 0x00, 0x02,             // pop rc
+        ]);
+
+        step_instruction(&mut cpu, &mut pins);
+        step_instruction(&mut cpu, &mut pins);
+        assert!(cpu.flags.contains(Flag::Z));
+
+        step_instruction(&mut cpu, &mut pins);
+        step_instruction(&mut cpu, &mut pins);
+        assert!(cpu.flags.contains(Flag::N));
+        assert_eq!(cpu.rc.get_word(), 0x8000);
+    }
+}
@@ -1,15 +1,15 @@
-use crate::cpu::{AddressingMode, Flag, Pins, ReadWrite::Read, CPU};
+use crate::cpu::{AddressingMode, EmuError, Flag, Pins, CPU};
 
 impl CPU {
     // FIXME: Subtraction is off by 1. Have to add 1? (Tested with D007 - CA7)
-    pub fn SUB(&mut self, pins: &mut Pins, mode: AddressingMode) {
+    pub fn SUB(&mut self, pins: &mut Pins, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
             AddressingMode::Immediate => match self.cycle {
                 1 => self.mode_immediate(pins, Some(false)),
                 2 => {
                     let val = super::get_register(self.decode_register(self.instruction.metadata.reg0()));
                     let temp =
-                        val as u32 + ((pins.data ^ 0xFFFF) + 1) as u32 + self.flags.contains(Flag::C) as u32;
+                        val as u32 + (pins.data ^ 0xFFFF).wrapping_add(1) as u32 + self.flags.contains(Flag::C) as u32;
 
                     self.flags.set(Flag::C, temp > 0xFFFF);
                     self.flags.set(
@@ -24,11 +24,11 @@ impl CPU {
                     self.pc.increment_amount(2);
                     self.finish(pins);
                 }
-                _ => panic!("SUB(I) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
             AddressingMode::Register => {
                 let val1 = super::get_register(self.decode_register(self.instruction.metadata.reg0()));
-                let val2 = (super::get_register(self.decode_register(self.instruction.metadata.reg1())) ^ 0xFFFF) + 1;
+                let val2 = (super::get_register(self.decode_register(self.instruction.metadata.reg1())) ^ 0xFFFF).wrapping_add(1);
                 let temp =
                         val1 as u32 + val2 as u32 + self.flags.contains(Flag::C) as u32;
 
@@ -42,15 +42,14 @@ impl CPU {
 
                 super::set_register(self.decode_register(self.instruction.metadata.reg0()), temp as u16);
 
-                self.pc.increment();
                 self.finish(pins);
             }
             AddressingMode::Absolute => match self.cycle {
-                1..=3 => self.mode_absolute(pins, Some(false), Some(CPU::mode_absolute_data)),
+                1..=3 => self.mode_absolute(pins, Some(false), Some(CPU::mode_absolute_data))?,
                 4 => {
                     let val = super::get_register(self.decode_register(self.instruction.metadata.reg0()));
                     let temp =
-                        val as u32 + ((pins.data ^ 0xFFFF) + 1) as u32 + self.flags.contains(Flag::C) as u32;
+                        val as u32 + (pins.data ^ 0xFFFF).wrapping_add(1) as u32 + self.flags.contains(Flag::C) as u32;
 
                     self.flags.set(Flag::C, temp > 0xFFFF);
                     self.flags.set(
@@ -62,16 +61,17 @@ impl CPU {
 
                     super::set_register(self.decode_register(self.instruction.metadata.reg0()), temp as u16);
 
-                    self.pc.increment();
                     self.finish(pins);
                 }
-                _ => panic!("SUB(A) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
-            _ => panic!()
+            _ => return Err(self.fault(EmuError::InvalidAddressingMode)),
         }
+
+        Ok(())
     }
 
-    pub fn SUBB(&mut self, pins: &mut Pins, mode: AddressingMode) {
+    pub fn SUBB(&mut self, pins: &mut Pins, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
             AddressingMode::Immediate => match self.cycle {
                 1 => self.mode_immediate(pins, Some(true)),
@@ -93,7 +93,7 @@ impl CPU {
                     self.pc.increment();
                     self.finish(pins);
                 }
-                _ => panic!("SUB(I) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
             AddressingMode::Register => {
                 let val1 = super::get_register(self.decode_register(self.instruction.metadata.reg0()));
@@ -110,11 +110,10 @@ impl CPU {
 
                 super::set_register(self.decode_register(self.instruction.metadata.reg0()), temp & 0xFF);
 
-                self.pc.increment();
                 self.finish(pins);
             }
             AddressingMode::Absolute => match self.cycle {
-                1..=3 => self.mode_absolute(pins, Some(true), Some(CPU::mode_absolute_data)),
+                1..=3 => self.mode_absolute(pins, Some(true), Some(CPU::mode_absolute_data))?,
                 4 => {
                     let val = super::get_register(self.decode_register(self.instruction.metadata.reg0()));
                     let temp =
@@ -130,12 +129,68 @@ impl CPU {
 
                     super::set_register(self.decode_register(self.instruction.metadata.reg0()), temp & 0xFF);
 
-                    self.pc.increment();
                     self.finish(pins);
                 }
-                _ => panic!("SUB(A) tried to execute non-existent cycle {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
-            _ => panic!()
+            _ => return Err(self.fault(EmuError::InvalidAddressingMode)),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::{
+        instructions::testing::{setup, step_instruction, with_data},
+        Flag,
+    };
+
+    #[test]
+    fn test_sub_register_and_absolute() {
+        let program = with_data(
+            &[
+                210, 0x00, 0x00,                   // clc
+                59, 0x00, 0x00, 0x00, 0x05,        // mov ra, 0x0005
+                83, 0x00, 0x00, 0x00, 0x00,        // sub ra, 0x0000
+                59, 0x00, 0x01, 0x00, 0x03,        // mov rb, 0x0003
+                60, 0x00, 0x10,                    // sub ra, rb
+                210, 0x00, 0x00,                   // clc
+                43, 0x00, 0x00, 0x00, 0x02, 0x00,  // sub ra, [$000200]
+                210, 0x00, 0x00,                   // clc
+                126, 0x00, 0xB9,                   // subb ral, rbl
+                109, 0x00, 0x09, 0x00, 0x02, 0x02, // subb ral, [$000202]
+                59, 0x00, 0x03, 0x00, 0x2A,        // mov rd, 0x002A
+            ],
+            &[0x00, 0x01, 0x10],
+        );
+        let (mut cpu, mut pins) = setup(&program);
+
+        // Subtracting 0 negates it to 0x10000, which has to wrap around.
+        for _ in 0..3 {
+            step_instruction(&mut cpu, &mut pins);
         }
+
+        assert_eq!(cpu.ra.get_word(), 0x0005);
+        assert!(!cpu.flags.contains(Flag::C));
+
+        step_instruction(&mut cpu, &mut pins);
+        step_instruction(&mut cpu, &mut pins);
+        assert_eq!(cpu.ra.get_word(), 0x0002);
+        assert_eq!(cpu.pc, 0x00_0115);
+
+        step_instruction(&mut cpu, &mut pins);
+        step_instruction(&mut cpu, &mut pins);
+        assert_eq!(cpu.ra.get_word(), 0x0001);
+        assert_eq!(cpu.pc, 0x00_011E);
+
+        for _ in 0..4 {
+            step_instruction(&mut cpu, &mut pins);
+        }
+
+        assert_eq!(cpu.ra.get_low(), 0xEE);
+        assert_eq!(cpu.rd.get_word(), 0x002A);
+        assert_eq!(cpu.pc, 0x00_012F);
     }
 }
@@ -0,0 +1,8 @@
+use crate::cpu::{AddressingMode, EmuError, Pins, CPU};
+
+impl CPU {
+    // Every opcode without an instruction in the dispatch table ends up here.
+    pub fn TRAP(&mut self, _pins: &mut Pins, _mode: AddressingMode) -> Result<(), EmuError> {
+        Err(self.fault(EmuError::UnknownOpcode))
+    }
+}
@@ -1,13 +1,22 @@
 #![allow(dead_code, reason = "Missing instructions which use these methods")]
 
+mod error;
 pub mod instructions;
+mod state;
+mod timing;
+mod watch;
 use std::{cell::RefCell, fmt::Display, rc::Rc};
 
 use crate::{device::{DeviceResult, IOMappedDevice}, info::InstructionInfoFile};
 use bitflags::bitflags;
 use proc_bitfield::bitfield;
+use serde::{Deserialize, Serialize};
 
 use super::device::AddressMappedDevice;
+pub use error::{EmuError, Exception, FaultInfo, FIRST_IRQ_VECTOR};
+pub use state::{CpuSnapshot, CpuState};
+pub use timing::cycle_table;
+pub use watch::{AddressSpace, BusAccess, WatchAction, WatchId, WatchKind};
 
 type AddressMappedDevices = Vec<Rc<RefCell<dyn AddressMappedDevice>>>;
 type IOMappedDevices = Vec<Rc<RefCell<dyn IOMappedDevice>>>;
@@ -27,6 +36,27 @@ bitflags! {
     }
 }
 
+// Prints the flags as `ZNCOLGI`, with a `-` for every flag that is cleared.
+impl Display for Flag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = [
+            (Flag::Z, 'Z'),
+            (Flag::N, 'N'),
+            (Flag::C, 'C'),
+            (Flag::O, 'O'),
+            (Flag::L, 'L'),
+            (Flag::G, 'G'),
+            (Flag::I, 'I'),
+        ];
+
+        for (flag, name) in names {
+            write!(f, "{}", if self.contains(flag) { name } else { '-' })?;
+        }
+
+        Ok(())
+    }
+}
+
 bitfield! {
     #[derive(Debug, Default, Clone, Copy)]
     struct CPUMetadata(pub u16) {
@@ -41,7 +71,7 @@ bitfield! {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 enum AddressingMode {
     Implied,
     Immediate,
@@ -49,8 +79,9 @@ enum AddressingMode {
     Absolute,
 }
 
-#[derive(Debug, Default, PartialEq, Eq)]
-enum CPUState {
+/// Which part of the state machine the CPU is in, together with `cycle` this says exactly where execution is.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CPUPhase {
     #[default]
     Reset,
     Fetch,
@@ -59,12 +90,31 @@ enum CPUState {
     Interrupt,
 }
 
-#[derive(Debug, Default, PartialEq, Eq)]
+/// What [`CPU::cycle`] reports back to the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CycleStatus {
+    Running,
+    /// The CPU executed HLT with `exit_on_hlt` set, carrying the exit code from RA.
+    Halted(u16),
+    /// An access hit a pausing watchpoint. Only [`Machine`](crate::machine::Machine) reports this, after the
+    /// bus was serviced.
+    Watchpoint(BusAccess),
+}
+
+/// A device by the order it was added to the CPU in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum DeviceId {
+    Memory(usize),
+    Io(usize),
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 enum InterruptStatus {
     #[default]
     None,
     Normal,
     NonMaskable,
+    Exception,
 }
 
 enum RegisterReturn<'a> {
@@ -139,7 +189,7 @@ struct Instruction {
     pub metadata: CPUMetadata,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct IRQ {
     pub req: bool,
     pub nmi: bool,
@@ -147,7 +197,7 @@ pub struct IRQ {
     pub data: u8, // Limited to 4 bits
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct StackAddress {
     value: u16,
     page: u8
@@ -162,6 +212,14 @@ impl StackAddress {
         }
     }
 
+    pub fn get_value(&self) -> u16 {
+        self.value
+    }
+
+    pub fn get_page(&self) -> u8 {
+        self.page
+    }
+
     fn set(&mut self, address: u16) {
         self.value = address;
     }
@@ -178,6 +236,10 @@ impl StackAddress {
         (self.value, _) = self.value.overflowing_sub(1);
     }
 
+    fn decrement_amount(&mut self, amount: u16) {
+        (self.value, _) = self.value.overflowing_sub(amount);
+    }
+
     fn offset(&mut self, offset: i8) {
         if offset < 0 {
             self.value -= offset.unsigned_abs() as u16;
@@ -217,7 +279,8 @@ impl From<StackAddress> for ExtendedAddress {
     }
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "u32", into = "u32")]
 pub struct ExtendedAddress {
     value: u32,
 }
@@ -334,6 +397,12 @@ impl From<ExtendedAddress> for u32 {
     }
 }
 
+impl From<u32> for ExtendedAddress {
+    fn from(value: u32) -> Self {
+        ExtendedAddress::new_ext_address(value)
+    }
+}
+
 impl Display for ExtendedAddress {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:#08x}", self.value)
@@ -346,14 +415,14 @@ impl PartialEq<u32> for ExtendedAddress {
     }
 }
 
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ReadWrite {
     #[default]
     Read,
     Write,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Pins {
     pub address: ExtendedAddress,
     pub data: u16,
@@ -366,32 +435,163 @@ pub struct Pins {
     pub irq: IRQ,
 }
 
+/// Where the CPU gets the address of the first instruction from after a reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetVector {
+    /// Read the 3 byte vector from memory, the jump table entry at `$000000` by default.
+    Memory(ExtendedAddress),
+    /// Start at this address without reading memory.
+    Fixed(ExtendedAddress),
+}
+
+impl Default for ResetVector {
+    fn default() -> Self {
+        ResetVector::Memory(ExtendedAddress::new_16bit_address(0x0000))
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct EmuOptions {
     flags: u8,
+    reset_vector: ResetVector,
 }
 
 #[allow(dead_code)]
 impl EmuOptions {
     pub fn new() -> Self {
-        Self { flags: 0 }
+        Self::default()
     }
 
     pub fn new_value(flags: u8) -> Self {
-        Self { flags }
+        Self { flags, ..Default::default() }
+    }
+
+    pub fn reset_vector(&self) -> ResetVector {
+        self.reset_vector
+    }
+
+    pub fn set_reset_vector(&mut self, reset_vector: ResetVector) {
+        self.reset_vector = reset_vector;
     }
 
     fn exit_on_hlt(&self) -> bool {
         self.flags & 1 > 0
     }
 
-    fn set_exit_on_hlt(&mut self) {
+    pub fn set_exit_on_hlt(&mut self) {
         self.flags |= 1;
     }
 
-    fn unset_exit_on_hlt(&mut self) {
+    pub fn unset_exit_on_hlt(&mut self) {
         self.flags &= 0b11111110;
     }
+
+    /// Deliver illegal instructions and bus faults to the guest as [`Exception`]s instead of returning them.
+    fn trap_faults(&self) -> bool {
+        self.flags & 2 > 0
+    }
+
+    pub fn set_trap_faults(&mut self) {
+        self.flags |= 2;
+    }
+
+    pub fn unset_trap_faults(&mut self) {
+        self.flags &= 0b11111101;
+    }
+}
+
+type InstructionFn = fn(&mut CPU, &mut Pins, AddressingMode) -> Result<(), EmuError>;
+type DispatchTable = Vec<(InstructionFn, AddressingMode)>;
+
+fn instruction_fn(name: &str) -> Option<InstructionFn> {
+    let func: InstructionFn = match name {
+        "mov" => CPU::MOV,
+        "movb" => CPU::MOVB,
+        "st" => CPU::ST,
+        "stb" => CPU::STB,
+        "and" => CPU::AND,
+        "andb" => CPU::ANDB,
+        "or" => CPU::OR,
+        "orb" => CPU::ORB,
+        "xor" => CPU::XOR,
+        "xorb" => CPU::XORB,
+        "psh" => CPU::PSH,
+        "pshb" => CPU::PSHB,
+        "pop" => CPU::POP,
+        "popb" => CPU::POPB,
+        "add" => CPU::ADD,
+        "addb" => CPU::ADDB,
+        "sub" => CPU::SUB,
+        "subb" => CPU::SUBB,
+        "cmp" => CPU::CMP,
+        "cmpb" => CPU::CMPB,
+        "inc" => CPU::INC,
+        "incb" => CPU::INCB,
+        "dec" => CPU::DEC,
+        "decb" => CPU::DECB,
+        "sbl" => CPU::SBL,
+        "sblb" => CPU::SBLB,
+        "sbr" => CPU::SBR,
+        "sbrb" => CPU::SBRB,
+        "rol" => CPU::ROL,
+        "rolb" => CPU::ROLB,
+        "ror" => CPU::ROR,
+        "rorb" => CPU::RORB,
+        "clc" => CPU::CLC,
+        "cli" => CPU::CLI,
+        "clv" => CPU::CLV,
+        "sei" => CPU::SEI,
+        "jmp" => CPU::JMP,
+        "jsr" => CPU::JSR,
+        "biz" => CPU::BIZ,
+        "bin" => CPU::BIN,
+        "bic" => CPU::BIC,
+        "bio" => CPU::BIO,
+        "bil" => CPU::BIL,
+        "big" => CPU::BIG,
+        "bnz" => CPU::BNZ,
+        "bnn" => CPU::BNN,
+        "bnc" => CPU::BNC,
+        "bno" => CPU::BNO,
+        "bnl" => CPU::BNL,
+        "bng" => CPU::BNG,
+        "rts" => CPU::RTS,
+        "rti" => CPU::RTI,
+        "in" => CPU::IN,
+        "out" => CPU::OUT,
+        "hlt" => CPU::HLT,
+        _ => return None,
+    };
+
+    Some(func)
+}
+
+fn addressing_mode(mode: &str) -> Option<AddressingMode> {
+    match mode {
+        "M" => Some(AddressingMode::Implied),
+        "I" => Some(AddressingMode::Immediate),
+        "R" => Some(AddressingMode::Register),
+        "A" => Some(AddressingMode::Absolute),
+        _ => None,
+    }
+}
+
+// Every opcode starts out as a trap, only the opcodes listed in the instruction info (with an implementation
+// and a known addressing mode) are replaced with their handler.
+fn build_dispatch_table(inst_info: &InstructionInfoFile) -> DispatchTable {
+    let mut table: DispatchTable = vec![(CPU::TRAP, AddressingMode::Implied); 256];
+
+    for (&opcode, entry) in &inst_info.opcodes {
+        let Some((name, mode)) = entry.split_once('|') else {
+            continue;
+        };
+
+        if let (Some(func), Some(mode)) = (instruction_fn(name), addressing_mode(mode)) {
+            table[opcode as usize] = (func, mode);
+        }
+    }
+
+    table
 }
 
 #[derive(Default)]
@@ -402,19 +602,30 @@ pub struct CPU {
     rd: Register,
     flags: Flag,
     pc: ExtendedAddress,
+    inst_pc: ExtendedAddress,
     sp: StackAddress,
     devices: AddressMappedDevices,
     io_devices: IOMappedDevices,
     instruction: Instruction,
-    state: CPUState,
+    state: CPUPhase,
     int_status: InterruptStatus,
     int_num: u8,
+    irq_in_service: bool,
+    nmi_in_service: bool,
+    exception_in_service: bool,
+    fault_address: ExtendedAddress,
     cycle: u8,
+    cycles: u64,
+    instructions: u64,
     temp16: u16,
     temp_addr: ExtendedAddress,
     options: EmuOptions,
     word: bool,
-    inst_info: InstructionInfoFile
+    inst_info: InstructionInfoFile,
+    dispatch: DispatchTable,
+    watchpoints: Vec<watch::Watchpoint>,
+    watch_hit: Option<BusAccess>,
+    next_watch_id: u32,
 }
 
 // Public
@@ -423,6 +634,7 @@ impl CPU {
         Self {
             cycle: 1,
             options: options.unwrap_or_default(),
+            dispatch: build_dispatch_table(&inst_info),
             inst_info,
             ..Default::default()
         }
@@ -444,34 +656,105 @@ impl CPU {
         self.io_devices.push(device);
     }
 
-    pub fn cycle(&mut self, pins: &mut Pins) {
-        if pins.irq.req && self.int_status == InterruptStatus::None {
-            self.int_status = InterruptStatus::Normal;
-        } else if pins.irq.nmi && self.int_status == InterruptStatus::None {
-            self.int_status = InterruptStatus::NonMaskable;
+    /// Ticks every device, memory mapped ones first, and returns the ones that assert their interrupt line
+    /// together with their interrupt numbers.
+    pub(crate) fn tick_devices(&mut self, cycles: u64) -> Vec<(DeviceId, u8)> {
+        let mut requests = Vec::new();
+
+        for (index, device) in self.devices.iter().enumerate() {
+            let mut device = device.borrow_mut();
+            device.tick(cycles);
+            requests.extend(device.interrupt().map(|number| (DeviceId::Memory(index), number)));
+        }
+
+        for (index, device) in self.io_devices.iter().enumerate() {
+            let mut device = device.borrow_mut();
+            device.tick(cycles);
+            requests.extend(device.interrupt().map(|number| (DeviceId::Io(index), number)));
+        }
+
+        requests
+    }
+
+    pub(crate) fn acknowledge_device(&mut self, id: DeviceId) {
+        match id {
+            DeviceId::Memory(index) => self.devices[index].borrow_mut().acknowledge(),
+            DeviceId::Io(index) => self.io_devices[index].borrow_mut().acknowledge(),
         }
+    }
+
+    /// Whether the CPU just acknowledged a maskable interrupt and reads its number from `IRQ::data` next cycle.
+    pub fn acknowledging_irq(&self, pins: &Pins) -> bool {
+        pins.irq.ack && self.state == CPUPhase::Interrupt && self.int_status == InterruptStatus::Normal
+    }
 
-        match self.state {
-            CPUState::Reset => self.reset_handler(pins),
-            CPUState::Fetch => self.fetch_handler(pins),
-            CPUState::Execute => self.execute_handler(pins),
-            CPUState::Interrupt => self.interrupt_handler(pins),
-            CPUState::Halt => {
+    /// Runs a single cycle. Once HLT executed the CPU stays halted, with `exit_on_hlt` set every cycle from then
+    /// on reports [`CycleStatus::Halted`] so the host can stop, otherwise it just idles.
+    pub fn cycle(&mut self, pins: &mut Pins) -> Result<CycleStatus, EmuError> {
+        self.cycles += 1;
+        self.latch_interrupt(pins);
+
+        let result = match self.state {
+            CPUPhase::Reset => self.reset_handler(pins),
+            CPUPhase::Fetch => self.fetch_handler(pins),
+            CPUPhase::Execute => self.execute_handler(pins),
+            CPUPhase::Interrupt => self.interrupt_handler(pins),
+            CPUPhase::Halt => {
                 if self.options.exit_on_hlt() {
-                    println!("Flags: {:?}", self.flags);
-                    std::process::exit(0)
-                } else {
-                    self.cycle -= 1;
+                    return Ok(CycleStatus::Halted(self.ra.get_word()));
                 }
+
+                return Ok(CycleStatus::Running);
             }
+        };
+
+        if let Err(err) = result {
+            self.raise_exception(pins, err)?;
+            return Ok(CycleStatus::Running);
         }
 
-        self.cycle += 1
+        self.cycle += 1;
+
+        Ok(CycleStatus::Running)
+    }
+
+    /// Services the memory and IO bus requests the last cycle put on the pins, this has to run after every
+    /// [`CPU::cycle`].
+    pub fn service_bus(&mut self, pins: &mut Pins) -> Result<(), EmuError> {
+        match self.access_bus(pins) {
+            Err(err) => self.raise_exception(pins, err),
+            ok => ok,
+        }
     }
 
-    pub fn read(&self, address: ExtendedAddress) -> DeviceResult {
+    pub fn read(&mut self, address: ExtendedAddress) -> DeviceResult {
+        let res = self.peek(address, self.word);
+
+        let (data, word) = match res {
+            DeviceResult::Ok8(data) => (data as u16, false),
+            DeviceResult::Ok16(data) => (data, true),
+            _ => return res,
+        };
+
+        self.check_watchpoints(AddressSpace::Memory, address.into(), ReadWrite::Read, data, word);
+        res
+    }
+
+    pub fn write(&mut self, address: ExtendedAddress, data: u16) -> DeviceResult {
+        let res = self.poke(address, data, self.word);
+
+        if res == DeviceResult::Ok {
+            self.check_watchpoints(AddressSpace::Memory, address.into(), ReadWrite::Write, data, self.word);
+        }
+
+        res
+    }
+
+    /// Reads a byte or word from the address mapped devices, independent of the width the current instruction
+    /// uses. Meant for debuggers and other tools looking at memory between cycles.
+    pub fn peek(&self, address: ExtendedAddress, word: bool) -> DeviceResult {
         for device in &self.devices {
-            let res = device.borrow_mut().read(address, self.word);
+            let res = device.borrow_mut().read(address, word);
 
             if let DeviceResult::NotMyAddress = res {
                 continue;
@@ -483,9 +766,10 @@ impl CPU {
         DeviceResult::NoValidDevice
     }
 
-    pub fn write(&mut self, address: ExtendedAddress, data: u16) -> DeviceResult {
+    /// Writes a byte or word to the address mapped devices, see [`CPU::peek`].
+    pub fn poke(&mut self, address: ExtendedAddress, data: u16, word: bool) -> DeviceResult {
         for device in &self.devices {
-            let res = device.borrow_mut().write(address, data, self.word);
+            let res = device.borrow_mut().write(address, data, word);
 
             if let DeviceResult::NotMyAddress = res {
                 continue;
@@ -497,32 +781,76 @@ impl CPU {
         DeviceResult::NoValidDevice
     }
 
-    pub fn read_io(&self, address: u8) -> DeviceResult {
-        for device in &self.io_devices {
-            let mut dev_unwrapped = device.borrow_mut();
+    pub fn read_io(&mut self, address: u8) -> DeviceResult {
+        let res = self.io_device(address).map_or(DeviceResult::NoValidDevice, |device| {
+            let mut device = device.borrow_mut();
+            let register = address.wrapping_sub(device.io_address());
+            device.io_read_register(register)
+        });
 
-            if dev_unwrapped.io_address() != address {
-                continue;
-            }
-
-            return dev_unwrapped.io_read();
+        if let DeviceResult::Ok8(data) = res {
+            self.check_watchpoints(AddressSpace::Io, address as u32, ReadWrite::Read, data as u16, false);
         }
 
-        DeviceResult::NoValidDevice
+        res
     }
 
     pub fn write_io(&mut self, address: u8, data: u8) -> DeviceResult {
-        for device in &self.io_devices {
-            let mut dev_unwrapped = device.borrow_mut();
+        let res = self.io_device(address).map_or(DeviceResult::NoValidDevice, |device| {
+            let mut device = device.borrow_mut();
+            let register = address.wrapping_sub(device.io_address());
+            device.io_write_register(register, data)
+        });
+
+        if res == DeviceResult::Ok {
+            self.check_watchpoints(AddressSpace::Io, address as u32, ReadWrite::Write, data as u16, false);
+        }
 
-            if dev_unwrapped.io_address() != address {
-                continue;
-            }
+        res
+    }
 
-            return dev_unwrapped.io_write(data);
-        }
+    // The device whose ports include `address`.
+    fn io_device(&self, address: u8) -> Option<Rc<RefCell<dyn IOMappedDevice>>> {
+        self.io_devices
+            .iter()
+            .find(|device| {
+                let device = device.borrow();
+                address.checked_sub(device.io_address()).is_some_and(|register| register < device.io_size())
+            })
+            .cloned()
+    }
 
-        DeviceResult::NoValidDevice
+    pub fn options(&self) -> &EmuOptions {
+        &self.options
+    }
+
+    pub fn options_mut(&mut self) -> &mut EmuOptions {
+        &mut self.options
+    }
+
+    /// Cycles run since the CPU was created, including the reset sequence and cycles spent halted. Resets don't
+    /// clear the counter.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Instructions that finished executing since the CPU was created. Instructions abandoned because of a
+    /// trapped fault are not counted.
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn pc(&self) -> ExtendedAddress {
+        self.pc
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.state == CPUPhase::Halt
+    }
+
+    /// True when the next cycle fetches a new instruction.
+    pub fn at_instruction_boundary(&self) -> bool {
+        self.state == CPUPhase::Fetch && self.cycle == 1
     }
 
     pub fn reset(&mut self, pins: &mut Pins) {
@@ -534,10 +862,16 @@ impl CPU {
         self.pc.set_18bit_value(0);
         self.sp = StackAddress::new(0x0000, None);
 
-        self.state = CPUState::Reset;
+        self.state = CPUPhase::Reset;
         self.cycle = 1;
         self.flags.0 .0 = 0;
 
+        // A reset inside a handler leaves it for good, its RTI never comes.
+        self.int_status = InterruptStatus::None;
+        self.irq_in_service = false;
+        self.nmi_in_service = false;
+        self.exception_in_service = false;
+
         pins.address.set_16bit_value(0);
         pins.data = 0;
         pins.rw = ReadWrite::Read;
@@ -558,18 +892,48 @@ impl CPU {
         self.rd.update_word();
     }
 
-    fn reset_handler(&mut self, pins: &mut Pins) {
+    // Interrupt priority, highest first: exceptions, NMI, then normal interrupts. A pending normal interrupt that
+    // has not been entered yet is replaced by an NMI asserted before the current instruction finishes. Nothing
+    // is latched while an exception handler runs.
+    fn latch_interrupt(&mut self, pins: &Pins) {
+        if self.state == CPUPhase::Interrupt || self.exception_in_service {
+            return;
+        }
+
+        if pins.irq.nmi && !self.nmi_in_service && self.int_status != InterruptStatus::NonMaskable {
+            self.int_status = InterruptStatus::NonMaskable;
+        } else if pins.irq.req
+            && self.int_status == InterruptStatus::None
+            && !self.flags.contains(Flag::I)
+            && !self.irq_in_service
+            && !self.nmi_in_service
+        {
+            self.int_status = InterruptStatus::Normal;
+        }
+    }
+
+    fn reset_handler(&mut self, pins: &mut Pins) -> Result<(), EmuError> {
+        let vector = match self.options.reset_vector {
+            ResetVector::Memory(vector) => vector,
+            ResetVector::Fixed(address) => {
+                self.pc = address;
+                self.finish(pins);
+                return Ok(());
+            }
+        };
+
         match self.cycle {
             1 => {
                 self.word = false;
-                pins.address.set_16bit_value(0x0000);
+                pins.address = vector;
                 pins.rw = ReadWrite::Read;
             }
             2 => {
                 self.temp_addr.set_extended_value(pins.data as u8);
 
                 self.word = true;
-                pins.address.set_16bit_value(0x0001);
+                pins.address = vector;
+                pins.address.increment();
                 pins.rw = ReadWrite::Read;
             }
             3 => {
@@ -578,13 +942,16 @@ impl CPU {
                 self.pc = self.temp_addr;
                 self.finish(pins);
             }
-            _ => panic!("Tried to execute cycle {} for reset_handler", self.cycle),
+            _ => return Err(self.fault(EmuError::InvalidCycle)),
         }
+
+        Ok(())
     }
 
-    fn fetch_handler(&mut self, pins: &mut Pins) {
+    fn fetch_handler(&mut self, pins: &mut Pins) -> Result<(), EmuError> {
         match self.cycle {
             1 => {
+                self.inst_pc = self.pc;
                 self.word = false;
                 pins.address = self.pc;
                 pins.rw = ReadWrite::Read;
@@ -602,84 +969,61 @@ impl CPU {
                 self.pc.increment_amount(2);
 
                 self.finish(pins);
-                self.state = CPUState::Execute;
+                self.state = CPUPhase::Execute;
             }
-            _ => panic!("Tried to execute cycle {} for fetch_handler", self.cycle),
+            _ => return Err(self.fault(EmuError::InvalidCycle)),
         }
+
+        Ok(())
     }
 
-    fn execute_handler(&mut self, pins: &mut Pins) {
+    fn execute_handler(&mut self, pins: &mut Pins) -> Result<(), EmuError> {
         pins.bus_enable = true;
 
-        let parts: Vec<&str> = self.inst_info.opcodes.get(&self.instruction.opcode).unwrap_or_else(|| panic!("Unknown opcode: {:#04x}", self.instruction.opcode)).split("|").collect();
-        let (inst_name, inst_mode) = (parts[0], parts[1]);
-
-        let func: fn(&mut CPU, &mut Pins, AddressingMode) = match inst_name {
-            "mov" => CPU::MOV,
-            "movb" => CPU::MOVB,
-            "st" => CPU::ST,
-            "stb" => CPU::STB,
-            "and" => CPU::AND,
-            "andb" => CPU::ANDB,
-            "or" => CPU::OR,
-            "orb" => CPU::ORB,
-            "xor" => CPU::XOR,
-            "xorb" => CPU::XORB,
-            "psh" => CPU::PSH,
-            "pshb" => CPU::PSHB,
-            "add" => CPU::ADD,
-            "addb" => CPU::ADDB,
-            "sub" => CPU::SUB,
-            "subb" => CPU::SUBB,
-            "cmp" => CPU::CMP,
-            "cmpb" => CPU::CMPB,
-            "inc" => CPU::INC,
-            "incb" => CPU::INCB,
-            "dec" => CPU::DEC,
-            "decb" => CPU::DECB,
-            "sbl" => CPU::SBL,
-            "sblb" => CPU::SBLB,
-            "sbr" => CPU::SBR,
-            "sbrb" => CPU::SBRB,
-            "rol" => CPU::ROL,
-            "rolb" => CPU::ROLB,
-            "ror" => CPU::ROR,
-            "rorb" => CPU::RORB,
-            "clc" => CPU::CLC,
-            "cli" => CPU::CLI,
-            "clv" => CPU::CLV,
-            "sei" => CPU::SEI,
-            "jmp" => CPU::JMP,
-            "jsr" => CPU::JSR,
-            "biz" => CPU::BIZ,
-            "bin" => CPU::BIN,
-            "bic" => CPU::BIC,
-            "bio" => CPU::BIO,
-            "bil" => CPU::BIL,
-            "big" => CPU::BIG,
-            "bnz" => CPU::BNZ,
-            "bnn" => CPU::BNN,
-            "bnc" => CPU::BNC,
-            "bno" => CPU::BNO,
-            "bnl" => CPU::BNL,
-            "bng" => CPU::BNG,
-            "rts" => CPU::RTS,
-            "in" => CPU::IN,
-            "out" => CPU::OUT,
-            "hlt" => CPU::HLT,
-            _ => panic!("Unimplemented function pointer to instruction function for instruction `{inst_name}`")
-        };
+        let (func, mode) = self.dispatch[self.instruction.opcode as usize];
+        (func)(self, pins, mode)?;
+
+        if self.state != CPUPhase::Execute {
+            self.instructions += 1;
+        }
+
+        Ok(())
+    }
+
+    fn access_bus(&mut self, pins: &mut Pins) -> Result<(), EmuError> {
+        if pins.bus_enable {
+            if pins.rw == ReadWrite::Read {
+                match self.read(pins.address) {
+                    DeviceResult::Ok8(val) => pins.data = val as u16,
+                    DeviceResult::Ok16(val) => pins.data = val,
+                    res => return Err(self.bus_fault(pins.address, res)),
+                }
+            } else {
+                match self.write(pins.address, pins.data) {
+                    DeviceResult::Ok => {}
+                    res => return Err(self.bus_fault(pins.address, res)),
+                }
+            }
+        }
 
-        match inst_mode {
-            "M" => (func)(self, pins, AddressingMode::Implied),
-            "I" => (func)(self, pins, AddressingMode::Immediate),
-            "R" => (func)(self, pins, AddressingMode::Register),
-            "A" => (func)(self, pins, AddressingMode::Absolute),
-            _ => panic!("Unimplemented addressing mode")
+        if pins.io_enable {
+            if pins.io_rw == ReadWrite::Read {
+                match self.read_io(pins.io_address) {
+                    DeviceResult::Ok8(val) => pins.io_data = val,
+                    res => return Err(self.io_fault(pins.io_address, res)),
+                }
+            } else {
+                match self.write_io(pins.io_address, pins.io_data) {
+                    DeviceResult::Ok => {}
+                    res => return Err(self.io_fault(pins.io_address, res)),
+                }
+            }
         }
+
+        Ok(())
     }
 
-    fn interrupt_handler(&mut self, pins: &mut Pins) {
+    fn interrupt_handler(&mut self, pins: &mut Pins) -> Result<(), EmuError> {
         self.word = false;
         match self.int_status {
             InterruptStatus::Normal => match self.cycle {
@@ -690,7 +1034,7 @@ impl CPU {
                     pins.bus_enable = true;
                     pins.irq.ack = false;
                     self.int_num = pins.irq.data;
-                    pins.address = ExtendedAddress::new_16bit_address((pins.irq.data * 3) as u16);
+                    pins.address = ExtendedAddress::new_16bit_address((pins.irq.data & 0xF) as u16 * 3);
                     pins.rw = ReadWrite::Read;
                 }
                 3 => {
@@ -710,6 +1054,7 @@ impl CPU {
                     pins.rw = ReadWrite::Write;
                 }
                 6 => {
+                    self.flags.set(Flag::I, true);
                     self.sp.increment();
                     pins.address = self.sp.into();
                     pins.data = self.pc.get_extended_value() as u16;
@@ -731,15 +1076,17 @@ impl CPU {
                     self.sp.increment();
                     self.pc = self.temp_addr;
                     self.int_status = InterruptStatus::None;
+                    self.irq_in_service = true;
                     self.finish(pins);
                 }
-                _ => panic!("Unknown normal interrupt cycle: {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
             InterruptStatus::NonMaskable => match self.cycle {
                 1 => {
                     pins.irq.ack = true;
                 }
                 2 => {
+                    pins.bus_enable = true;
                     pins.irq.ack = false;
                     pins.address = ExtendedAddress::new_16bit_address(0x03);
                     pins.rw = ReadWrite::Read;
@@ -761,6 +1108,7 @@ impl CPU {
                     pins.rw = ReadWrite::Write;
                 }
                 6 => {
+                    self.flags.set(Flag::I, true);
                     self.sp.increment();
                     pins.address = self.sp.into();
                     pins.data = self.pc.get_extended_value() as u16;
@@ -781,31 +1129,159 @@ impl CPU {
                 9 => {
                     self.sp.increment();
                     self.pc = self.temp_addr;
+                    self.int_status = InterruptStatus::None;
+                    self.nmi_in_service = true;
                     self.finish(pins);
                 }
-                _ => panic!("Unknown non-maskable interrupt cycle: {}", self.cycle),
+                _ => return Err(self.fault(EmuError::InvalidCycle)),
             },
-            _ => panic!("Cannot handle interrupt with status {:?}", self.int_status),
+            InterruptStatus::Exception => self.exception_handler(pins)?,
+            // finish() only switches to the interrupt state with a pending interrupt.
+            InterruptStatus::None => unreachable!("Interrupt state without a pending interrupt"),
         }
+
+        Ok(())
+    }
+
+    // Same sequence as an interrupt without the acknowledge, the vector comes from the exception itself. After the
+    // usual frame the faulting address is pushed (ext, hi, lo), so a handler pops it before it can RTI.
+    fn exception_handler(&mut self, pins: &mut Pins) -> Result<(), EmuError> {
+        match self.cycle {
+            1 => {
+                pins.bus_enable = true;
+                pins.address = ExtendedAddress::new_16bit_address(self.int_num as u16 * 3);
+                pins.rw = ReadWrite::Read;
+            }
+            2 => {
+                self.temp_addr.set_extended_value(pins.data as u8);
+                pins.address.increment();
+                pins.rw = ReadWrite::Read;
+            }
+            3 => {
+                self.temp_addr.set_hi_byte(pins.data as u8);
+                pins.address.increment();
+                pins.rw = ReadWrite::Read;
+            }
+            4 => {
+                self.temp_addr.set_low_byte(pins.data as u8);
+                pins.address = self.sp.into();
+                pins.data = self.flags.bits();
+                pins.rw = ReadWrite::Write;
+            }
+            5..=10 => {
+                if self.cycle == 5 {
+                    self.flags.set(Flag::I, true);
+                }
+
+                let byte = match self.cycle {
+                    5 => self.pc.get_extended_value(),
+                    6 => self.pc.get_hi_byte(),
+                    7 => self.pc.get_low_byte(),
+                    8 => self.fault_address.get_extended_value(),
+                    9 => self.fault_address.get_hi_byte(),
+                    _ => self.fault_address.get_low_byte(),
+                };
+
+                self.sp.increment();
+                pins.address = self.sp.into();
+                pins.data = byte as u16;
+                pins.rw = ReadWrite::Write;
+            }
+            11 => {
+                self.sp.increment();
+                self.pc = self.temp_addr;
+                self.int_status = InterruptStatus::None;
+                self.exception_in_service = true;
+                self.finish(pins);
+            }
+            _ => return Err(self.fault(EmuError::InvalidCycle)),
+        }
+
+        Ok(())
+    }
+
+    // Turns `err` into an exception when faults trap to the guest, the faulting instruction is abandoned and the
+    // pushed PC points back at it. Faults during reset or interrupt entry, and faults inside an exception
+    // handler, can't be handled by the guest and are returned to the host.
+    fn raise_exception(&mut self, pins: &mut Pins, err: EmuError) -> Result<(), EmuError> {
+        let Some((exception, address)) = err.exception() else {
+            return Err(err);
+        };
+
+        if !self.options.trap_faults()
+            || self.exception_in_service
+            || matches!(self.state, CPUPhase::Reset | CPUPhase::Interrupt)
+        {
+            return Err(err);
+        }
+
+        self.pc = self.inst_pc;
+        self.fault_address = address;
+        self.int_num = exception as u8;
+        self.int_status = InterruptStatus::Exception;
+        self.state = CPUPhase::Interrupt;
+        self.cycle = 1;
+        self.word = false;
+
+        pins.bus_enable = false;
+        pins.io_enable = false;
+
+        Ok(())
+    }
+
+    fn fault_info(&self, address: ExtendedAddress) -> FaultInfo {
+        FaultInfo {
+            pc: self.inst_pc,
+            opcode: self.instruction.opcode,
+            cycle: self.cycle,
+            address,
+        }
+    }
+
+    // Faults that are not caused by a bus access report the instruction itself as the faulting address.
+    fn fault(&self, kind: fn(FaultInfo) -> EmuError) -> EmuError {
+        kind(self.fault_info(self.inst_pc))
+    }
+
+    // The bus is serviced after `cycle` was already incremented, so the access belongs to the previous cycle.
+    fn bus_fault_info(&self, address: ExtendedAddress) -> FaultInfo {
+        FaultInfo {
+            cycle: self.cycle.wrapping_sub(1),
+            ..self.fault_info(address)
+        }
+    }
+
+    fn bus_fault(&self, address: ExtendedAddress, res: DeviceResult) -> EmuError {
+        EmuError::Bus(self.bus_fault_info(address), res)
+    }
+
+    fn io_fault(&self, port: u8, res: DeviceResult) -> EmuError {
+        EmuError::IO(self.bus_fault_info(ExtendedAddress::new_16bit_address(port as u16)), res)
     }
 
     fn finish(&mut self, pins: &mut Pins) {
         self.cycle = 0;
         pins.bus_enable = false;
 
+        // A normal interrupt latched while the instruction ran is dropped if the instruction masked it (SEI).
+        // The request is level triggered, so it is latched again once the I flag is cleared.
+        if self.int_status == InterruptStatus::Normal && self.flags.contains(Flag::I) {
+            self.int_status = InterruptStatus::None;
+        }
+
         if self.int_status != InterruptStatus::None {
-            self.state = CPUState::Interrupt;
+            self.state = CPUPhase::Interrupt;
             return;
         }
 
-        if self.state == CPUState::Halt {
+        if self.state == CPUPhase::Halt {
             return;
         }
 
-        self.state = CPUState::Fetch;
+        self.state = CPUPhase::Fetch;
     }
 
-    fn decode_register(&mut self, mut reg: u8) -> RegisterReturn {
+    fn decode_register(&mut self, mut reg: u8) -> RegisterReturn<'_> {
         reg &= 0xF;
 
         match reg {
@@ -824,8 +1300,7 @@ impl CPU {
             0b1100 => RegisterReturn::Partial(self.rc.get_mut_high()),
             0b1101 => RegisterReturn::Partial(self.rc.get_mut_low()),
             0b1110 => RegisterReturn::Partial(self.rd.get_mut_high()),
-            0b1111 => RegisterReturn::Partial(self.rd.get_mut_low()),
-            _ => panic!("Invalid register #"),
+            _ => RegisterReturn::Partial(self.rd.get_mut_low()),
         }
     }
 
@@ -855,39 +1330,92 @@ impl CPU {
         }
     }
 
-    fn jump_if_flag(&mut self, pins: &mut Pins, flag: Flag, inst_name: &str) {
+    fn jump_if_flag(&mut self, pins: &mut Pins, flag: Flag) -> Result<(), EmuError> {
         match self.cycle {
-            1..=2 => self.mode_absolute(pins, Some(false), None),
+            1..=2 => self.mode_absolute(pins, Some(false), None)?,
             3 => {
-                self.mode_absolute(pins, Some(false), None);
+                self.mode_absolute(pins, Some(false), None)?;
                 self.mode_absolute_jmp_cond(pins, flag, true);
             }
-            _ => panic!(
-                "{} tried to execute non-existent cycle {}",
-                inst_name, self.cycle
-            ),
+            _ => return Err(self.fault(EmuError::InvalidCycle)),
+        }
+
+        Ok(())
+    }
+
+    // Unwinds the 4 byte frame written by JSR and the interrupt handler (see `doc/cpu.md`, Stack Frames).
+    // The frame is popped in reverse: PC low, PC high, PC extended and finally the flags.
+    fn pop_return_frame(&mut self, pins: &mut Pins) -> Result<(), EmuError> {
+        match self.cycle {
+            1 => {
+                self.sp.decrement();
+                pins.address = self.sp.into();
+                pins.rw = ReadWrite::Read;
+                self.word = false;
+            }
+            2 => {
+                self.sp.decrement();
+                self.temp_addr.set_low_byte(pins.data as u8);
+
+                pins.address = self.sp.into();
+                pins.rw = ReadWrite::Read;
+            }
+            3 => {
+                self.sp.decrement();
+                self.temp_addr.set_hi_byte(pins.data as u8);
+
+                pins.address = self.sp.into();
+                pins.rw = ReadWrite::Read;
+            }
+            4 => {
+                self.sp.decrement();
+                self.temp_addr.set_extended_value(pins.data as u8);
+
+                pins.address = self.sp.into();
+                pins.rw = ReadWrite::Read;
+            }
+            5 => {
+                self.flags.0 .0 = pins.data & 0xFF;
+
+                self.pc = self.temp_addr;
+                self.finish(pins);
+            }
+            _ => return Err(self.fault(EmuError::InvalidCycle)),
+        }
+
+        Ok(())
+    }
+
+    // Leaves the innermost interrupt service routine. An NMI can preempt a normal interrupt, so it is
+    // always the one being returned from when both are in service.
+    fn end_of_interrupt(&mut self) {
+        if self.exception_in_service {
+            self.exception_in_service = false;
+        } else if self.nmi_in_service {
+            self.nmi_in_service = false;
+        } else {
+            self.irq_in_service = false;
         }
     }
 
-    fn jump_not_flag(&mut self, pins: &mut Pins, flag: Flag, inst_name: &str) {
+    fn jump_not_flag(&mut self, pins: &mut Pins, flag: Flag) -> Result<(), EmuError> {
         match self.cycle {
-            1..=2 => self.mode_absolute(pins, Some(false), None),
+            1..=2 => self.mode_absolute(pins, Some(false), None)?,
             3 => {
-                self.mode_absolute(pins, Some(false), None);
+                self.mode_absolute(pins, Some(false), None)?;
                 self.mode_absolute_jmp_cond(pins, flag, false);
             }
-            _ => panic!(
-                "{} tried to execute non-existent cycle {}",
-                inst_name, self.cycle
-            ),
+            _ => return Err(self.fault(EmuError::InvalidCycle)),
         }
+
+        Ok(())
     }
 }
 
 type AbsoluteFunc = fn (&mut CPU, pins: &mut Pins, byte_instruction: bool);
 // Addressing Modes
 impl CPU {
-    fn mode_absolute(&mut self, pins: &mut Pins, byte_instruction: Option<bool>, func: Option<AbsoluteFunc>) {
+    fn mode_absolute(&mut self, pins: &mut Pins, byte_instruction: Option<bool>, func: Option<AbsoluteFunc>) -> Result<(), EmuError> {
         match self.cycle {
             1 => {
                 self.word = false;
@@ -927,8 +1455,10 @@ impl CPU {
                 
                 self.word = !byte_instruction.unwrap_or(false);
             },
-            _ => panic!("Addressing mode does not have cycle {}, cycles available: 1-3", self.cycle)
+            _ => return Err(self.fault(EmuError::InvalidCycle)),
         }
+
+        Ok(())
     }
 
     fn mode_absolute_data(&mut self, pins: &mut Pins, _byte_instruction: bool) {
@@ -947,17 +1477,21 @@ impl CPU {
         pins.rw = ReadWrite::Write;
     }
 
+    fn mode_absolute_pop(&mut self, pins: &mut Pins, word: bool) {
+        if word {
+            self.sp.decrement_amount(2);
+        } else {
+            self.sp.decrement();
+        }
+
+        pins.address = self.sp.into();
+        pins.rw = ReadWrite::Read;
+    }
+
     fn mode_absolute_jmp_cond(&mut self, pins: &mut Pins, flag: Flag, contains: bool) {
-        if contains {
-            if self.flags.contains(flag) {
-                self.pc = self.temp_addr;
-            } else {
-                self.pc.increment();
-            }
-        } else if !self.flags.contains(flag) {
+        // Not taken, `mode_absolute` already moved the PC past the operand.
+        if self.flags.contains(flag) == contains {
             self.pc = self.temp_addr;
-        } else {
-            self.pc.increment();
         }
 
         self.word = false;
@@ -977,3 +1511,214 @@ impl CPU {
         self.word = !byte_instruction.unwrap_or(false);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cpu::instructions::testing::{setup, setup_with_vectors, step_instruction, try_step_instruction},
+        info::get_instructions,
+    };
+
+    #[test]
+    fn test_every_opcode_has_a_handler() {
+        let inst_info = get_instructions();
+
+        for (opcode, entry) in &inst_info.opcodes {
+            let (name, mode) = entry.split_once('|').unwrap();
+
+            assert!(instruction_fn(name).is_some(), "No handler for {entry} ({opcode:#04x})");
+            assert!(addressing_mode(mode).is_some(), "Unknown addressing mode for {entry} ({opcode:#04x})");
+        }
+    }
+
+    #[test]
+    fn test_reset_vector() {
+        let (mut cpu, mut pins) = setup_with_vectors(&[], &[(1, 0x00_0150)]);
+
+        cpu.options_mut().set_reset_vector(ResetVector::Memory(ExtendedAddress::new_16bit_address(0x0003)));
+        cpu.reset(&mut pins);
+        assert_eq!(step_instruction(&mut cpu, &mut pins), 3);
+        assert_eq!(cpu.pc(), 0x00_0150);
+
+        cpu.options_mut().set_reset_vector(ResetVector::Fixed(ExtendedAddress::new_ext_address(0x01_2345)));
+        cpu.reset(&mut pins);
+        assert_eq!(step_instruction(&mut cpu, &mut pins), 1);
+        assert_eq!(cpu.pc(), 0x01_2345);
+    }
+
+    #[test]
+    fn test_counters() {
+        let (mut cpu, mut pins) = setup(&[
+            210, 0x00, 0x00,                  // clc
+            232, 0x00, 0x00, 0x02, 0x00, 0x00, // st ra, $020000
+        ]);
+
+        assert_eq!((cpu.cycles(), cpu.instructions()), (3, 0));
+
+        step_instruction(&mut cpu, &mut pins);
+        assert_eq!((cpu.cycles(), cpu.instructions()), (7, 1));
+
+        // The faulting store is not counted, the reset keeps the counters.
+        try_step_instruction(&mut cpu, &mut pins).unwrap_err();
+        cpu.reset(&mut pins);
+        assert_eq!((cpu.cycles(), cpu.instructions()), (13, 1));
+    }
+
+    #[test]
+    fn test_undefined_opcode_traps() {
+        let (mut cpu, mut pins) = setup(&[0x00, 0x00, 0x00]);
+
+        let err = try_step_instruction(&mut cpu, &mut pins).unwrap_err();
+
+        assert_eq!(
+            err,
+            EmuError::UnknownOpcode(FaultInfo {
+                pc: ExtendedAddress::new_16bit_address(0x0100),
+                opcode: 0x00,
+                cycle: 1,
+                address: ExtendedAddress::new_16bit_address(0x0100),
+            })
+        );
+    }
+
+    #[test]
+    fn test_bus_fault() {
+        let (mut cpu, mut pins) = setup(&[
+            232, 0x00, 0x00, 0x02, 0x00, 0x00, // st ra, $020000
+        ]);
+
+        let err = try_step_instruction(&mut cpu, &mut pins).unwrap_err();
+
+        assert_eq!(
+            err,
+            EmuError::Bus(
+                FaultInfo {
+                    pc: ExtendedAddress::new_16bit_address(0x0100),
+                    opcode: 232,
+                    cycle: 3,
+                    address: ExtendedAddress::new_ext_address(0x02_0000),
+                },
+                DeviceResult::NoValidDevice
+            )
+        );
+    }
+
+    // Places `handler` at 0x000200 behind `program`.
+    fn with_handler(program: &[u8], handler: &[u8]) -> Vec<u8> {
+        let mut data = program.to_vec();
+        data.resize(0x100, 0);
+        data.extend_from_slice(handler);
+        data
+    }
+
+    fn read_bytes(cpu: &mut CPU, start: u32, count: u32) -> Vec<DeviceResult> {
+        cpu.word = false;
+        (0..count).map(|i| cpu.read(ExtendedAddress::new_ext_address(start + i))).collect()
+    }
+
+    fn trapping(program: &[u8], handler: &[u8], exception: Exception) -> (CPU, Pins) {
+        let (mut cpu, pins) = setup_with_vectors(&with_handler(program, handler), &[(exception as u8, 0x00_0200)]);
+        cpu.options_mut().set_trap_faults();
+        (cpu, pins)
+    }
+
+    #[test]
+    fn test_illegal_instruction_exception() {
+        let (mut cpu, mut pins) = trapping(&[0x00, 0x00, 0x00], &[], Exception::IllegalInstruction);
+
+        step_instruction(&mut cpu, &mut pins);
+
+        assert_eq!(cpu.pc, 0x00_0200);
+        assert!(cpu.flags.contains(Flag::I));
+        assert!(cpu.exception_in_service);
+        assert_eq!(u32::from(cpu.sp), 0x01_0007);
+        assert_eq!(
+            read_bytes(&mut cpu, 0x01_0000, 7),
+            [0x00, 0x00, 0x01, 0x00, 0x00, 0x01, 0x00].map(DeviceResult::Ok8)
+        );
+    }
+
+    #[test]
+    fn test_bus_error_exception_returns() {
+        let (mut cpu, mut pins) = trapping(
+            &[
+                232, 0x00, 0x00, 0x02, 0x00, 0x00, // st ra, $020000
+            ],
+            &[
+                131, 0x00, 0x00, // popb ra
+                131, 0x00, 0x00, // popb ra
+                131, 0x00, 0x00, // popb ra
+                92, 0x00, 0x00,  // rti
+            ],
+            Exception::BusError,
+        );
+
+        step_instruction(&mut cpu, &mut pins);
+
+        assert_eq!(cpu.pc, 0x00_0200);
+        assert_eq!(
+            read_bytes(&mut cpu, 0x01_0004, 3),
+            [0x02, 0x00, 0x00].map(DeviceResult::Ok8)
+        );
+
+        for _ in 0..3 {
+            step_instruction(&mut cpu, &mut pins);
+        }
+        assert_eq!(cpu.ra.get_word(), 0x0002);
+
+        step_instruction(&mut cpu, &mut pins);
+        assert_eq!(cpu.pc, 0x00_0100);
+        assert_eq!(u32::from(cpu.sp), 0x01_0000);
+        assert!(!cpu.exception_in_service);
+        assert!(!cpu.flags.contains(Flag::I));
+    }
+
+    #[test]
+    fn test_rom_write_exception() {
+        let (mut cpu, mut pins) = trapping(
+            &[
+                232, 0x00, 0x00, 0x00, 0x00, 0x80, // st ra, $000080
+            ],
+            &[],
+            Exception::RomWrite,
+        );
+
+        step_instruction(&mut cpu, &mut pins);
+
+        assert_eq!(cpu.pc, 0x00_0200);
+        assert_eq!(
+            read_bytes(&mut cpu, 0x01_0004, 3),
+            [0x00, 0x00, 0x80].map(DeviceResult::Ok8)
+        );
+    }
+
+    #[test]
+    fn test_io_fault_exception() {
+        let (mut cpu, mut pins) = trapping(
+            &[
+                224, 0x00, 0x00, 0xA0, // in ra, 0xA0
+            ],
+            &[],
+            Exception::BusError,
+        );
+
+        step_instruction(&mut cpu, &mut pins);
+
+        assert_eq!(cpu.pc, 0x00_0200);
+        assert_eq!(
+            read_bytes(&mut cpu, 0x01_0004, 3),
+            [0x00, 0x00, 0xA0].map(DeviceResult::Ok8)
+        );
+    }
+
+    #[test]
+    fn test_fault_in_exception_handler_stops_host() {
+        let (mut cpu, mut pins) = trapping(&[0x00, 0x00, 0x00], &[0x00, 0x00, 0x00], Exception::IllegalInstruction);
+
+        step_instruction(&mut cpu, &mut pins);
+        let err = try_step_instruction(&mut cpu, &mut pins).unwrap_err();
+
+        assert!(matches!(err, EmuError::UnknownOpcode(info) if info.pc == 0x00_0200));
+    }
+}
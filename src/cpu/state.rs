@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{CPUMetadata, CPUPhase, ExtendedAddress, Flag, InterruptStatus, StackAddress, CPU};
+
+/// Snapshot of the programmer visible CPU state, plus where the state machine is. Bus latches, the current
+/// instruction and interrupt bookkeeping are not part of it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CpuState {
+    pub ra: u16,
+    pub rb: u16,
+    pub rc: u16,
+    pub rd: u16,
+    pub flags: u16,
+    pub pc: ExtendedAddress,
+    pub sp: StackAddress,
+    pub phase: CPUPhase,
+    pub cycle: u8,
+}
+
+/// Everything needed to resume a CPU in the middle of an instruction: the visible [`CpuState`], the decoded
+/// instruction, the temporaries handlers keep between cycles and the interrupt bookkeeping.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CpuSnapshot {
+    state: CpuState,
+    opcode: u8,
+    metadata: u16,
+    inst_pc: ExtendedAddress,
+    temp_addr: ExtendedAddress,
+    temp16: u16,
+    word: bool,
+    int_status: InterruptStatus,
+    int_num: u8,
+    irq_in_service: bool,
+    nmi_in_service: bool,
+    exception_in_service: bool,
+    fault_address: ExtendedAddress,
+    cycles: u64,
+    instructions: u64,
+}
+
+impl CpuSnapshot {
+    pub fn state(&self) -> &CpuState {
+        &self.state
+    }
+}
+
+impl CPU {
+    pub fn state(&self) -> CpuState {
+        CpuState {
+            ra: self.ra.get_word(),
+            rb: self.rb.get_word(),
+            rc: self.rc.get_word(),
+            rd: self.rd.get_word(),
+            flags: self.flags.bits(),
+            pc: self.pc,
+            sp: self.sp,
+            phase: self.state,
+            cycle: self.cycle,
+        }
+    }
+
+    pub fn set_state(&mut self, state: &CpuState) {
+        self.ra.set_word(state.ra);
+        self.rb.set_word(state.rb);
+        self.rc.set_word(state.rc);
+        self.rd.set_word(state.rd);
+        self.flags = Flag::from_bits_retain(state.flags);
+        self.pc = state.pc;
+        self.sp = state.sp;
+        self.state = state.phase;
+        self.cycle = state.cycle;
+    }
+
+    pub fn snapshot(&self) -> CpuSnapshot {
+        CpuSnapshot {
+            state: self.state(),
+            opcode: self.instruction.opcode,
+            metadata: self.instruction.metadata.0,
+            inst_pc: self.inst_pc,
+            temp_addr: self.temp_addr,
+            temp16: self.temp16,
+            word: self.word,
+            int_status: self.int_status,
+            int_num: self.int_num,
+            irq_in_service: self.irq_in_service,
+            nmi_in_service: self.nmi_in_service,
+            exception_in_service: self.exception_in_service,
+            fault_address: self.fault_address,
+            cycles: self.cycles,
+            instructions: self.instructions,
+        }
+    }
+
+    /// Restores the CPU from `snapshot`, devices are restored separately.
+    pub fn restore(&mut self, snapshot: &CpuSnapshot) {
+        self.set_state(&snapshot.state);
+        self.instruction.opcode = snapshot.opcode;
+        self.instruction.metadata = CPUMetadata(snapshot.metadata);
+        self.inst_pc = snapshot.inst_pc;
+        self.temp_addr = snapshot.temp_addr;
+        self.temp16 = snapshot.temp16;
+        self.word = snapshot.word;
+        self.int_status = snapshot.int_status;
+        self.int_num = snapshot.int_num;
+        self.irq_in_service = snapshot.irq_in_service;
+        self.nmi_in_service = snapshot.nmi_in_service;
+        self.exception_in_service = snapshot.exception_in_service;
+        self.fault_address = snapshot.fault_address;
+        self.cycles = snapshot.cycles;
+        self.instructions = snapshot.instructions;
+    }
+
+    pub(crate) fn device_count(&self) -> (usize, usize) {
+        (self.devices.len(), self.io_devices.len())
+    }
+
+    /// Saved state of every memory device followed by every IO device, in the order they were added.
+    pub(crate) fn save_device_states(&self) -> (Vec<Value>, Vec<Value>) {
+        let devices = self.devices.iter().map(|device| device.borrow().save_state()).collect();
+        let io_devices = self.io_devices.iter().map(|device| device.borrow().save_state()).collect();
+
+        (devices, io_devices)
+    }
+
+    pub(crate) fn load_device_states(&mut self, devices: &[Value], io_devices: &[Value]) -> Result<(), serde_json::Error> {
+        for (device, state) in self.devices.iter().zip(devices) {
+            device.borrow_mut().load_state(state)?;
+        }
+
+        for (device, state) in self.io_devices.iter().zip(io_devices) {
+            device.borrow_mut().load_state(state)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::instructions::testing::{setup, step_instruction};
+
+    #[test]
+    fn test_state_after_instruction() {
+        let (mut cpu, mut pins) = setup(&[
+            59, 0x00, 0x00, 0x12, 0x34, // mov ra, 0x1234
+        ]);
+
+        step_instruction(&mut cpu, &mut pins);
+
+        let state = cpu.state();
+        assert_eq!(state.ra, 0x1234);
+        assert_eq!(state.pc, 0x000105);
+        assert_eq!(state.sp, StackAddress::new(0x0000, None));
+        assert_eq!(state.phase, CPUPhase::Fetch);
+        assert_eq!(state.cycle, 1);
+    }
+
+    #[test]
+    fn test_set_state() {
+        let (mut cpu, mut pins) = setup(&[
+            59, 0x00, 0x01, 0x00, 0x01, // mov rb, 0x0001
+        ]);
+
+        step_instruction(&mut cpu, &mut pins);
+
+        let mut state = cpu.state();
+        state.ra = 0xBEEF;
+        state.flags = Flag::C.bits();
+        state.pc = ExtendedAddress::new_16bit_address(0x0100);
+        state.sp = StackAddress::new(0x0010, Some(2));
+        cpu.set_state(&state);
+
+        assert_eq!(cpu.state(), state);
+        assert_eq!(cpu.pc(), 0x000100);
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let state = CpuState {
+            ra: 1,
+            rb: 2,
+            rc: 3,
+            rd: 4,
+            flags: 0b0100_0001,
+            pc: ExtendedAddress::new_ext_address(0x123456),
+            sp: StackAddress::new(0x0042, None),
+            phase: CPUPhase::Execute,
+            cycle: 3,
+        };
+
+        let json = serde_json::to_string(&state).unwrap();
+        assert!(json.contains("\"pc\":1193046"));
+        assert_eq!(serde_json::from_str::<CpuState>(&json).unwrap(), state);
+    }
+}
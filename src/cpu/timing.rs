@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use super::{CPUPhase, ExtendedAddress, Pins, CPU};
+use crate::{
+    device::{DeviceResult, IOMappedDevice, RAM},
+    info::InstructionInfoFile,
+};
+
+// Answers every IO access, so IN and OUT can be timed without real devices.
+struct Port;
+
+impl IOMappedDevice for Port {
+    fn io_address(&self) -> u8 {
+        0x00
+    }
+
+    fn io_read(&mut self) -> DeviceResult {
+        DeviceResult::Ok8(0)
+    }
+
+    fn io_write(&mut self, _data: u8) -> DeviceResult {
+        DeviceResult::Ok
+    }
+
+    fn io_name(&self) -> &str {
+        "Port"
+    }
+}
+
+/// Runs every opcode in `opcodes` through the CPU and counts the cycles from the first fetch cycle until the next
+/// instruction would be fetched. HLT counts until the CPU halted. The operands are all zero, no instruction takes
+/// a different number of cycles depending on its operands or the flags.
+pub fn cycle_table(opcodes: &HashMap<u8, String>) -> HashMap<u8, u8> {
+    let inst_info = InstructionInfoFile {
+        opcodes: opcodes.clone(),
+        info: Vec::new(),
+    };
+
+    let mut cpu = CPU::new(inst_info, None);
+    let mut pins = Pins::default();
+
+    cpu.add_device(RAM::new(
+        ExtendedAddress::new_ext_address(0x00_0000),
+        ExtendedAddress::new_ext_address(0x01_FFFF),
+    ));
+    cpu.add_io_device(Port);
+
+    opcodes.keys().filter_map(|&opcode| Some((opcode, measure(&mut cpu, &mut pins, opcode)?))).collect()
+}
+
+fn measure(cpu: &mut CPU, pins: &mut Pins, opcode: u8) -> Option<u8> {
+    // Reset vector to $000100, where the instruction is placed. Zero operands keep every access in the first
+    // 64KB, the stack is on page 1. Earlier instructions may have stored over the vector, so both are rewritten.
+    let code = [(0x00_0000, [0x00, 0x01, 0x00].as_slice()), (0x00_0100, &[opcode, 0x00, 0x00, 0x00, 0x00, 0x00])];
+
+    for (start, bytes) in code {
+        for (address, &byte) in (start..).zip(bytes) {
+            cpu.poke(ExtendedAddress::new_ext_address(address), byte as u16, false);
+        }
+    }
+
+    cpu.reset(pins);
+
+    while cpu.state == CPUPhase::Reset {
+        cpu.cycle(pins).ok()?;
+        cpu.service_bus(pins).ok()?;
+    }
+
+    for cycles in 1..=u8::MAX {
+        cpu.cycle(pins).ok()?;
+        cpu.service_bus(pins).ok()?;
+
+        if cpu.at_instruction_boundary() || cpu.is_halted() {
+            return Some(cycles);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::info::get_instructions;
+
+    #[test]
+    fn test_cycle_table() {
+        let inst_info = get_instructions();
+        let table = cycle_table(&inst_info.opcodes);
+
+        assert_eq!(table.len(), inst_info.opcodes.len());
+        assert_eq!(table[&210], 4); // clc
+        assert_eq!(table[&59], 5); // mov ra, 0x0000
+        assert_eq!(table[&232], 7); // st ra, $000000
+        assert_eq!(table[&48], 9); // jsr $000000
+        assert_eq!(table[&8], 4); // hlt
+    }
+}
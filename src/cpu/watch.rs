@@ -0,0 +1,172 @@
+use std::ops::RangeInclusive;
+
+use serde::{Deserialize, Serialize};
+
+use super::{ExtendedAddress, ReadWrite, CPU};
+
+/// Which bus a watchpoint or access is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AddressSpace {
+    Memory,
+    Io,
+}
+
+/// Which accesses a watchpoint triggers on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access,
+}
+
+/// A bus access that hit a watchpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusAccess {
+    pub space: AddressSpace,
+    /// The memory address, or the IO port.
+    pub address: u32,
+    pub rw: ReadWrite,
+    /// The value that was read or written.
+    pub data: u16,
+    pub word: bool,
+    /// Address of the instruction that made the access.
+    pub pc: ExtendedAddress,
+    /// The watchpoint that was hit.
+    pub watchpoint: WatchId,
+}
+
+pub enum WatchAction {
+    /// Stop after the access, [`Machine`](crate::machine::Machine) reports this as
+    /// [`CycleStatus::Watchpoint`](super::CycleStatus::Watchpoint).
+    Pause,
+    /// Call the function and keep running.
+    Callback(Box<dyn FnMut(&BusAccess)>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct WatchId(pub u32);
+
+pub(super) struct Watchpoint {
+    id: WatchId,
+    space: AddressSpace,
+    range: RangeInclusive<u32>,
+    kind: WatchKind,
+    action: WatchAction,
+    // Set by the machine for its own bookkeeping (the tracer), left out of `watchpoints` and `remove_watchpoint`.
+    internal: bool,
+}
+
+impl CPU {
+    /// Watches `range` on the memory or IO bus. Word accesses hit if either of their bytes is in the range. Only
+    /// accesses the CPU makes are checked, [`CPU::peek`] and [`CPU::poke`] are not.
+    pub fn add_watchpoint(
+        &mut self,
+        space: AddressSpace,
+        range: RangeInclusive<u32>,
+        kind: WatchKind,
+        action: WatchAction,
+    ) -> WatchId {
+        self.push_watchpoint(space, range, kind, action, false)
+    }
+
+    /// A watchpoint hidden from [`CPU::watchpoints`], so users of the list can't see or remove it.
+    pub(crate) fn add_internal_watchpoint(
+        &mut self,
+        space: AddressSpace,
+        range: RangeInclusive<u32>,
+        kind: WatchKind,
+        action: WatchAction,
+    ) -> WatchId {
+        self.push_watchpoint(space, range, kind, action, true)
+    }
+
+    pub fn remove_watchpoint(&mut self, id: WatchId) -> bool {
+        self.retain_watchpoints(|watchpoint| watchpoint.id != id || watchpoint.internal)
+    }
+
+    pub(crate) fn remove_internal_watchpoint(&mut self, id: WatchId) -> bool {
+        self.retain_watchpoints(|watchpoint| watchpoint.id != id || !watchpoint.internal)
+    }
+
+    /// The watchpoints as `(id, space, range, kind)`.
+    pub fn watchpoints(&self) -> Vec<(WatchId, AddressSpace, RangeInclusive<u32>, WatchKind)> {
+        self.watchpoints
+            .iter()
+            .filter(|watchpoint| !watchpoint.internal)
+            .map(|watchpoint| (watchpoint.id, watchpoint.space, watchpoint.range.clone(), watchpoint.kind))
+            .collect()
+    }
+
+    /// Returns and clears the access that hit a pausing watchpoint, if there was one since the last call.
+    pub fn take_watch_hit(&mut self) -> Option<BusAccess> {
+        self.watch_hit.take()
+    }
+
+    fn push_watchpoint(
+        &mut self,
+        space: AddressSpace,
+        range: RangeInclusive<u32>,
+        kind: WatchKind,
+        action: WatchAction,
+        internal: bool,
+    ) -> WatchId {
+        let id = WatchId(self.next_watch_id);
+        self.next_watch_id += 1;
+
+        self.watchpoints.push(Watchpoint {
+            id,
+            space,
+            range,
+            kind,
+            action,
+            internal,
+        });
+
+        id
+    }
+
+    // Keeps the watchpoints `keep` is true for, whether any was removed.
+    fn retain_watchpoints(&mut self, keep: impl Fn(&Watchpoint) -> bool) -> bool {
+        let count = self.watchpoints.len();
+        self.watchpoints.retain(keep);
+
+        self.watchpoints.len() != count
+    }
+
+    pub(super) fn check_watchpoints(&mut self, space: AddressSpace, address: u32, rw: ReadWrite, data: u16, word: bool) {
+        let last = address + word as u32;
+
+        for watchpoint in &mut self.watchpoints {
+            let kind_matches = match watchpoint.kind {
+                WatchKind::Read => rw == ReadWrite::Read,
+                WatchKind::Write => rw == ReadWrite::Write,
+                WatchKind::Access => true,
+            };
+
+            if watchpoint.space != space
+                || !kind_matches
+                || address > *watchpoint.range.end()
+                || last < *watchpoint.range.start()
+            {
+                continue;
+            }
+
+            let access = BusAccess {
+                space,
+                address,
+                rw,
+                data,
+                word,
+                pc: self.inst_pc,
+                watchpoint: watchpoint.id,
+            };
+
+            match &mut watchpoint.action {
+                WatchAction::Pause => {
+                    self.watch_hit.get_or_insert(access);
+                }
+                WatchAction::Callback(callback) => callback(&access),
+            }
+        }
+    }
+}
@@ -0,0 +1,658 @@
+//! GDB Remote Serial Protocol server on top of the [`Debugger`]. Connect with `target remote localhost:<port>`.
+//!
+//! Registers are sent in the order of [`TARGET_XML`] and, like memory, in big endian byte order. Breakpoints do
+//! not patch memory, they use the debugger breakpoints, so they also work for code in ROM. Watchpoints are
+//! checked on the memory bus by the CPU.
+//!
+//! Stock GDB has no HexaCore architecture, so the description names none and GDB keeps its default one, see the
+//! GDB section of `doc/debugger.md`. The stub is only tested against the scripted client in the tests below.
+
+use std::{
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    ops::RangeInclusive,
+};
+
+use super::{Debugger, Stop};
+use crate::cpu::{AddressSpace, BusAccess, EmuError, Exception, ExtendedAddress, StackAddress, WatchKind};
+
+/// Description of the register file. There is no `<architecture>`, a GDB without a HexaCore port has none to
+/// name, so it only lays out the `g` packet of a GDB that accepts the description on its default architecture.
+pub const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.hexacore.cpu">
+    <flags id="hexacore_flags" size="2">
+      <field name="Z" start="0" end="0"/>
+      <field name="N" start="1" end="1"/>
+      <field name="C" start="2" end="2"/>
+      <field name="O" start="3" end="3"/>
+      <field name="L" start="4" end="4"/>
+      <field name="G" start="5" end="5"/>
+      <field name="I" start="6" end="6"/>
+    </flags>
+    <reg name="ra" bitsize="16" type="uint16" regnum="0"/>
+    <reg name="rb" bitsize="16" type="uint16"/>
+    <reg name="rc" bitsize="16" type="uint16"/>
+    <reg name="rd" bitsize="16" type="uint16"/>
+    <reg name="pc" bitsize="24" type="code_ptr"/>
+    <reg name="sp" bitsize="24" type="data_ptr"/>
+    <reg name="flags" bitsize="16" type="hexacore_flags"/>
+  </feature>
+</target>
+"#;
+
+// Size of every register in bytes, in the order of the target description.
+const REGISTER_SIZES: [usize; 7] = [2, 2, 2, 2, 3, 3, 2];
+
+// Instructions run between checks for a ^C from GDB while continuing.
+const CONTINUE_BATCH: u64 = 1000;
+
+// Signals used in stop replies.
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+/// Waits for a single GDB connection on `address` and serves it until GDB detaches or kills the program.
+pub fn listen(debugger: &mut Debugger, address: impl ToSocketAddrs) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    let (stream, _) = listener.accept()?;
+
+    serve(debugger, stream)
+}
+
+/// Serves a GDB session on an accepted connection.
+pub fn serve(debugger: &mut Debugger, stream: TcpStream) -> io::Result<()> {
+    // Every packet waits for an ack, so don't let small writes sit in the send buffer.
+    stream.set_nodelay(true)?;
+
+    let mut session = Session {
+        writer: stream.try_clone()?,
+        reader: BufReader::new(stream),
+        no_ack: false,
+        last_stop: format!("S{SIGTRAP:02x}"),
+        debugger,
+    };
+
+    while let Some(packet) = session.read_packet()? {
+        let reply = match packet.as_str() {
+            "k" => return Ok(()),
+            "D" => {
+                session.write_packet("OK")?;
+                return Ok(());
+            }
+            _ => session.handle(&packet)?,
+        };
+
+        session.write_packet(&reply)?;
+    }
+
+    Ok(())
+}
+
+struct Session<'a> {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    no_ack: bool,
+    // The reply to the last `s` or `c`, `?` repeats it. Before the first one the program stopped after the reset.
+    last_stop: String,
+    debugger: &'a mut Debugger,
+}
+
+impl Session<'_> {
+    fn handle(&mut self, packet: &str) -> io::Result<String> {
+        let Some(command) = packet.get(..1) else {
+            return Ok(String::new());
+        };
+        let args = &packet[1..];
+
+        let reply = match command {
+            "?" => self.last_stop.clone(),
+            "g" => self.read_registers(),
+            "G" => ok_or_error(self.write_registers(args)),
+            "p" => match parse_hex(args).and_then(|register| self.read_register(register as usize)) {
+                Some(value) => value,
+                None => error(),
+            },
+            "P" => {
+                let written = args
+                    .split_once('=')
+                    .and_then(|(register, value)| Some((parse_hex(register)? as usize, value)))
+                    .and_then(|(register, value)| self.write_register(register, value));
+
+                ok_or_error(written)
+            }
+            "m" => match parse_range(args) {
+                Some((address, length)) => match self.debugger.read_memory(address, length) {
+                    bytes if bytes.is_empty() && length > 0 => error(),
+                    bytes => encode_hex(&bytes),
+                },
+                None => error(),
+            },
+            "M" => {
+                let written = args.split_once(':').and_then(|(range, data)| {
+                    let (address, length) = parse_range(range)?;
+                    let bytes = decode_hex(data).filter(|bytes| bytes.len() == length as usize)?;
+
+                    self.debugger.write_memory(address, &bytes).ok()
+                });
+
+                ok_or_error(written)
+            }
+            "Z" | "z" => {
+                let point = match args.split(',').collect::<Vec<_>>()[..] {
+                    [kind, address, length] => parse_hex(address).zip(parse_hex(length)).map(|(address, length)| (kind, address, length)),
+                    _ => None,
+                };
+
+                match point {
+                    // Software and hardware breakpoints are the same thing here.
+                    Some(("0" | "1", address, _)) => {
+                        match command {
+                            "Z" => self.debugger.add_breakpoint(address),
+                            _ => self.debugger.remove_breakpoint(address),
+                        };
+
+                        "OK".into()
+                    }
+                    Some((kind @ ("2" | "3" | "4"), address, length)) => {
+                        let kind = match kind {
+                            "2" => WatchKind::Write,
+                            "3" => WatchKind::Read,
+                            _ => WatchKind::Access,
+                        };
+                        let end = match address.checked_add(length.max(1) - 1) {
+                            Some(end) if end <= 0xFF_FFFF => end,
+                            _ => return Ok(error()),
+                        };
+
+                        match command {
+                            "Z" => {
+                                self.debugger.add_watchpoint(AddressSpace::Memory, address, end, kind);
+                            }
+                            _ => self.remove_watchpoint(address..=end, kind),
+                        }
+
+                        "OK".into()
+                    }
+                    _ => String::new(),
+                }
+            }
+            "s" => {
+                self.resume_at(args);
+
+                self.last_stop = match self.debugger.step_instructions(1) {
+                    Ok(Stop::Halted(code)) => exited(code),
+                    Ok(Stop::Watchpoint(access)) => self.watch_reply(&access),
+                    Ok(_) => format!("S{SIGTRAP:02x}"),
+                    Err(err) => fault(&err),
+                };
+                self.last_stop.clone()
+            }
+            "c" => {
+                self.resume_at(args);
+                self.last_stop = self.resume()?;
+                self.last_stop.clone()
+            }
+            "H" => "OK".into(),
+            "q" | "Q" => self.query(packet),
+            _ => String::new(),
+        };
+
+        Ok(reply)
+    }
+
+    fn query(&mut self, packet: &str) -> String {
+        if let Some(annex) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            return match parse_range(annex) {
+                Some((offset, length)) => {
+                    let rest = TARGET_XML.get(offset as usize..).unwrap_or("");
+                    let chunk = &rest[..rest.len().min(length as usize)];
+                    let marker = if chunk.len() < rest.len() { 'm' } else { 'l' };
+
+                    format!("{marker}{chunk}")
+                }
+                None => error(),
+            };
+        }
+
+        match packet.split(':').next().unwrap_or("") {
+            "qSupported" => "PacketSize=4000;qXfer:features:read+;QStartNoAckMode+;swbreak+".into(),
+            "QStartNoAckMode" => {
+                self.no_ack = true;
+                "OK".into()
+            }
+            "qAttached" => "1".into(),
+            "qC" => "QC1".into(),
+            "qfThreadInfo" => "m1".into(),
+            "qsThreadInfo" => "l".into(),
+            _ => String::new(),
+        }
+    }
+
+    // `c` and `s` can carry an address to resume at.
+    fn resume_at(&mut self, address: &str) {
+        if let Some(address) = parse_hex(address) {
+            let cpu = self.debugger.machine_mut().cpu_mut();
+            let mut state = cpu.state();
+
+            state.pc = ExtendedAddress::new_ext_address(address);
+            cpu.set_state(&state);
+        }
+    }
+
+    // Runs in batches, so a ^C from GDB can stop a program that never reaches a breakpoint.
+    fn resume(&mut self) -> io::Result<String> {
+        loop {
+            let reply = match self.debugger.step_instructions(CONTINUE_BATCH) {
+                Ok(Stop::Stepped) if self.interrupted()? => format!("S{SIGINT:02x}"),
+                Ok(Stop::Stepped) => continue,
+                Ok(Stop::Breakpoint(_)) => format!("T{SIGTRAP:02x}swbreak:;"),
+                Ok(Stop::Halted(code)) => exited(code),
+                Ok(Stop::Returned) => format!("S{SIGTRAP:02x}"),
+                Ok(Stop::Watchpoint(access)) => self.watch_reply(&access),
+                Err(err) => fault(&err),
+            };
+
+            return Ok(reply);
+        }
+    }
+
+    fn watch_reply(&self, access: &BusAccess) -> String {
+        let kind = self
+            .debugger
+            .machine()
+            .cpu()
+            .watchpoints()
+            .into_iter()
+            .find(|(id, ..)| *id == access.watchpoint)
+            .map(|(.., kind)| kind);
+
+        let name = match kind {
+            Some(WatchKind::Read) => "rwatch",
+            Some(WatchKind::Access) => "awatch",
+            _ => "watch",
+        };
+
+        format!("T{SIGTRAP:02x}{name}:{:x};", access.address)
+    }
+
+    fn remove_watchpoint(&mut self, range: RangeInclusive<u32>, kind: WatchKind) {
+        let found = self
+            .debugger
+            .machine()
+            .cpu()
+            .watchpoints()
+            .into_iter()
+            .find(|watchpoint| watchpoint.1 == AddressSpace::Memory && watchpoint.2 == range && watchpoint.3 == kind);
+
+        if let Some((id, ..)) = found {
+            self.debugger.remove_watchpoint(id);
+        }
+    }
+
+    fn interrupted(&mut self) -> io::Result<bool> {
+        if self.reader.buffer().is_empty() {
+            self.reader.get_ref().set_nonblocking(true)?;
+            let filled = self.reader.fill_buf().map(|_| ());
+            self.reader.get_ref().set_nonblocking(false)?;
+
+            match filled {
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(false),
+                result => result?,
+            }
+        }
+
+        if self.reader.buffer().first() == Some(&0x03) {
+            self.reader.consume(1);
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+
+    fn registers(&self) -> [u32; 7] {
+        let state = self.debugger.machine().cpu().state();
+
+        [
+            state.ra as u32,
+            state.rb as u32,
+            state.rc as u32,
+            state.rd as u32,
+            u32::from(state.pc),
+            u32::from(state.sp),
+            state.flags as u32,
+        ]
+    }
+
+    fn read_registers(&self) -> String {
+        self.registers()
+            .iter()
+            .zip(REGISTER_SIZES)
+            .map(|(value, size)| encode_hex(&value.to_be_bytes()[4 - size..]))
+            .collect()
+    }
+
+    fn read_register(&self, register: usize) -> Option<String> {
+        let size = *REGISTER_SIZES.get(register)?;
+
+        Some(encode_hex(&self.registers()[register].to_be_bytes()[4 - size..]))
+    }
+
+    fn write_registers(&mut self, data: &str) -> Option<()> {
+        let bytes = decode_hex(data).filter(|bytes| bytes.len() == REGISTER_SIZES.iter().sum::<usize>())?;
+        let mut values = [0; 7];
+        let mut offset = 0;
+
+        for (value, size) in values.iter_mut().zip(REGISTER_SIZES) {
+            *value = bytes[offset..offset + size].iter().fold(0, |value, &byte| value << 8 | byte as u32);
+            offset += size;
+        }
+
+        self.set_registers(values);
+        Some(())
+    }
+
+    fn write_register(&mut self, register: usize, data: &str) -> Option<()> {
+        let bytes = decode_hex(data).filter(|bytes| Some(&bytes.len()) == REGISTER_SIZES.get(register))?;
+        let mut values = self.registers();
+
+        values[register] = bytes.iter().fold(0, |value, &byte| value << 8 | byte as u32);
+
+        self.set_registers(values);
+        Some(())
+    }
+
+    fn set_registers(&mut self, [ra, rb, rc, rd, pc, sp, flags]: [u32; 7]) {
+        let cpu = self.debugger.machine_mut().cpu_mut();
+        let mut state = cpu.state();
+
+        state.ra = ra as u16;
+        state.rb = rb as u16;
+        state.rc = rc as u16;
+        state.rd = rd as u16;
+        state.pc = ExtendedAddress::new_ext_address(pc);
+        state.sp = StackAddress::new(sp as u16, Some((sp >> 16) as u8));
+        state.flags = flags as u16;
+
+        cpu.set_state(&state);
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+
+        match self.reader.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    // Returns `None` once GDB closed the connection. Acks and stray ^Cs while stopped are skipped.
+    fn read_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            loop {
+                match self.read_byte()? {
+                    None => return Ok(None),
+                    Some(b'$') => break,
+                    Some(_) => continue,
+                }
+            }
+
+            let mut data = Vec::new();
+
+            loop {
+                match self.read_byte()? {
+                    None => return Ok(None),
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                }
+            }
+
+            let mut checksum = [0; 2];
+            self.reader.read_exact(&mut checksum)?;
+
+            let valid = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|checksum| u8::from_str_radix(checksum, 16).ok())
+                .is_some_and(|checksum| checksum == data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)));
+
+            if !self.no_ack {
+                self.writer.write_all(if valid { b"+" } else { b"-" })?;
+            }
+
+            if valid || self.no_ack {
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+        }
+    }
+
+    fn write_packet(&mut self, data: &str) -> io::Result<()> {
+        let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+
+        loop {
+            write!(self.writer, "${data}#{checksum:02x}")?;
+
+            if self.no_ack {
+                return Ok(());
+            }
+
+            // Resend until GDB acknowledges the packet.
+            match self.read_byte()? {
+                Some(b'-') => continue,
+                _ => return Ok(()),
+            }
+        }
+    }
+}
+
+fn exited(code: u16) -> String {
+    format!("W{:02x}", code as u8)
+}
+
+fn fault(err: &EmuError) -> String {
+    let signal = match err.exception() {
+        Some((Exception::IllegalInstruction, _)) => SIGILL,
+        Some((Exception::BusError | Exception::RomWrite, _)) => SIGSEGV,
+        None => SIGTRAP,
+    };
+
+    format!("S{signal:02x}")
+}
+
+fn error() -> String {
+    "E01".into()
+}
+
+fn ok_or_error(result: Option<()>) -> String {
+    match result {
+        Some(()) => "OK".into(),
+        None => error(),
+    }
+}
+
+fn parse_hex(value: &str) -> Option<u32> {
+    u32::from_str_radix(value, 16).ok()
+}
+
+// `addr,length`, as used by `m`, `M` and `qXfer`.
+fn parse_range(value: &str) -> Option<(u32, u32)> {
+    let (address, length) = value.split_once(',')?;
+
+    Some((parse_hex(address)?, parse_hex(length)?))
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn decode_hex(data: &str) -> Option<Vec<u8>> {
+    if !data.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..data.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(data.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debugger::tests::debugger;
+    use std::thread;
+
+    // Sends every packet in order over loopback and returns the replies, the session ends with `k`. A `\x03`
+    // is sent raw right after the packet before it, which is how GDB interrupts a `c`.
+    fn exchange(packets: &'static [&'static str]) -> Vec<String> {
+        let mut debugger = debugger();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            stream.set_nodelay(true).unwrap();
+            let mut replies = Vec::new();
+            let packets: Vec<&str> = packets.iter().copied().chain(["k"]).collect();
+
+            for (index, packet) in packets.iter().enumerate() {
+                if *packet == "\x03" {
+                    continue;
+                }
+
+                let checksum = packet.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+                write!(stream, "${packet}#{checksum:02x}").unwrap();
+
+                let mut ack = [0];
+                stream.read_exact(&mut ack).unwrap();
+                assert_eq!(ack[0], b'+');
+
+                if *packet == "k" {
+                    break;
+                }
+
+                if packets.get(index + 1) == Some(&"\x03") {
+                    stream.write_all(b"\x03").unwrap();
+                }
+
+                // Read the reply up to and including the checksum, then acknowledge it.
+                let mut reply = Vec::new();
+                let mut byte = [0];
+
+                while byte[0] != b'#' {
+                    stream.read_exact(&mut byte).unwrap();
+                    reply.push(byte[0]);
+                }
+
+                stream.read_exact(&mut [0; 2]).unwrap();
+                stream.write_all(b"+").unwrap();
+
+                let reply = String::from_utf8(reply).unwrap();
+                replies.push(reply[1..reply.len() - 1].to_string());
+            }
+
+            replies
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        serve(&mut debugger, stream).unwrap();
+
+        client.join().unwrap()
+    }
+
+    #[test]
+    fn test_registers_and_memory() {
+        let replies = exchange(&[
+            "?",
+            "g",
+            "P2=abcd",
+            "p2",
+            "m100,5",
+            "M10000,2:4142",
+            "m10000,2",
+            "M0,1:00",
+            "Mffffffff,2:0102",
+            "m1000000,1",
+            "vMustReplyEmpty",
+        ]);
+
+        assert_eq!(
+            replies,
+            [
+                "S05",
+                "00000000000000000001000100000000",
+                "OK",
+                "abcd",
+                "3b00000001",
+                "OK",
+                "4142",
+                "E01",
+                "E01",
+                "E01",
+                "",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_breakpoints_and_stepping() {
+        let replies = exchange(&["Z0,113,1", "c", "p4", "s", "p4", "p1", "z0,113,1", "c"]);
+
+        assert_eq!(replies, ["OK", "T05swbreak:;", "000113", "S05", "000118", "0002", "OK", "W03"]);
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        // The JSR pushes its frame to $010000-$010003, the RTS reads it back.
+        let replies = exchange(&[
+            "Z2,10000,4",
+            "c",
+            "?",
+            "p4",
+            "z2,10000,4",
+            "Z3,10003,1",
+            "c",
+            "z3,10003,1",
+            "Z2,ffffffff,4",
+            "Z4,fffffe,4",
+            "c",
+            "?",
+        ]);
+
+        assert_eq!(
+            replies,
+            [
+                "OK",
+                "T05watch:10000;",
+                "T05watch:10000;",
+                "00010b",
+                "OK",
+                "OK",
+                "T05rwatch:10003;",
+                "OK",
+                "E01",
+                "E01",
+                "W03",
+                "W03",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_interrupt() {
+        // `jmp $010100` in RAM never stops on its own.
+        let replies = exchange(&["M10100,6:480000010100", "c10100", "\x03", "?", "p4"]);
+
+        assert_eq!(replies, ["OK", "S02", "S02", "010100"]);
+    }
+
+    #[test]
+    fn test_target_description() {
+        let replies = exchange(&["qSupported:swbreak+", "qXfer:features:read:target.xml:0,10", "qXfer:features:read:target.xml:10,4000"]);
+
+        assert!(replies[0].contains("qXfer:features:read+"));
+        assert_eq!(replies[1], format!("m{}", &TARGET_XML[..0x10]));
+        assert_eq!(replies[2], format!("l{}", &TARGET_XML[0x10..]));
+    }
+}
@@ -0,0 +1,848 @@
+//! Interactive monitor around a [`Machine`]: breakpoints, stepping, registers and memory. The stepping methods
+//! can be used on their own, [`Debugger::execute`] and [`Debugger::repl`] add the command prompt on top and
+//! [`gdb`] serves them to GDB.
+
+pub mod gdb;
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Display,
+    io::{self, BufRead, Write},
+};
+
+use crate::{
+    cpu::{
+        AddressSpace, BusAccess, CPUPhase, CycleStatus, EmuError, ExtendedAddress, Flag, ReadWrite, StackAddress,
+        WatchAction, WatchId, WatchKind,
+    },
+    device::DeviceResult,
+    disasm::{Disassembler, Line},
+    info::InstructionInfoFile,
+    machine::Machine,
+    util::parse_number,
+};
+
+const HELP: &str = "\
+break <addr>        b    Set a breakpoint
+delete <addr>       d    Remove a breakpoint
+breakpoints         bl   List the breakpoints
+step [n]            s    Run n instructions
+cycle [n]           sc   Run n cycles
+continue            c    Run until a breakpoint or HLT
+return              ret  Run until the current subroutine or interrupt handler returns
+regs                r    Print the registers, flags and stack pointer
+set <reg> <value>        Change ra, rb, rc, rd, pc, sp or flags
+mem <addr> [len]    x    Dump memory
+write <addr> <b>... w    Write bytes to memory
+dis [addr] [n]           Disassemble n instructions, from the PC by default
+watch [io] <addr> [end]  Pause after writes to memory or an IO port
+rwatch [io] <addr> [end] Pause after reads
+awatch [io] <addr> [end] Pause after reads and writes
+unwatch <id>             Remove a watchpoint
+watchpoints         wl   List the watchpoints
+reset                    Reset the CPU
+quit                q    Leave the debugger
+Addresses and values are written as $0200, 0x0200 or 512. An empty line repeats the last command.";
+
+/// Why running stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    /// The requested number of instructions or cycles ran.
+    Stepped,
+    Breakpoint(u32),
+    /// The CPU halted, with the exit code from RA.
+    Halted(u16),
+    /// The subroutine or interrupt handler [`Debugger::run_to_return`] started in returned.
+    Returned,
+    /// A pausing watchpoint was hit, the CPU stopped right after the access.
+    Watchpoint(BusAccess),
+}
+
+impl Display for Stop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stop::Stepped => write!(f, "Stepped"),
+            Stop::Breakpoint(address) => write!(f, "Breakpoint at ${address:06X}"),
+            Stop::Halted(code) => write!(f, "Halted with exit code {code}"),
+            Stop::Returned => write!(f, "Returned"),
+            Stop::Watchpoint(access) => {
+                let (action, preposition) = match access.rw {
+                    ReadWrite::Read => ("Read", "from"),
+                    ReadWrite::Write => ("Write", "to"),
+                };
+
+                write!(
+                    f,
+                    "Watchpoint {}: {action} of 0x{:0width$X} {preposition} {} by the instruction at ${:06X}",
+                    access.watchpoint.0,
+                    access.data,
+                    format_location(access.space, access.address),
+                    u32::from(access.pc),
+                    width = if access.word { 4 } else { 2 },
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugError {
+    UnknownCommand(String),
+    Usage(&'static str),
+    InvalidNumber(String),
+    InvalidRange(u32, u32),
+    Memory(u32, DeviceResult),
+    Emulator(EmuError),
+}
+
+impl Display for DebugError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DebugError::UnknownCommand(command) => write!(f, "Unknown command '{command}', try 'help'"),
+            DebugError::Usage(usage) => write!(f, "Usage: {usage}"),
+            DebugError::InvalidNumber(number) => write!(f, "Invalid number '{number}'"),
+            DebugError::InvalidRange(start, end) => write!(f, "Invalid range, ${end:06X} is before ${start:06X}"),
+            DebugError::Memory(address, res) => write!(f, "Could not access ${address:06X}: {res:?}"),
+            DebugError::Emulator(err) => write!(f, "Emulator Error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for DebugError {}
+
+impl From<EmuError> for DebugError {
+    fn from(err: EmuError) -> Self {
+        DebugError::Emulator(err)
+    }
+}
+
+pub struct Debugger {
+    machine: Machine,
+    disassembler: Disassembler,
+    // Mnemonics by opcode, to tell the instructions that push and pop return frames.
+    mnemonics: HashMap<u8, String>,
+    breakpoints: BTreeSet<u32>,
+    last_command: String,
+}
+
+impl Debugger {
+    /// `inst_info` is used for disassembling and finding returns, it should be the table the CPU was built with.
+    pub fn new(machine: Machine, inst_info: InstructionInfoFile) -> Self {
+        let mnemonics = inst_info
+            .opcodes
+            .iter()
+            .map(|(opcode, entry)| (*opcode, entry.split('|').next().unwrap_or_default().to_owned()))
+            .collect();
+
+        Self {
+            machine,
+            disassembler: Disassembler::new(&inst_info),
+            mnemonics,
+            breakpoints: BTreeSet::new(),
+            last_command: String::new(),
+        }
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn machine_mut(&mut self) -> &mut Machine {
+        &mut self.machine
+    }
+
+    pub fn breakpoints(&self) -> &BTreeSet<u32> {
+        &self.breakpoints
+    }
+
+    /// Breakpoints stop execution before the instruction at `address` is fetched. Returns false if there
+    /// already was one.
+    pub fn add_breakpoint(&mut self, address: u32) -> bool {
+        self.breakpoints.insert(address & 0xFF_FFFF)
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u32) -> bool {
+        self.breakpoints.remove(&(address & 0xFF_FFFF))
+    }
+
+    /// Runs `count` instructions, stopping early on a breakpoint or HLT.
+    pub fn step_instructions(&mut self, count: u64) -> Result<Stop, EmuError> {
+        for _ in 0..count {
+            let status = self.machine.step_instruction()?;
+
+            if let Some(stop) = self.check_stop(status) {
+                return Ok(stop);
+            }
+        }
+
+        Ok(Stop::Stepped)
+    }
+
+    /// Runs `count` cycles, stopping early on a breakpoint or HLT.
+    pub fn step_cycles(&mut self, count: u64) -> Result<Stop, EmuError> {
+        for _ in 0..count {
+            let status = self.machine.step_cycle()?;
+
+            if let Some(stop) = self.check_stop(status) {
+                return Ok(stop);
+            }
+        }
+
+        Ok(Stop::Stepped)
+    }
+
+    /// Runs until a breakpoint or HLT. A breakpoint on the current instruction does not stop it again.
+    pub fn resume(&mut self) -> Result<Stop, EmuError> {
+        loop {
+            let status = self.machine.step_instruction()?;
+
+            if let Some(stop) = self.check_stop(status) {
+                return Ok(stop);
+            }
+        }
+    }
+
+    /// Runs until the `RTS` or `RTI` that pops the return frame of the current subroutine or interrupt handler,
+    /// like GDB's `finish`. Frames the calls, interrupts and exceptions on the way push are counted, so their
+    /// returns don't stop it. Breakpoints and HLT stop it earlier.
+    pub fn run_to_return(&mut self) -> Result<Stop, EmuError> {
+        // Frames pushed since the start that were not popped yet.
+        let mut depth = 0u32;
+
+        loop {
+            let mnemonic = self.mnemonic_at_pc().map(str::to_owned);
+            let (status, entered) = self.step_instruction_entering()?;
+
+            match mnemonic.as_deref() {
+                Some("rts" | "rti") if depth == 0 => return Ok(self.check_stop(status).unwrap_or(Stop::Returned)),
+                Some("rts" | "rti") => depth -= 1,
+                Some("jsr") => depth += 1,
+                _ => {}
+            }
+
+            depth += entered;
+
+            if let Some(stop) = self.check_stop(status) {
+                return Ok(stop);
+            }
+        }
+    }
+
+    fn mnemonic_at_pc(&self) -> Option<&str> {
+        let cpu = self.machine.cpu();
+
+        match cpu.peek(cpu.pc(), false) {
+            DeviceResult::Ok8(opcode) => self.mnemonics.get(&opcode).map(String::as_str),
+            _ => None,
+        }
+    }
+
+    // Runs one instruction like `Machine::step_instruction`, also returning how many interrupts and exceptions
+    // were entered on the way. Each of them pushes a return frame.
+    fn step_instruction_entering(&mut self) -> Result<(CycleStatus, u32), EmuError> {
+        let mut entered = 0;
+        let mut entering = false;
+
+        loop {
+            let status = self.machine.step_cycle()?;
+            let cpu = self.machine.cpu();
+            let interrupt = cpu.state().phase == CPUPhase::Interrupt;
+
+            if interrupt && !entering {
+                entered += 1;
+            }
+
+            entering = interrupt;
+
+            if status != CycleStatus::Running || cpu.at_instruction_boundary() || cpu.is_halted() {
+                return Ok((status, entered));
+            }
+        }
+    }
+
+    fn check_stop(&self, status: CycleStatus) -> Option<Stop> {
+        let cpu = self.machine.cpu();
+
+        match status {
+            CycleStatus::Halted(code) => return Some(Stop::Halted(code)),
+            CycleStatus::Watchpoint(access) => return Some(Stop::Watchpoint(access)),
+            CycleStatus::Running => {}
+        }
+
+        // Without exit on HLT the CPU never reports it, but it will not do anything else either.
+        if cpu.is_halted() {
+            return Some(Stop::Halted(cpu.state().ra));
+        }
+
+        let pc = u32::from(cpu.pc());
+
+        (cpu.at_instruction_boundary() && self.breakpoints.contains(&pc)).then_some(Stop::Breakpoint(pc))
+    }
+
+    /// Adds a watchpoint that stops running with [`Stop::Watchpoint`].
+    pub fn add_watchpoint(&mut self, space: AddressSpace, start: u32, end: u32, kind: WatchKind) -> WatchId {
+        self.machine.cpu_mut().add_watchpoint(space, start..=end, kind, WatchAction::Pause)
+    }
+
+    pub fn remove_watchpoint(&mut self, id: WatchId) -> bool {
+        self.machine.cpu_mut().remove_watchpoint(id)
+    }
+
+    /// Reads `count` bytes, stopping at the first address without a readable device.
+    pub fn read_memory(&self, address: u32, count: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        for address in address..address.saturating_add(count).min(0x100_0000) {
+            match self.machine.cpu().peek(ExtendedAddress::new_ext_address(address), false) {
+                DeviceResult::Ok8(byte) => bytes.push(byte),
+                DeviceResult::Ok16(word) => bytes.push(word as u8),
+                _ => break,
+            }
+        }
+
+        bytes
+    }
+
+    /// Writes `bytes` from `address` on, the whole range has to be inside the 24 bit address space.
+    pub fn write_memory(&mut self, address: u32, bytes: &[u8]) -> Result<(), DebugError> {
+        let end = u32::try_from(bytes.len()).ok().and_then(|length| address.checked_add(length));
+
+        if end.is_none_or(|end| end > 0x100_0000) {
+            return Err(DebugError::Memory(address, DeviceResult::InvalidAddress));
+        }
+
+        for (offset, &byte) in bytes.iter().enumerate() {
+            let address = address + offset as u32;
+
+            match self.machine.cpu_mut().poke(ExtendedAddress::new_ext_address(address), byte as u16, false) {
+                DeviceResult::Ok => {}
+                res => return Err(DebugError::Memory(address, res)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Disassembles `count` instructions starting at `address`.
+    pub fn disassemble(&self, address: u32, count: usize) -> Vec<Line> {
+        // No instruction is longer than 6 bytes, so this always holds `count` complete instructions.
+        let length = u32::try_from(count).unwrap_or(u32::MAX).saturating_mul(6);
+        let bytes = self.read_memory(address, length);
+
+        self.disassembler
+            .linear(&bytes, 0, bytes.len() as u32)
+            .into_iter()
+            .take(count)
+            .map(|line| Line {
+                address: line.address + address,
+                ..line
+            })
+            .collect()
+    }
+
+    /// Runs one command line and returns what it prints. `quit` is handled by [`Debugger::repl`].
+    pub fn execute(&mut self, line: &str) -> Result<String, DebugError> {
+        let line = match line.trim() {
+            "" => self.last_command.clone(),
+            line => {
+                self.last_command = line.into();
+                line.into()
+            }
+        };
+
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(String::new());
+        };
+        let args: Vec<&str> = words.collect();
+
+        match command.to_lowercase().as_str() {
+            "help" | "h" => Ok(HELP.into()),
+            "break" | "b" => {
+                let address = single_number(&args, "break <addr>")?;
+                self.add_breakpoint(address);
+                Ok(format!("Breakpoint at ${address:06X}"))
+            }
+            "delete" | "d" => {
+                let address = single_number(&args, "delete <addr>")?;
+
+                match self.remove_breakpoint(address) {
+                    true => Ok(format!("Deleted breakpoint at ${address:06X}")),
+                    false => Ok(format!("No breakpoint at ${address:06X}")),
+                }
+            }
+            "breakpoints" | "bl" => {
+                let lines: Vec<String> = self.breakpoints.iter().map(|address| format!("${address:06X}")).collect();
+
+                match lines.is_empty() {
+                    true => Ok("No breakpoints".into()),
+                    false => Ok(lines.join("\n")),
+                }
+            }
+            "step" | "s" => {
+                let count = optional_number(&args, 1, "step [n]")?;
+                let stop = self.step_instructions(count as u64)?;
+                Ok(self.report(stop))
+            }
+            "cycle" | "sc" => {
+                let count = optional_number(&args, 1, "cycle [n]")?;
+                let stop = self.step_cycles(count as u64)?;
+                Ok(self.report(stop))
+            }
+            "continue" | "c" => {
+                let stop = self.resume()?;
+                Ok(self.report(stop))
+            }
+            "return" | "ret" => {
+                let stop = self.run_to_return()?;
+                Ok(self.report(stop))
+            }
+            "watch" | "rwatch" | "awatch" => {
+                let kind = match command.to_lowercase().as_str() {
+                    "watch" => WatchKind::Write,
+                    "rwatch" => WatchKind::Read,
+                    _ => WatchKind::Access,
+                };
+                let (space, range) = match args.split_first() {
+                    Some((&"io", range)) => (AddressSpace::Io, range),
+                    _ => (AddressSpace::Memory, &args[..]),
+                };
+                let (start, end) = match range {
+                    [start] => (number(start)?, number(start)?),
+                    [start, end] => (number(start)?, number(end)?),
+                    _ => return Err(DebugError::Usage("watch [io] <addr> [end]")),
+                };
+
+                if end < start {
+                    return Err(DebugError::InvalidRange(start, end));
+                }
+
+                let id = self.add_watchpoint(space, start, end, kind);
+                Ok(format!("Watchpoint {} on {}", id.0, format_range(space, start, end)))
+            }
+            "unwatch" => {
+                let id = WatchId(single_number(&args, "unwatch <id>")?);
+
+                match self.remove_watchpoint(id) {
+                    true => Ok(format!("Deleted watchpoint {}", id.0)),
+                    false => Ok(format!("No watchpoint {}", id.0)),
+                }
+            }
+            "watchpoints" | "wl" => {
+                let lines: Vec<String> = self
+                    .machine
+                    .cpu()
+                    .watchpoints()
+                    .into_iter()
+                    .map(|(id, space, range, kind)| {
+                        format!("{}: {kind:?} {}", id.0, format_range(space, *range.start(), *range.end()))
+                    })
+                    .collect();
+
+                match lines.is_empty() {
+                    true => Ok("No watchpoints".into()),
+                    false => Ok(lines.join("\n")),
+                }
+            }
+            "regs" | "r" => Ok(self.registers()),
+            "set" => self.set_register(&args),
+            "mem" | "x" => {
+                let (address, count) = match args[..] {
+                    [address] => (number(address)?, 64),
+                    [address, count] => (number(address)?, number(count)?),
+                    _ => return Err(DebugError::Usage("mem <addr> [len]")),
+                };
+
+                Ok(hex_dump(address, &self.read_memory(address, count)))
+            }
+            "write" | "w" => {
+                let [address, values @ ..] = &args[..] else {
+                    return Err(DebugError::Usage("write <addr> <byte>..."));
+                };
+
+                let address = number(address)?;
+                let bytes = values
+                    .iter()
+                    .map(|value| match number(value)? {
+                        byte @ 0..=0xFF => Ok(byte as u8),
+                        _ => Err(DebugError::InvalidNumber(value.to_string())),
+                    })
+                    .collect::<Result<Vec<u8>, DebugError>>()?;
+
+                if bytes.is_empty() {
+                    return Err(DebugError::Usage("write <addr> <byte>..."));
+                }
+
+                self.write_memory(address, &bytes)?;
+                Ok(format!("Wrote {} byte(s) at ${address:06X}", bytes.len()))
+            }
+            "dis" => {
+                let pc = u32::from(self.machine.cpu().pc());
+                let (address, count) = match args[..] {
+                    [] => (pc, 8),
+                    [address] => (number(address)?, 8),
+                    [address, count] => (number(address)?, number(count)? as usize),
+                    _ => return Err(DebugError::Usage("dis [addr] [n]")),
+                };
+
+                // Memory can not hold more instructions than it has bytes.
+                if count > 0x100_0000 {
+                    return Err(DebugError::InvalidNumber(count.to_string()));
+                }
+
+                let lines: Vec<String> = self
+                    .disassemble(address, count)
+                    .iter()
+                    .map(|line| format_line(line, line.address == pc))
+                    .collect();
+
+                Ok(lines.join("\n"))
+            }
+            "reset" => {
+                self.machine.reset();
+                Ok(self.current_line())
+            }
+            _ => Err(DebugError::UnknownCommand(command.into())),
+        }
+    }
+
+    /// Reads commands from `input` until `quit` or the end of the input, printing results and errors to
+    /// `output`.
+    pub fn repl(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        writeln!(output, "{}", self.current_line())?;
+        write!(output, "(hexacore) ")?;
+        output.flush()?;
+
+        for line in input.lines() {
+            let line = line?;
+
+            if matches!(line.trim(), "quit" | "q") {
+                return Ok(());
+            }
+
+            match self.execute(&line) {
+                Ok(text) if text.is_empty() => {}
+                Ok(text) => writeln!(output, "{text}")?,
+                Err(err) => writeln!(output, "{err}")?,
+            }
+
+            write!(output, "(hexacore) ")?;
+            output.flush()?;
+        }
+
+        writeln!(output)
+    }
+
+    fn report(&self, stop: Stop) -> String {
+        format!("{stop}\n{}", self.current_line())
+    }
+
+    fn current_line(&self) -> String {
+        let pc = u32::from(self.machine.cpu().pc());
+
+        match self.disassemble(pc, 1).first() {
+            Some(line) => format_line(line, true),
+            None => format!("> {pc:06X}: (no device)"),
+        }
+    }
+
+    fn registers(&self) -> String {
+        let state = self.machine.cpu().state();
+        let flags = Flag::from_bits_retain(state.flags);
+
+        format!(
+            "ra=0x{:04X} rb=0x{:04X} rc=0x{:04X} rd=0x{:04X}\npc=${:06X} sp=${:06X} flags={flags} ({:?} cycle {})",
+            state.ra,
+            state.rb,
+            state.rc,
+            state.rd,
+            u32::from(state.pc),
+            u32::from(state.sp),
+            state.phase,
+            state.cycle,
+        )
+    }
+
+    fn set_register(&mut self, args: &[&str]) -> Result<String, DebugError> {
+        const USAGE: &str = "set <ra|rb|rc|rd|pc|sp|flags> <value>";
+
+        let [register, value] = args else {
+            return Err(DebugError::Usage(USAGE));
+        };
+
+        let value = number(value)?;
+        let mut state = self.machine.cpu().state();
+
+        match register.to_lowercase().as_str() {
+            "ra" => state.ra = value as u16,
+            "rb" => state.rb = value as u16,
+            "rc" => state.rc = value as u16,
+            "rd" => state.rd = value as u16,
+            "flags" => state.flags = value as u16,
+            "pc" => state.pc = ExtendedAddress::new_ext_address(value),
+            "sp" => state.sp = StackAddress::new(value as u16, Some((value >> 16) as u8)),
+            _ => return Err(DebugError::Usage(USAGE)),
+        }
+
+        self.machine.cpu_mut().set_state(&state);
+        Ok(self.registers())
+    }
+}
+
+fn number(value: &str) -> Result<u32, DebugError> {
+    parse_number(value).ok_or_else(|| DebugError::InvalidNumber(value.into()))
+}
+
+fn single_number(args: &[&str], usage: &'static str) -> Result<u32, DebugError> {
+    match args {
+        [value] => number(value),
+        _ => Err(DebugError::Usage(usage)),
+    }
+}
+
+fn optional_number(args: &[&str], default: u32, usage: &'static str) -> Result<u32, DebugError> {
+    match args {
+        [] => Ok(default),
+        [value] => number(value),
+        _ => Err(DebugError::Usage(usage)),
+    }
+}
+
+fn format_location(space: AddressSpace, address: u32) -> String {
+    match space {
+        AddressSpace::Memory => format!("${address:06X}"),
+        AddressSpace::Io => format!("port 0x{address:02X}"),
+    }
+}
+
+fn format_range(space: AddressSpace, start: u32, end: u32) -> String {
+    match start == end {
+        true => format_location(space, start),
+        false => format!("{}-{}", format_location(space, start), format_location(space, end)),
+    }
+}
+
+fn format_line(line: &Line, current: bool) -> String {
+    let bytes: Vec<String> = line.bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+    let marker = if current { '>' } else { ' ' };
+
+    format!("{marker} {:06X}: {:<17}  {}", line.address, bytes.join(" "), line.text)
+}
+
+fn hex_dump(address: u32, bytes: &[u8]) -> String {
+    let mut lines: Vec<String> = bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(row, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|byte| format!("{byte:02X}")).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+                .collect();
+
+            format!("{:06X}: {:<47}  |{ascii}|", address + row as u32 * 16, hex.join(" "))
+        })
+        .collect();
+
+    if bytes.is_empty() {
+        lines.push(format!("${address:06X} is not mapped"));
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asm::assemble,
+        cpu::{EmuOptions, CPU},
+        device::{RAM, ROM},
+        info::get_instructions,
+    };
+
+    const PROGRAM: &str = "
+        .byte 0x00 .word $0100
+        .org $0100
+        main:
+            mov ra, 0x0001
+            jsr sub
+            mov ra, 0x0003
+            hlt
+        sub:
+            mov rb, 0x0002
+            rts";
+
+    pub(super) fn debugger() -> Debugger {
+        debugger_for(PROGRAM)
+    }
+
+    fn debugger_for(program: &str) -> Debugger {
+        let image = assemble(program, &get_instructions()).unwrap().image;
+
+        let mut machine = Machine::new(CPU::new(get_instructions(), Some(EmuOptions::new_value(1))));
+        machine.add_device(ROM::new(
+            ExtendedAddress::new_ext_address(0x00_0000),
+            ExtendedAddress::new_ext_address(0x00_FFFF),
+            image,
+        ));
+        machine.add_device(RAM::new(
+            ExtendedAddress::new_ext_address(0x01_0000),
+            ExtendedAddress::new_ext_address(0x01_FFFF),
+        ));
+
+        let mut debugger = Debugger::new(machine, get_instructions());
+        // Run the reset sequence, so the PC is on the first instruction.
+        debugger.step_instructions(1).unwrap();
+        debugger
+    }
+
+    fn pc(debugger: &Debugger) -> u32 {
+        u32::from(debugger.machine().cpu().pc())
+    }
+
+    #[test]
+    fn test_breakpoints_and_return() {
+        let mut debugger = debugger();
+        assert_eq!(pc(&debugger), 0x100);
+
+        debugger.add_breakpoint(0x113);
+        assert_eq!(debugger.resume(), Ok(Stop::Breakpoint(0x113)));
+        assert_eq!(debugger.run_to_return(), Ok(Stop::Returned));
+        assert_eq!(pc(&debugger), 0x10B);
+        assert_eq!(debugger.machine().cpu().state().rb, 2);
+
+        assert_eq!(debugger.resume(), Ok(Stop::Halted(3)));
+    }
+
+    #[test]
+    fn test_return_with_pops_and_calls() {
+        let mut debugger = debugger_for(
+            "
+            .byte 0x00 .word $0100
+            .org $0100
+            main:
+                jsr sub
+                hlt
+            sub:
+                psh ra
+                pop rb
+                jsr nested
+                rts
+            nested:
+                rts",
+        );
+
+        // Neither the pop below the stack pointer it started at nor the return of the nested call stop it.
+        debugger.step_instructions(2).unwrap();
+        assert_eq!(debugger.run_to_return(), Ok(Stop::Returned));
+        assert_eq!(pc(&debugger), 0x106);
+        assert_eq!(debugger.machine().cpu().state().sp.get_value(), 0x0000);
+    }
+
+    #[test]
+    fn test_step() {
+        let mut debugger = debugger();
+
+        assert_eq!(debugger.step_instructions(2), Ok(Stop::Stepped));
+        assert_eq!(pc(&debugger), 0x113);
+
+        // A breakpoint stops stepping early.
+        debugger.add_breakpoint(0x118);
+        assert_eq!(debugger.step_instructions(5), Ok(Stop::Breakpoint(0x118)));
+
+        debugger.remove_breakpoint(0x118);
+        assert_eq!(debugger.step_cycles(1), Ok(Stop::Stepped));
+        assert_eq!(debugger.machine().cpu().state().cycle, 2);
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let mut debugger = debugger();
+
+        // The JSR pushes its frame to $010000-$010003, the return address is written as one word.
+        assert_eq!(debugger.execute("watch $010002 $010003").unwrap(), "Watchpoint 0 on $010002-$010003");
+        assert_eq!(
+            debugger.execute("c").unwrap(),
+            "Watchpoint 0: Write of 0x010B to $010002 by the instruction at $000105\n\
+            > 00010B: 3B 00 00 00 03     mov ra, 0x0003"
+        );
+        assert!(!debugger.machine().cpu().at_instruction_boundary());
+
+        assert_eq!(debugger.execute("unwatch 0").unwrap(), "Deleted watchpoint 0");
+        assert_eq!(debugger.execute("rwatch $010001").unwrap(), "Watchpoint 1 on $010001");
+        assert_eq!(debugger.execute("wl").unwrap(), "1: Read $010001");
+
+        // The RTS pops the frame back.
+        match debugger.resume() {
+            Ok(Stop::Watchpoint(access)) => {
+                assert_eq!(access.rw, ReadWrite::Read);
+                assert_eq!(access.address, 0x01_0001);
+                assert_eq!(u32::from(access.pc), 0x118);
+            }
+            stop => panic!("Unexpected stop {stop:?}"),
+        }
+
+        debugger.remove_watchpoint(WatchId(1));
+        assert_eq!(debugger.resume(), Ok(Stop::Halted(3)));
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut debugger = debugger();
+
+        assert_eq!(debugger.execute("break $0113").unwrap(), "Breakpoint at $000113");
+        assert_eq!(debugger.execute("c").unwrap(), "Breakpoint at $000113\n> 000113: 3B 00 01 00 02     mov rb, 0x0002");
+        assert_eq!(
+            debugger.execute("regs").unwrap(),
+            "ra=0x0001 rb=0x0000 rc=0x0000 rd=0x0000\npc=$000113 sp=$010004 flags=------- (Fetch cycle 1)"
+        );
+        assert_eq!(
+            debugger.execute("dis $0100 2").unwrap(),
+            "  000100: 3B 00 00 00 01     mov ra, 0x0001\n  000105: 30 00 00 00 01 13  jsr $000113"
+        );
+
+        // The return frame pushed by the JSR.
+        assert_eq!(
+            debugger.execute("x $010000 4").unwrap(),
+            "010000: 00 00 01 0B                                      |....|"
+        );
+        assert_eq!(debugger.execute("w $010010 0x41 66").unwrap(), "Wrote 2 byte(s) at $010010");
+        assert_eq!(debugger.read_memory(0x01_0010, 2), b"AB");
+
+        assert!(debugger.execute("set rc 0x1234").unwrap().contains("rc=0x1234"));
+        assert!(debugger.execute("set flags 0x45").unwrap().contains("flags=Z-C---I"));
+        assert_eq!(debugger.execute("w $0100 0").unwrap_err(), DebugError::Memory(0x100, DeviceResult::ReadOnly));
+        assert_eq!(
+            debugger.execute("w 0xFFFFFFFF 1 2").unwrap_err(),
+            DebugError::Memory(0xFFFF_FFFF, DeviceResult::InvalidAddress)
+        );
+        assert_eq!(
+            debugger.execute("w $FFFFFF 1 2").unwrap_err(),
+            DebugError::Memory(0xFF_FFFF, DeviceResult::InvalidAddress)
+        );
+        assert_eq!(debugger.execute("dis 0 1000000000").unwrap_err(), DebugError::InvalidNumber("1000000000".into()));
+        assert_eq!(debugger.disassemble(0xFFFF_FFFF, usize::MAX), []);
+        assert_eq!(debugger.execute("watch $0200 $0100").unwrap_err(), DebugError::InvalidRange(0x200, 0x100));
+        assert_eq!(debugger.execute("frobnicate").unwrap_err(), DebugError::UnknownCommand("frobnicate".into()));
+        assert_eq!(debugger.execute("break").unwrap_err(), DebugError::Usage("break <addr>"));
+    }
+
+    #[test]
+    fn test_repl() {
+        let mut debugger = debugger();
+        let mut output = Vec::new();
+
+        debugger.repl(&b"s\n\nq\nregs\n"[..], &mut output).unwrap();
+
+        // The empty line repeats the step, and nothing after quit runs.
+        assert_eq!(pc(&debugger), 0x113);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "> 000100: 3B 00 00 00 01     mov ra, 0x0001\n\
+            (hexacore) Stepped\n> 000105: 30 00 00 00 01 13  jsr $000113\n\
+            (hexacore) Stepped\n> 000113: 3B 00 01 00 02     mov rb, 0x0002\n\
+            (hexacore) "
+        );
+    }
+}
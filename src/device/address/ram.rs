@@ -1,3 +1,6 @@
+use serde::de::Error;
+use serde_json::Value;
+
 use super::{AddressMappedDevice, DeviceResult};
 use crate::cpu::ExtendedAddress;
 
@@ -22,10 +25,10 @@ impl AddressMappedDevice for RAM {
         let address_from: u32 = u32::from(address);
 
         if word {
-            if (u32::from(self.start)..=(u32::from(self.end) + 1)).contains(&address_from) {
+            if (u32::from(self.start)..u32::from(self.end)).contains(&address_from) {
                 return DeviceResult::Ok16(
                     ((self.data[self.relative(address)] as u16) << 8)
-                        | self.data[self.relative(address)] as u16,
+                        | self.data[self.relative(address) + 1] as u16,
                 );
             }
         } else if (u32::from(self.start)..=u32::from(self.end)).contains(&address_from) {
@@ -38,6 +41,10 @@ impl AddressMappedDevice for RAM {
     fn write(&mut self, address: ExtendedAddress, data: u16, word: bool) -> DeviceResult {
         let address_from: u32 = u32::from(address);
 
+        if word && address_from == u32::from(self.end) {
+            return DeviceResult::NotMyAddress;
+        }
+
         if address_from >= u32::from(self.start) && address_from <= u32::from(self.end) {
             let index = self.relative(address);
 
@@ -69,6 +76,38 @@ impl AddressMappedDevice for RAM {
     fn end(&self) -> ExtendedAddress {
         self.end
     }
+
+    // The contents are saved as a hex string, a JSON array of numbers would be around twice the size.
+    fn save_state(&self) -> Value {
+        let mut hex = String::with_capacity(self.data.len() * 2);
+
+        for byte in &self.data {
+            hex.push_str(&format!("{byte:02x}"));
+        }
+
+        Value::String(hex)
+    }
+
+    fn load_state(&mut self, state: &Value) -> Result<(), serde_json::Error> {
+        let hex = state
+            .as_str()
+            .filter(|hex| hex.is_ascii())
+            .ok_or_else(|| serde_json::Error::custom("RAM state has to be a hex string"))?;
+
+        if hex.len() != self.data.len() * 2 {
+            return Err(serde_json::Error::custom(format!(
+                "RAM state is {} bytes, expected {}",
+                hex.len() / 2,
+                self.data.len()
+            )));
+        }
+
+        for (index, byte) in self.data.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).map_err(serde_json::Error::custom)?;
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -110,7 +149,7 @@ mod tests {
         let result = ram.write(ExtendedAddress::new_16bit_address(0xD007), 0xCA, false);
 
         assert_eq!(ram.data[0xD007], 0xCA);
-        assert_eq!(result, DeviceResult::Ok8(0));
+        assert_eq!(result, DeviceResult::Ok);
     }
 
     #[test]
@@ -136,4 +175,25 @@ mod tests {
 
         assert_eq!(result, DeviceResult::NotMyAddress);
     }
+
+    #[test]
+    fn test_ram_save_state() {
+        let mut ram = RAM::new(
+            ExtendedAddress::new_16bit_address(0x0000),
+            ExtendedAddress::new_16bit_address(0x0003),
+        );
+        ram.data.copy_from_slice(&[0x01, 0xAB, 0x00, 0xFF]);
+
+        let state = ram.save_state();
+        assert_eq!(state, Value::String("01ab00ff".into()));
+
+        let mut restored = RAM::new(
+            ExtendedAddress::new_16bit_address(0x0000),
+            ExtendedAddress::new_16bit_address(0x0003),
+        );
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.data, ram.data);
+
+        assert!(restored.load_state(&Value::String("01ab".into())).is_err());
+    }
 }
@@ -39,7 +39,7 @@ impl AddressMappedDevice for ROM {
         let address_from: u32 = u32::from(address);
 
         if word {
-            if (u32::from(self.start)..=(u32::from(self.end) + 1)).contains(&address_from) {
+            if (u32::from(self.start)..u32::from(self.end)).contains(&address_from) {
                 return DeviceResult::Ok16(
                     ((self.data[self.relative(address)] as u16) << 8)
                         | self.data[self.relative(*address.clone().increment())] as u16,
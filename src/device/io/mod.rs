@@ -1,3 +1,6 @@
 use crate::device::{DeviceResult, IOMappedDevice};
 
 pub mod out;
+pub mod pic;
+pub mod timer;
+pub mod uart;
@@ -1,23 +1,120 @@
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    rc::Rc,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
 use super::{DeviceResult, IOMappedDevice};
 
+/// Modes written before each value. Values of the 16 bit modes are written as two bytes, the high one first.
+pub const OUT_HEX: u8 = 0;
+pub const OUT_CHAR: u8 = 1;
+pub const OUT_HEX16: u8 = 2;
+pub const OUT_UNSIGNED: u8 = 3;
+pub const OUT_SIGNED: u8 = 4;
+pub const OUT_RAW: u8 = 5;
+
+// The 16 bit modes hold the high byte once it was written.
+#[derive(Serialize, Deserialize)]
 enum OutMode {
     WaitingForMode,
     Int,
     Char,
+    Hex16(Option<u8>),
+    Unsigned(Option<u8>),
+    Signed(Option<u8>),
+    Raw,
 }
 
 use OutMode::*;
 
+/// A buffer the host keeps a clone of to read back what an [`Out`] wrote.
+#[derive(Debug, Default, Clone)]
+pub struct OutBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl OutBuffer {
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.borrow().clone()
+    }
+
+    /// The contents so far as text, invalid UTF-8 is replaced.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+
+    pub fn take(&self) -> Vec<u8> {
+        self.0.take()
+    }
+}
+
+impl Write for OutBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes values to stdout or another sink on a single port. Every value is preceded by its mode: `OUT_HEX`
+/// prints a byte as hex, `OUT_CHAR` as a character, `OUT_HEX16`, `OUT_UNSIGNED` and `OUT_SIGNED` print a 16 bit
+/// value as hex or decimal and `OUT_RAW` passes the byte through unchanged. Unknown modes print a character.
 pub struct Out {
     address: u8,
     mode: OutMode,
+    writer: Box<dyn Write>,
+    error: Option<io::Error>,
 }
 
 impl Out {
     pub fn new(address: u8) -> Self {
+        Self::with_writer(address, io::stdout())
+    }
+
+    pub fn with_writer<W: Write + 'static>(address: u8, writer: W) -> Self {
         Self {
             address,
             mode: OutMode::WaitingForMode,
+            writer: Box::new(writer),
+            error: None,
+        }
+    }
+
+    /// Writes into a new buffer, returned along with the device.
+    pub fn with_buffer(address: u8) -> (Self, OutBuffer) {
+        let buffer = OutBuffer::default();
+        (Self::with_writer(address, buffer.clone()), buffer)
+    }
+
+    /// The first error the writer reported. Values written after it are dropped.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    // The output for the last byte of a value.
+    fn format(&self, data: u8) -> Vec<u8> {
+        let value = |high| u16::from_be_bytes([high, data]);
+
+        match self.mode {
+            Int => format!("0x{:02x}\n", data).into_bytes(),
+            Hex16(Some(high)) => format!("0x{:04x}\n", value(high)).into_bytes(),
+            Unsigned(Some(high)) => format!("{}\n", value(high)).into_bytes(),
+            Signed(Some(high)) => format!("{}\n", value(high) as i16).into_bytes(),
+            Raw => vec![data],
+            _ => (data as char).to_string().into_bytes(),
+        }
+    }
+
+    fn output(&mut self, bytes: &[u8]) {
+        if self.error.is_none() {
+            if let Err(err) = self.writer.write_all(bytes) {
+                self.error = Some(err);
+            }
         }
     }
 }
@@ -32,30 +129,75 @@ impl IOMappedDevice for Out {
     }
 
     fn io_write(&mut self, data: u8) -> DeviceResult {
-        match self.mode {
-            WaitingForMode => {
-                if data == 0 {
-                    self.mode = Int;
-                } else {
-                    self.mode = Char;
-                }
-
-                DeviceResult::Ok
-            }
-            Int => {
-                println!("0x{:02x}", data);
-                self.mode = WaitingForMode;
-                DeviceResult::Ok
-            }
-            Char => {
-                print!("{}", data as char);
-                self.mode = WaitingForMode;
-                DeviceResult::Ok
+        self.mode = match self.mode {
+            WaitingForMode => match data {
+                OUT_HEX => Int,
+                OUT_HEX16 => Hex16(None),
+                OUT_UNSIGNED => Unsigned(None),
+                OUT_SIGNED => Signed(None),
+                OUT_RAW => Raw,
+                _ => Char,
+            },
+            Hex16(None) => Hex16(Some(data)),
+            Unsigned(None) => Unsigned(Some(data)),
+            Signed(None) => Signed(Some(data)),
+            _ => {
+                let output = self.format(data);
+                self.output(&output);
+                WaitingForMode
             }
-        }
+        };
+
+        DeviceResult::Ok
     }
 
     fn io_name(&self) -> &str {
         "Out"
     }
+
+    fn save_state(&self) -> Value {
+        serde_json::to_value(&self.mode).unwrap()
+    }
+
+    fn load_state(&mut self, state: &Value) -> Result<(), serde_json::Error> {
+        self.mode = OutMode::deserialize(state)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(out: &mut Out, bytes: &[u8]) {
+        for byte in bytes {
+            assert_eq!(out.io_write(*byte), DeviceResult::Ok);
+        }
+    }
+
+    #[test]
+    fn test_modes() {
+        let (mut out, buffer) = Out::with_buffer(0xA0);
+
+        write(&mut out, &[OUT_HEX, 0x2A, OUT_CHAR, b'A', 0x7F, b'\n']);
+        write(&mut out, &[OUT_HEX16, 0x12, 0x34, OUT_UNSIGNED, 0xFF, 0xFE, OUT_SIGNED, 0xFF, 0xFE]);
+        assert_eq!(buffer.text(), "0x2a\nA\n0x1234\n65534\n-2\n");
+
+        buffer.take();
+        write(&mut out, &[OUT_RAW, 0xC3, OUT_RAW, 0xA9, OUT_CHAR, 0xE9]);
+        assert_eq!(buffer.contents(), "éé".as_bytes());
+    }
+
+    #[test]
+    fn test_state() {
+        let (mut out, buffer) = Out::with_buffer(0xA0);
+        write(&mut out, &[OUT_SIGNED, 0x80]);
+
+        let (mut restored, restored_buffer) = Out::with_buffer(0xA0);
+        restored.load_state(&out.save_state()).unwrap();
+        write(&mut restored, &[0x00]);
+
+        assert!(buffer.contents().is_empty());
+        assert_eq!(restored_buffer.text(), "-32768\n");
+    }
 }
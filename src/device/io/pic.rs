@@ -0,0 +1,194 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{DeviceResult, IOMappedDevice};
+
+/// Register offsets from the controller's base port.
+pub const PIC_MASK: u8 = 0;
+pub const PIC_PENDING: u8 = 1;
+pub const PIC_IN_SERVICE: u8 = 2;
+pub const PIC_COMMAND: u8 = 3;
+pub const PIC_VECTOR_BASE: u8 = 4;
+
+/// Commands written to `PIC_COMMAND`. The specific end of interrupt takes the input in the low 3 bits.
+pub const PIC_EOI: u8 = 0x20;
+pub const PIC_SPECIFIC_EOI: u8 = 0x60;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct PicState {
+    mask: u8,
+    pending: u8,
+    in_service: u8,
+    vector_base: u8,
+}
+
+/// A programmable interrupt controller on 5 consecutive ports with 8 inputs. Input 0 has the highest priority.
+/// Add it with [`Machine::set_interrupt_controller`](crate::machine::Machine::set_interrupt_controller), the
+/// interrupt number a device asserts is then the input it is wired to.
+///
+/// +------------+-------------------------------------------------------------+
+/// | **Offset** | **Register**                                                |
+/// +------------+-------------------------------------------------------------+
+/// | 0          | Mask, a set bit ignores the input                           |
+/// +------------+-------------------------------------------------------------+
+/// | 1          | Pending, the inputs that are asserted (read only)           |
+/// +------------+-------------------------------------------------------------+
+/// | 2          | In service, the inputs whose handlers run (read only)       |
+/// +------------+-------------------------------------------------------------+
+/// | 3          | Command, end of interrupt (write only)                      |
+/// +------------+-------------------------------------------------------------+
+/// | 4          | Vector base, input n is interrupt number base + n           |
+/// +------------+-------------------------------------------------------------+
+///
+/// Inputs are level triggered. An unmasked pending input is requested from the CPU while no input with the same
+/// or a higher priority is in service. Acknowledging moves the highest priority one in service, until the handler
+/// ends it with an end of interrupt command.
+pub struct Pic {
+    address: u8,
+    state: PicState,
+}
+
+impl Pic {
+    pub fn new(address: u8, vector_base: u8) -> Self {
+        Self {
+            address,
+            state: PicState {
+                vector_base: vector_base & 0xF,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Sets which inputs are asserted, bit n for input n.
+    pub fn set_inputs(&mut self, inputs: u8) {
+        self.state.pending = inputs;
+    }
+
+    /// Whether the controller requests an interrupt from the CPU.
+    pub fn requesting(&self) -> bool {
+        self.highest_request().is_some()
+    }
+
+    /// Puts the highest priority request in service and returns its input and interrupt number, `None` if the
+    /// request went away before the CPU acknowledged it.
+    pub fn acknowledge_request(&mut self) -> Option<(u8, u8)> {
+        let input = self.highest_request()?;
+        self.state.in_service |= 1 << input;
+
+        Some((input, (self.state.vector_base + input) & 0xF))
+    }
+
+    fn highest_request(&self) -> Option<u8> {
+        let requests = self.state.pending & !self.state.mask;
+        let input = requests.trailing_zeros() as u8;
+
+        // Inputs in service block themselves and everything below them.
+        (input < 8 && input < self.state.in_service.trailing_zeros() as u8).then_some(input)
+    }
+}
+
+impl IOMappedDevice for Pic {
+    fn io_address(&self) -> u8 {
+        self.address
+    }
+
+    fn io_read(&mut self) -> DeviceResult {
+        self.io_read_register(PIC_MASK)
+    }
+
+    fn io_write(&mut self, data: u8) -> DeviceResult {
+        self.io_write_register(PIC_MASK, data)
+    }
+
+    fn io_name(&self) -> &str {
+        "PIC"
+    }
+
+    fn io_size(&self) -> u8 {
+        5
+    }
+
+    fn io_read_register(&mut self, register: u8) -> DeviceResult {
+        match register {
+            PIC_MASK => DeviceResult::Ok8(self.state.mask),
+            PIC_PENDING => DeviceResult::Ok8(self.state.pending),
+            PIC_IN_SERVICE => DeviceResult::Ok8(self.state.in_service),
+            PIC_COMMAND => DeviceResult::WriteOnly,
+            PIC_VECTOR_BASE => DeviceResult::Ok8(self.state.vector_base),
+            _ => DeviceResult::InvalidAddress,
+        }
+    }
+
+    fn io_write_register(&mut self, register: u8, data: u8) -> DeviceResult {
+        match register {
+            PIC_MASK => self.state.mask = data,
+            PIC_PENDING | PIC_IN_SERVICE => return DeviceResult::ReadOnly,
+            // Ends the highest priority interrupt in service.
+            PIC_COMMAND if data == PIC_EOI => self.state.in_service &= self.state.in_service.wrapping_sub(1),
+            PIC_COMMAND if data & !0x7 == PIC_SPECIFIC_EOI => self.state.in_service &= !(1 << (data & 0x7)),
+            PIC_COMMAND => return DeviceResult::InvalidAddress,
+            PIC_VECTOR_BASE => self.state.vector_base = data & 0xF,
+            _ => return DeviceResult::InvalidAddress,
+        }
+
+        DeviceResult::Ok
+    }
+
+    fn save_state(&self) -> Value {
+        serde_json::to_value(&self.state).unwrap()
+    }
+
+    fn load_state(&mut self, state: &Value) -> Result<(), serde_json::Error> {
+        self.state = PicState::deserialize(state)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_priority() {
+        let mut pic = Pic::new(0x20, 8);
+        assert!(!pic.requesting());
+
+        pic.set_inputs(0b0001_0100);
+        assert_eq!(pic.acknowledge_request(), Some((2, 0xA)));
+
+        // Input 4 waits for input 2, input 1 preempts it.
+        assert!(!pic.requesting());
+        pic.set_inputs(0b0001_0110);
+        assert_eq!(pic.acknowledge_request(), Some((1, 0x9)));
+        assert_eq!(pic.io_read_register(PIC_IN_SERVICE), DeviceResult::Ok8(0b0000_0110));
+
+        // A plain EOI ends input 1, the specific one input 2.
+        pic.set_inputs(0b0001_0000);
+        pic.io_write_register(PIC_COMMAND, PIC_EOI);
+        assert!(!pic.requesting());
+        pic.io_write_register(PIC_COMMAND, PIC_SPECIFIC_EOI | 2);
+        assert_eq!(pic.acknowledge_request(), Some((4, 0xC)));
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut pic = Pic::new(0x20, 0);
+
+        pic.io_write_register(PIC_MASK, 0b0000_0001);
+        pic.io_write_register(PIC_VECTOR_BASE, 0x15);
+        pic.set_inputs(0b0000_0011);
+
+        assert_eq!(pic.io_read_register(PIC_PENDING), DeviceResult::Ok8(0b0000_0011));
+        assert_eq!(pic.acknowledge_request(), Some((1, 0x6)));
+        assert_eq!(pic.io_write_register(PIC_PENDING, 0), DeviceResult::ReadOnly);
+        assert_eq!(pic.io_write_register(PIC_COMMAND, 0x42), DeviceResult::InvalidAddress);
+        assert_eq!(pic.io_read_register(PIC_COMMAND), DeviceResult::WriteOnly);
+
+        // Inputs past the 4 bit interrupt numbers wrap around.
+        pic.io_write_register(PIC_MASK, 0);
+        pic.io_write_register(PIC_VECTOR_BASE, 0xE);
+        pic.io_write_register(PIC_COMMAND, PIC_EOI);
+        pic.set_inputs(0b0000_0100);
+        assert_eq!(pic.acknowledge_request(), Some((2, 0x0)));
+    }
+}
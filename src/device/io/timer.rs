@@ -0,0 +1,265 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{DeviceResult, IOMappedDevice};
+
+/// Register offsets from the timer's base port.
+pub const TIMER_CONTROL: u8 = 0;
+pub const TIMER_PRESCALER: u8 = 1;
+pub const TIMER_RELOAD_LOW: u8 = 2;
+pub const TIMER_RELOAD_HIGH: u8 = 3;
+pub const TIMER_STATUS: u8 = 4;
+pub const TIMER_INTERRUPT: u8 = 5;
+
+/// Bits of the control register.
+pub const TIMER_ENABLE: u8 = 0b001;
+pub const TIMER_PERIODIC: u8 = 0b010;
+pub const TIMER_IRQ_ENABLE: u8 = 0b100;
+
+/// Bits of the status register. Writing a 1 to `TIMER_EXPIRED` clears it and releases the interrupt.
+pub const TIMER_EXPIRED: u8 = 0b01;
+pub const TIMER_RUNNING: u8 = 0b10;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct TimerState {
+    control: u8,
+    prescaler: u8,
+    reload: u16,
+    counter: u32,
+    // Cycles counted towards the next prescaled tick.
+    divided: u64,
+    expired: bool,
+    interrupt: u8,
+}
+
+impl TimerState {
+    fn period(&self) -> u32 {
+        match self.reload {
+            0 => 0x1_0000,
+            reload => reload as u32,
+        }
+    }
+}
+
+/// A programmable interval timer on 6 consecutive ports. The counter is loaded from the reload value when the
+/// timer is enabled and counts down once every `prescaler + 1` cycles. When it reaches zero the timer expires:
+/// one-shot timers stop, periodic ones reload and keep counting. A reload value of 0 counts 65536 ticks.
+///
+/// +------------+-----------------------------------------------------------------+
+/// | **Offset** | **Register**                                                    |
+/// +------------+-----------------------------------------------------------------+
+/// | 0          | Control: enable, periodic, interrupt enable (bits 0-2)          |
+/// +------------+-----------------------------------------------------------------+
+/// | 1          | Prescaler                                                       |
+/// +------------+-----------------------------------------------------------------+
+/// | 2, 3       | Reload value low and high byte, reads return the counter        |
+/// +------------+-----------------------------------------------------------------+
+/// | 4          | Status: expired, running (bits 0-1), write 1 to clear expired   |
+/// +------------+-----------------------------------------------------------------+
+/// | 5          | Interrupt number put into `IRQ::data`                           |
+/// +------------+-----------------------------------------------------------------+
+///
+/// While it is expired and interrupts are enabled, the timer asserts its interrupt line until the expired bit is
+/// cleared.
+pub struct Timer {
+    address: u8,
+    state: TimerState,
+}
+
+impl Timer {
+    pub fn new(address: u8, interrupt: u8) -> Self {
+        Self {
+            address,
+            state: TimerState {
+                interrupt: interrupt & 0xF,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn status(&self) -> u8 {
+        let mut status = 0;
+
+        if self.state.expired {
+            status |= TIMER_EXPIRED;
+        }
+
+        if self.state.control & TIMER_ENABLE != 0 {
+            status |= TIMER_RUNNING;
+        }
+
+        status
+    }
+}
+
+impl IOMappedDevice for Timer {
+    fn io_address(&self) -> u8 {
+        self.address
+    }
+
+    fn io_read(&mut self) -> DeviceResult {
+        self.io_read_register(TIMER_CONTROL)
+    }
+
+    fn io_write(&mut self, data: u8) -> DeviceResult {
+        self.io_write_register(TIMER_CONTROL, data)
+    }
+
+    fn io_name(&self) -> &str {
+        "Timer"
+    }
+
+    fn io_size(&self) -> u8 {
+        6
+    }
+
+    fn io_read_register(&mut self, register: u8) -> DeviceResult {
+        let state = &self.state;
+
+        DeviceResult::Ok8(match register {
+            TIMER_CONTROL => state.control,
+            TIMER_PRESCALER => state.prescaler,
+            TIMER_RELOAD_LOW => state.counter as u8,
+            TIMER_RELOAD_HIGH => (state.counter >> 8) as u8,
+            TIMER_STATUS => self.status(),
+            TIMER_INTERRUPT => state.interrupt,
+            _ => return DeviceResult::InvalidAddress,
+        })
+    }
+
+    fn io_write_register(&mut self, register: u8, data: u8) -> DeviceResult {
+        match register {
+            TIMER_CONTROL => {
+                // Enabling a stopped timer starts a new period.
+                if data & TIMER_ENABLE != 0 && self.state.control & TIMER_ENABLE == 0 {
+                    self.state.counter = self.state.period();
+                    self.state.divided = 0;
+                }
+
+                self.state.control = data & (TIMER_ENABLE | TIMER_PERIODIC | TIMER_IRQ_ENABLE);
+            }
+            TIMER_PRESCALER => self.state.prescaler = data,
+            TIMER_RELOAD_LOW => self.state.reload = (self.state.reload & 0xFF00) | data as u16,
+            TIMER_RELOAD_HIGH => self.state.reload = (self.state.reload & 0x00FF) | (data as u16) << 8,
+            TIMER_STATUS => {
+                if data & TIMER_EXPIRED != 0 {
+                    self.state.expired = false;
+                }
+            }
+            TIMER_INTERRUPT => self.state.interrupt = data & 0xF,
+            _ => return DeviceResult::InvalidAddress,
+        }
+
+        DeviceResult::Ok
+    }
+
+    fn save_state(&self) -> Value {
+        serde_json::to_value(&self.state).unwrap()
+    }
+
+    fn load_state(&mut self, state: &Value) -> Result<(), serde_json::Error> {
+        self.state = TimerState::deserialize(state)?;
+        Ok(())
+    }
+
+    fn tick(&mut self, cycles: u64) {
+        let state = &mut self.state;
+
+        if state.control & TIMER_ENABLE == 0 {
+            return;
+        }
+
+        let divider = state.prescaler as u64 + 1;
+        let elapsed = state.divided + cycles;
+        let mut ticks = elapsed / divider;
+        state.divided = elapsed % divider;
+
+        while ticks > 0 && state.control & TIMER_ENABLE != 0 {
+            if ticks < state.counter as u64 {
+                state.counter -= ticks as u32;
+                return;
+            }
+
+            ticks -= state.counter as u64;
+            state.expired = true;
+
+            if state.control & TIMER_PERIODIC != 0 {
+                state.counter = state.period();
+                ticks %= state.counter as u64;
+            } else {
+                state.control &= !TIMER_ENABLE;
+                state.counter = 0;
+            }
+        }
+    }
+
+    fn interrupt(&self) -> Option<u8> {
+        (self.state.expired && self.state.control & TIMER_IRQ_ENABLE != 0).then_some(self.state.interrupt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timer(control: u8, prescaler: u8, reload: u16) -> Timer {
+        let mut timer = Timer::new(0xB0, 5);
+
+        timer.io_write_register(TIMER_PRESCALER, prescaler);
+        timer.io_write_register(TIMER_RELOAD_LOW, reload as u8);
+        timer.io_write_register(TIMER_RELOAD_HIGH, (reload >> 8) as u8);
+        timer.io_write_register(TIMER_CONTROL, control);
+        timer
+    }
+
+    #[test]
+    fn test_one_shot() {
+        let mut timer = timer(TIMER_ENABLE | TIMER_IRQ_ENABLE, 3, 10);
+
+        timer.tick(39);
+        assert_eq!(timer.io_read_register(TIMER_RELOAD_LOW), DeviceResult::Ok8(1));
+        assert_eq!(timer.interrupt(), None);
+
+        timer.tick(1);
+        assert_eq!(timer.interrupt(), Some(5));
+        assert_eq!(timer.io_read_register(TIMER_STATUS), DeviceResult::Ok8(TIMER_EXPIRED));
+
+        // Stopped, clearing the status releases the interrupt for good.
+        timer.io_write_register(TIMER_STATUS, TIMER_EXPIRED);
+        timer.tick(1000);
+        assert_eq!(timer.interrupt(), None);
+        assert_eq!(timer.io_read_register(TIMER_STATUS), DeviceResult::Ok8(0));
+    }
+
+    #[test]
+    fn test_periodic() {
+        let mut timer = timer(TIMER_ENABLE | TIMER_PERIODIC, 0, 100);
+
+        timer.tick(250);
+        assert_eq!(timer.io_read_register(TIMER_RELOAD_LOW), DeviceResult::Ok8(50));
+        assert_eq!(timer.io_read_register(TIMER_STATUS), DeviceResult::Ok8(TIMER_EXPIRED | TIMER_RUNNING));
+
+        // Interrupts are disabled, the status still shows the expiry.
+        assert_eq!(timer.interrupt(), None);
+
+        timer.io_write_register(TIMER_STATUS, TIMER_EXPIRED);
+        timer.io_write_register(TIMER_INTERRUPT, 0x17);
+        timer.io_write_register(TIMER_CONTROL, TIMER_ENABLE | TIMER_PERIODIC | TIMER_IRQ_ENABLE);
+        timer.tick(50);
+        assert_eq!(timer.interrupt(), Some(7));
+    }
+
+    #[test]
+    fn test_state() {
+        let mut timer = timer(TIMER_ENABLE, 1, 0);
+        timer.tick(3);
+
+        let mut restored = Timer::new(0xB0, 0);
+        restored.load_state(&timer.save_state()).unwrap();
+
+        assert_eq!(restored.io_read_register(TIMER_RELOAD_LOW), DeviceResult::Ok8(0xFF));
+        assert_eq!(restored.io_read_register(TIMER_RELOAD_HIGH), DeviceResult::Ok8(0xFF));
+        assert_eq!(restored.io_read_register(TIMER_INTERRUPT), DeviceResult::Ok8(5));
+        assert_eq!(restored.io_read_register(6), DeviceResult::InvalidAddress);
+    }
+}
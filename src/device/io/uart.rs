@@ -0,0 +1,422 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufWriter, Read, Write},
+    path::Path,
+    rc::Rc,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{DeviceResult, IOMappedDevice};
+
+/// Register offsets from the UART's base port.
+pub const UART_DATA: u8 = 0;
+pub const UART_STATUS: u8 = 1;
+pub const UART_CONTROL: u8 = 2;
+pub const UART_INTERRUPT: u8 = 3;
+
+/// Bits of the status register.
+pub const UART_TX_EMPTY: u8 = 0b0001;
+pub const UART_RX_READY: u8 = 0b0010;
+pub const UART_TX_FULL: u8 = 0b0100;
+pub const UART_RX_OVERRUN: u8 = 0b1000;
+
+/// Bits of the control register.
+pub const UART_RX_IRQ_ENABLE: u8 = 0b01;
+pub const UART_TX_IRQ_ENABLE: u8 = 0b10;
+
+/// Where a [`Uart`] sends the bytes the program transmits and gets the ones it receives from.
+pub trait SerialBackend {
+    /// The next received byte, `None` if there is none right now. Must not block.
+    fn receive(&mut self) -> Option<u8>;
+    fn transmit(&mut self, byte: u8) -> io::Result<()>;
+}
+
+/// Receives from stdin and transmits to stdout. Stdin is read on a separate thread, so the program keeps running
+/// while it waits for input.
+#[derive(Default)]
+pub struct Console {
+    input: Option<Receiver<u8>>,
+}
+
+impl SerialBackend for Console {
+    fn receive(&mut self) -> Option<u8> {
+        let input = self.input.get_or_insert_with(|| {
+            let (sender, receiver) = mpsc::channel();
+
+            thread::spawn(move || {
+                for byte in io::stdin().lock().bytes() {
+                    match byte {
+                        Ok(byte) if sender.send(byte).is_ok() => {}
+                        _ => break,
+                    }
+                }
+            });
+
+            receiver
+        });
+
+        input.try_recv().ok()
+    }
+
+    fn transmit(&mut self, byte: u8) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(&[byte])?;
+        stdout.flush()
+    }
+}
+
+/// Receives the contents of an input file and transmits into an output file, either can be left out.
+pub struct SerialFile {
+    input: VecDeque<u8>,
+    output: Option<BufWriter<File>>,
+}
+
+impl SerialFile {
+    pub fn new<P: AsRef<Path>>(input: Option<P>, output: Option<P>) -> io::Result<Self> {
+        let input = match input {
+            Some(path) => std::fs::read(path)?,
+            None => Vec::new(),
+        };
+
+        Ok(Self::from_parts(input, output.map(File::create).transpose()?))
+    }
+
+    /// Receives `input` and transmits into the already opened `output`.
+    pub fn from_parts(input: Vec<u8>, output: Option<File>) -> Self {
+        Self {
+            input: input.into(),
+            output: output.map(BufWriter::new),
+        }
+    }
+}
+
+impl SerialBackend for SerialFile {
+    fn receive(&mut self) -> Option<u8> {
+        self.input.pop_front()
+    }
+
+    fn transmit(&mut self, byte: u8) -> io::Result<()> {
+        match &mut self.output {
+            Some(output) => output.write_all(&[byte]),
+            None => Ok(()),
+        }
+    }
+}
+
+/// An in-memory backend the host keeps a clone of, to feed input and read back the output.
+#[derive(Debug, Default, Clone)]
+pub struct SerialBuffer {
+    input: Rc<RefCell<VecDeque<u8>>>,
+    output: Rc<RefCell<Vec<u8>>>,
+}
+
+impl SerialBuffer {
+    pub fn push_input(&self, bytes: &[u8]) {
+        self.input.borrow_mut().extend(bytes);
+    }
+
+    pub fn output(&self) -> Vec<u8> {
+        self.output.borrow().clone()
+    }
+
+    pub fn take_output(&self) -> Vec<u8> {
+        self.output.take()
+    }
+}
+
+impl SerialBackend for SerialBuffer {
+    fn receive(&mut self) -> Option<u8> {
+        self.input.borrow_mut().pop_front()
+    }
+
+    fn transmit(&mut self, byte: u8) -> io::Result<()> {
+        self.output.borrow_mut().push(byte);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct UartState {
+    rx: VecDeque<u8>,
+    tx: VecDeque<u8>,
+    control: u8,
+    overrun: bool,
+    interrupt: u8,
+    // Cycles since the last byte moved over the line.
+    elapsed: u64,
+}
+
+/// A serial port on 4 consecutive ports, with a FIFO for each direction. Every `cycles_per_byte` cycles one byte
+/// is transmitted from the TX FIFO and one is received into the RX FIFO.
+///
+/// +------------+-----------------------------------------------------------------+
+/// | **Offset** | **Register**                                                    |
+/// +------------+-----------------------------------------------------------------+
+/// | 0          | Data, writes go into the TX FIFO, reads take from the RX FIFO   |
+/// +------------+-----------------------------------------------------------------+
+/// | 1          | Status: TX empty, RX ready, TX full, RX overrun (bits 0-3)      |
+/// +------------+-----------------------------------------------------------------+
+/// | 2          | Control: RX interrupt enable, TX interrupt enable (bits 0-1)    |
+/// +------------+-----------------------------------------------------------------+
+/// | 3          | Interrupt number                                                |
+/// +------------+-----------------------------------------------------------------+
+///
+/// The interrupt line is asserted while the RX interrupt is enabled and a byte is ready, or while the TX interrupt
+/// is enabled and the TX FIFO is empty.
+pub struct Uart {
+    address: u8,
+    backend: Box<dyn SerialBackend>,
+    fifo_size: usize,
+    cycles_per_byte: u64,
+    state: UartState,
+    error: Option<io::Error>,
+}
+
+impl Uart {
+    pub fn new<B: SerialBackend + 'static>(address: u8, interrupt: u8, backend: B) -> Self {
+        Self {
+            address,
+            backend: Box::new(backend),
+            fifo_size: 16,
+            cycles_per_byte: 100,
+            state: UartState {
+                interrupt: interrupt & 0xF,
+                ..Default::default()
+            },
+            error: None,
+        }
+    }
+
+    /// Bytes each FIFO holds, 16 by default.
+    pub fn with_fifo_size(mut self, size: usize) -> Self {
+        self.fifo_size = size.max(1);
+        self
+    }
+
+    /// How fast bytes move over the line, 100 cycles per byte by default.
+    pub fn with_cycles_per_byte(mut self, cycles: u64) -> Self {
+        self.cycles_per_byte = cycles.max(1);
+        self
+    }
+
+    /// The first error the backend reported while transmitting. Bytes transmitted after it are dropped.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    fn transfer(&mut self) {
+        if let Some(byte) = self.state.tx.pop_front() {
+            if self.error.is_none() {
+                if let Err(err) = self.backend.transmit(byte) {
+                    self.error = Some(err);
+                }
+            }
+        }
+
+        if let Some(byte) = self.backend.receive() {
+            match self.state.rx.len() < self.fifo_size {
+                true => self.state.rx.push_back(byte),
+                false => self.state.overrun = true,
+            }
+        }
+    }
+
+    fn status(&self) -> u8 {
+        let state = &self.state;
+        let mut status = 0;
+
+        if state.tx.is_empty() {
+            status |= UART_TX_EMPTY;
+        }
+
+        if !state.rx.is_empty() {
+            status |= UART_RX_READY;
+        }
+
+        if state.tx.len() >= self.fifo_size {
+            status |= UART_TX_FULL;
+        }
+
+        if state.overrun {
+            status |= UART_RX_OVERRUN;
+        }
+
+        status
+    }
+}
+
+impl IOMappedDevice for Uart {
+    fn io_address(&self) -> u8 {
+        self.address
+    }
+
+    fn io_read(&mut self) -> DeviceResult {
+        self.io_read_register(UART_DATA)
+    }
+
+    fn io_write(&mut self, data: u8) -> DeviceResult {
+        self.io_write_register(UART_DATA, data)
+    }
+
+    fn io_name(&self) -> &str {
+        "UART"
+    }
+
+    fn io_size(&self) -> u8 {
+        4
+    }
+
+    fn io_read_register(&mut self, register: u8) -> DeviceResult {
+        DeviceResult::Ok8(match register {
+            // Reading an empty FIFO gives 0.
+            UART_DATA => self.state.rx.pop_front().unwrap_or(0),
+            // Reading the status clears the overrun.
+            UART_STATUS => {
+                let status = self.status();
+                self.state.overrun = false;
+                status
+            }
+            UART_CONTROL => self.state.control,
+            UART_INTERRUPT => self.state.interrupt,
+            _ => return DeviceResult::InvalidAddress,
+        })
+    }
+
+    fn io_write_register(&mut self, register: u8, data: u8) -> DeviceResult {
+        match register {
+            // Writing into a full FIFO drops the byte.
+            UART_DATA => {
+                if self.state.tx.len() < self.fifo_size {
+                    self.state.tx.push_back(data);
+                }
+            }
+            UART_STATUS => return DeviceResult::ReadOnly,
+            UART_CONTROL => self.state.control = data & (UART_RX_IRQ_ENABLE | UART_TX_IRQ_ENABLE),
+            UART_INTERRUPT => self.state.interrupt = data & 0xF,
+            _ => return DeviceResult::InvalidAddress,
+        }
+
+        DeviceResult::Ok
+    }
+
+    fn save_state(&self) -> Value {
+        serde_json::to_value(&self.state).unwrap()
+    }
+
+    fn load_state(&mut self, state: &Value) -> Result<(), serde_json::Error> {
+        self.state = UartState::deserialize(state)?;
+        Ok(())
+    }
+
+    fn tick(&mut self, cycles: u64) {
+        self.state.elapsed += cycles;
+
+        while self.state.elapsed >= self.cycles_per_byte {
+            self.state.elapsed -= self.cycles_per_byte;
+            self.transfer();
+        }
+    }
+
+    fn interrupt(&self) -> Option<u8> {
+        let state = &self.state;
+        let rx = state.control & UART_RX_IRQ_ENABLE != 0 && !state.rx.is_empty();
+        let tx = state.control & UART_TX_IRQ_ENABLE != 0 && state.tx.is_empty();
+
+        (rx || tx).then_some(state.interrupt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uart() -> (Uart, SerialBuffer) {
+        let buffer = SerialBuffer::default();
+        let uart = Uart::new(0xC0, 6, buffer.clone()).with_fifo_size(2).with_cycles_per_byte(10);
+
+        (uart, buffer)
+    }
+
+    #[test]
+    fn test_transmit() {
+        let (mut uart, buffer) = uart();
+
+        for byte in b"abc" {
+            uart.io_write_register(UART_DATA, *byte);
+        }
+
+        // The FIFO holds 2 bytes, the third one was dropped.
+        assert_eq!(uart.io_read_register(UART_STATUS), DeviceResult::Ok8(UART_TX_FULL));
+
+        uart.tick(15);
+        assert_eq!(buffer.output(), b"a");
+        assert_eq!(uart.io_read_register(UART_STATUS), DeviceResult::Ok8(0));
+
+        uart.tick(5);
+        assert_eq!(buffer.take_output(), b"ab");
+        assert_eq!(uart.io_read_register(UART_STATUS), DeviceResult::Ok8(UART_TX_EMPTY));
+        assert!(buffer.output().is_empty());
+    }
+
+    #[test]
+    fn test_receive() {
+        let (mut uart, buffer) = uart();
+        buffer.push_input(b"xyz");
+
+        uart.tick(30);
+        assert_eq!(uart.io_read_register(UART_STATUS), DeviceResult::Ok8(UART_TX_EMPTY | UART_RX_READY | UART_RX_OVERRUN));
+        assert_eq!(uart.io_read_register(UART_STATUS), DeviceResult::Ok8(UART_TX_EMPTY | UART_RX_READY));
+
+        assert_eq!(uart.io_read_register(UART_DATA), DeviceResult::Ok8(b'x'));
+        assert_eq!(uart.io_read_register(UART_DATA), DeviceResult::Ok8(b'y'));
+        assert_eq!(uart.io_read_register(UART_DATA), DeviceResult::Ok8(0));
+    }
+
+    #[test]
+    fn test_interrupts() {
+        let (mut uart, buffer) = uart();
+        assert_eq!(uart.interrupt(), None);
+
+        uart.io_write_register(UART_CONTROL, UART_TX_IRQ_ENABLE);
+        assert_eq!(uart.interrupt(), Some(6));
+        uart.io_write_register(UART_DATA, b'a');
+        assert_eq!(uart.interrupt(), None);
+
+        uart.io_write_register(UART_CONTROL, UART_RX_IRQ_ENABLE);
+        uart.io_write_register(UART_INTERRUPT, 9);
+        buffer.push_input(b"b");
+        uart.tick(10);
+        assert_eq!(uart.interrupt(), Some(9));
+
+        uart.io_read_register(UART_DATA);
+        assert_eq!(uart.interrupt(), None);
+    }
+
+    #[test]
+    fn test_file() {
+        let dir = std::env::temp_dir().join(format!("hexacore-uart-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("input.txt"), "in").unwrap();
+
+        let file = SerialFile::new(Some(dir.join("input.txt")), Some(dir.join("output.txt"))).unwrap();
+        let mut uart = Uart::new(0xC0, 6, file).with_cycles_per_byte(1);
+
+        uart.io_write_register(UART_DATA, b'o');
+        uart.tick(2);
+        let received = [uart.io_read_register(UART_DATA), uart.io_read_register(UART_DATA)];
+
+        // Dropping the UART flushes the output file.
+        drop(uart);
+        let output = std::fs::read(dir.join("output.txt"));
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(received, [DeviceResult::Ok8(b'i'), DeviceResult::Ok8(b'n')]);
+        assert_eq!(output.unwrap(), b"o");
+    }
+}
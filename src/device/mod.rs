@@ -2,12 +2,22 @@ mod address;
 mod io;
 
 use crate::cpu::ExtendedAddress;
+use serde_json::Value;
 pub use address::ram::RAM;
 pub use address::rom::ROM;
 
-pub use io::out::Out;
+pub use io::out::{Out, OutBuffer, OUT_CHAR, OUT_HEX, OUT_HEX16, OUT_RAW, OUT_SIGNED, OUT_UNSIGNED};
+pub use io::pic::{Pic, PIC_COMMAND, PIC_EOI, PIC_IN_SERVICE, PIC_MASK, PIC_PENDING, PIC_SPECIFIC_EOI, PIC_VECTOR_BASE};
+pub use io::timer::{
+    Timer, TIMER_CONTROL, TIMER_ENABLE, TIMER_EXPIRED, TIMER_INTERRUPT, TIMER_IRQ_ENABLE, TIMER_PERIODIC,
+    TIMER_PRESCALER, TIMER_RELOAD_HIGH, TIMER_RELOAD_LOW, TIMER_RUNNING, TIMER_STATUS,
+};
+pub use io::uart::{
+    Console, SerialBackend, SerialBuffer, SerialFile, Uart, UART_CONTROL, UART_DATA, UART_INTERRUPT, UART_RX_IRQ_ENABLE,
+    UART_RX_OVERRUN, UART_RX_READY, UART_STATUS, UART_TX_EMPTY, UART_TX_FULL, UART_TX_IRQ_ENABLE,
+};
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DeviceResult {
     Ok,
     Ok8(u8),
@@ -28,6 +38,28 @@ pub trait AddressMappedDevice {
     fn size(&self) -> usize;
     fn start(&self) -> ExtendedAddress;
     fn end(&self) -> ExtendedAddress;
+
+    /// State that has to survive a save state, devices without any (like ROM) keep the default `Null`.
+    fn save_state(&self) -> Value {
+        Value::Null
+    }
+
+    fn load_state(&mut self, _state: &Value) -> Result<(), serde_json::Error> {
+        Ok(())
+    }
+
+    /// Called by [`Machine`](crate::machine::Machine) after every cycle with the number of cycles that passed, so
+    /// the device can do work of its own.
+    fn tick(&mut self, _cycles: u64) {}
+
+    /// The interrupt number while the device asserts its interrupt line, `None` while the line is released.
+    fn interrupt(&self) -> Option<u8> {
+        None
+    }
+
+    /// The CPU acknowledged the interrupt this device requested. Devices that only request an interrupt once per
+    /// event release their line here, others keep it asserted until the handler talks to them.
+    fn acknowledge(&mut self) {}
 }
 
 pub trait IOMappedDevice {
@@ -35,4 +67,39 @@ pub trait IOMappedDevice {
     fn io_read(&mut self) -> DeviceResult;
     fn io_write(&mut self, data: u8) -> DeviceResult;
     fn io_name(&self) -> &str;
+
+    /// Number of consecutive ports from [`io_address`](IOMappedDevice::io_address) on, one per register.
+    fn io_size(&self) -> u8 {
+        1
+    }
+
+    /// Reads the register at `register` ports past the device's address. Devices with a single port only need
+    /// [`io_read`](IOMappedDevice::io_read).
+    fn io_read_register(&mut self, _register: u8) -> DeviceResult {
+        self.io_read()
+    }
+
+    fn io_write_register(&mut self, _register: u8, data: u8) -> DeviceResult {
+        self.io_write(data)
+    }
+
+    /// State that has to survive a save state, see [`AddressMappedDevice::save_state`].
+    fn save_state(&self) -> Value {
+        Value::Null
+    }
+
+    fn load_state(&mut self, _state: &Value) -> Result<(), serde_json::Error> {
+        Ok(())
+    }
+
+    /// See [`AddressMappedDevice::tick`].
+    fn tick(&mut self, _cycles: u64) {}
+
+    /// See [`AddressMappedDevice::interrupt`].
+    fn interrupt(&self) -> Option<u8> {
+        None
+    }
+
+    /// See [`AddressMappedDevice::acknowledge`].
+    fn acknowledge(&mut self) {}
 }
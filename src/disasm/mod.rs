@@ -0,0 +1,325 @@
+//! Disassembler for flat images like the ones the [assembler](crate::asm) writes. Like the assembler it decodes
+//! with the [`InstructionInfoFile`] the CPU is built from, and prints the same `.8do` syntax.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    asm::REGISTER_NAMES,
+    info::{InstructionInfo, InstructionInfoFile},
+};
+
+/// One decoded line. Unknown opcodes and instructions cut off by the end of the image are printed as `.byte`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub address: u32,
+    pub bytes: Vec<u8>,
+    pub text: String,
+}
+
+/// Where execution can continue after a line, used when following entry points.
+struct Flow {
+    falls_through: bool,
+    target: Option<u32>,
+}
+
+pub struct Disassembler {
+    opcodes: HashMap<u8, (InstructionInfo, String)>,
+}
+
+impl Disassembler {
+    pub fn new(inst_info: &InstructionInfoFile) -> Self {
+        let opcodes = inst_info
+            .info
+            .iter()
+            .flat_map(|info| info.opcode.iter().map(move |(mode, &opcode)| (opcode, (info.clone(), mode.clone()))))
+            .collect();
+
+        Self { opcodes }
+    }
+
+    /// Decodes the instruction at `address`, or returns `None` if the address is outside of `image`.
+    pub fn decode(&self, image: &[u8], address: u32) -> Option<Line> {
+        self.decode_flow(image, address).map(|(line, _)| line)
+    }
+
+    /// Decodes every line starting at `start`, until `end` (exclusive) or the end of the image is reached. Data
+    /// in between is decoded as if it were code.
+    pub fn linear(&self, image: &[u8], start: u32, end: u32) -> Vec<Line> {
+        let mut lines = Vec::new();
+        let mut address = start;
+
+        while address < end {
+            let Some(line) = self.decode(image, address) else {
+                break;
+            };
+
+            address += line.bytes.len() as u32;
+            lines.push(line);
+        }
+
+        lines
+    }
+
+    /// Decodes only what is reachable from `entries`, following jumps, branches and subroutine calls with a fixed
+    /// target. Jumps through a register offset can not be followed and have to be given as extra entries.
+    pub fn follow(&self, image: &[u8], entries: &[u32]) -> Vec<Line> {
+        let mut lines = BTreeMap::new();
+        let mut pending = entries.to_vec();
+
+        while let Some(address) = pending.pop() {
+            if lines.contains_key(&address) {
+                continue;
+            }
+
+            let Some((line, flow)) = self.decode_flow(image, address) else {
+                continue;
+            };
+
+            if flow.falls_through {
+                pending.push(address + line.bytes.len() as u32);
+            }
+
+            pending.extend(flow.target);
+            lines.insert(address, line);
+        }
+
+        lines.into_values().collect()
+    }
+
+    fn decode_flow(&self, image: &[u8], address: u32) -> Option<(Line, Flow)> {
+        let rest = image.get(address as usize..).filter(|rest| !rest.is_empty())?;
+
+        let Some((info, mode)) = self.opcodes.get(&rest[0]) else {
+            return Some(data(address, &rest[..1]));
+        };
+
+        let operand_size = match mode.chars().last() {
+            Some('I') if info.byte => 1,
+            Some('I') => 2,
+            Some('A') => 3,
+            _ => 0,
+        };
+
+        let Some(bytes) = rest.get(..3 + operand_size) else {
+            return Some(data(address, rest));
+        };
+
+        let metadata = u16::from_be_bytes([bytes[1], bytes[2]]);
+        let reg0 = REGISTER_NAMES[(metadata & 0xF) as usize];
+        let reg1 = REGISTER_NAMES[(metadata >> 4 & 0xF) as usize];
+        let operand = &bytes[3..];
+
+        let (text, target) = match mode.as_str() {
+            "M" => (info.name.clone(), None),
+            "R" => (format!("{} {reg0}", info.name), None),
+            "I" => (format!("{} {}", info.name, immediate(operand)), None),
+            "A" => {
+                let (text, target) = absolute(operand, metadata);
+                (format!("{} {text}", info.name), target)
+            }
+            "RR" => (format!("{} {reg0}, {reg1}", info.name), None),
+            "RI" => (format!("{} {reg0}, {}", info.name, immediate(operand)), None),
+            "RA" => (format!("{} {reg0}, {}", info.name, absolute(operand, metadata).0), None),
+            _ => return Some(data(address, &rest[..1])),
+        };
+
+        let flow = match info.name.as_str() {
+            "jmp" => Flow {
+                falls_through: false,
+                target,
+            },
+            "rts" | "rti" | "hlt" => Flow {
+                falls_through: false,
+                target: None,
+            },
+            // `jsr` and the conditional branches, which are the only other instructions starting with `b`.
+            name if name == "jsr" || name.starts_with('b') => Flow {
+                falls_through: true,
+                target,
+            },
+            _ => Flow {
+                falls_through: true,
+                target: None,
+            },
+        };
+
+        let line = Line {
+            address,
+            bytes: bytes.to_vec(),
+            text,
+        };
+
+        Some((line, flow))
+    }
+}
+
+fn data(address: u32, bytes: &[u8]) -> (Line, Flow) {
+    let values: Vec<String> = bytes.iter().map(|byte| format!("0x{byte:02X}")).collect();
+
+    let line = Line {
+        address,
+        bytes: bytes.to_vec(),
+        text: format!(".byte {}", values.join(", ")),
+    };
+
+    let flow = Flow {
+        falls_through: false,
+        target: None,
+    };
+
+    (line, flow)
+}
+
+fn immediate(operand: &[u8]) -> String {
+    match operand {
+        [byte] => format!("0x{byte:02X}"),
+        [hi, lo] => format!("0x{:04X}", u16::from_be_bytes([*hi, *lo])),
+        _ => unreachable!("Immediates are one or two bytes"),
+    }
+}
+
+// Formats an address operand with the offset the CPU adds to it, and returns the target if it does not depend
+// on a register.
+fn absolute(operand: &[u8], metadata: u16) -> (String, Option<u32>) {
+    let address = u32::from_be_bytes([0, operand[0], operand[1], operand[2]]);
+
+    if metadata & 1 << 15 > 0 {
+        let reg1 = metadata >> 4 & 0xF;
+
+        // The CPU only adds the register if it is one of the offset codes.
+        return match reg1 & 0b100 > 0 {
+            true => (format!("${address:06X} + {}", REGISTER_NAMES[reg1 as usize]), None),
+            false => (format!("${address:06X}"), Some(address)),
+        };
+    }
+
+    // The 6 bit offset with the sign bit on top, read as an `i8` just like the CPU does.
+    let offset = (metadata >> 8 & 0x3F | (metadata >> 14 & 1) << 7) as u8 as i8;
+    let target = address.wrapping_add_signed(offset as i32) & 0xFF_FFFF;
+
+    let text = match offset {
+        0 => format!("${address:06X}"),
+        offset if offset < 0 => format!("${address:06X} - {}", offset.unsigned_abs()),
+        offset => format!("${address:06X} + {offset}"),
+    };
+
+    (text, Some(target))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asm::assemble, info::get_instructions};
+
+    fn texts(lines: &[Line]) -> Vec<(u32, &str)> {
+        lines.iter().map(|line| (line.address, line.text.as_str())).collect()
+    }
+
+    #[test]
+    fn test_decode() {
+        let inst_info = get_instructions();
+        let disassembler = Disassembler::new(&inst_info);
+        let image = assemble(
+            "mov rb, 0x1234
+            movb rah, 0x12
+            mov rc, $000100 + rb
+            add ra, rd
+            out ra, 0xA0
+            biz $000200 - 2
+            hlt",
+            &inst_info,
+        )
+        .unwrap()
+        .image;
+
+        let lines = disassembler.linear(&image, 0, image.len() as u32);
+
+        assert_eq!(
+            texts(&lines),
+            [
+                (0x00, "mov rb, 0x1234"),
+                (0x05, "movb rah, 0x12"),
+                (0x09, "mov rc, $000100 + rb"),
+                (0x0F, "add ra, rd"),
+                (0x12, "out ra, 0xA0"),
+                (0x16, "biz $0001FE"),
+                (0x1C, "hlt"),
+            ]
+        );
+        assert_eq!(lines[3].bytes, [27, 0x00, 0x30]);
+    }
+
+    #[test]
+    fn test_decode_offsets_and_data() {
+        let inst_info = get_instructions();
+        let disassembler = Disassembler::new(&inst_info);
+
+        // jmp with an offset of +5 and -65 in the metadata, which the assembler never emits.
+        let image = [72, 0x05, 0x00, 0x00, 0x01, 0x00, 72, 0x7F, 0x00, 0x00, 0x01, 0x00, 0xFF, 59, 0x00];
+
+        assert_eq!(
+            texts(&disassembler.linear(&image, 0, 0x100)),
+            [
+                (0x00, "jmp $000100 + 5"),
+                (0x06, "jmp $000100 - 65"),
+                (0x0C, ".byte 0xFF"),
+                (0x0D, ".byte 0x3B, 0x00"),
+            ]
+        );
+        assert_eq!(disassembler.decode(&image, image.len() as u32), None);
+    }
+
+    #[test]
+    fn test_follow() {
+        let inst_info = get_instructions();
+        let disassembler = Disassembler::new(&inst_info);
+        let image = assemble(
+            ".byte 0x00 .word $0010
+            .org $0010
+            main:
+                jsr sub
+                biz end
+                jmp main
+            data: .byte 0xFF, 0xFF
+            sub:
+                rts
+            end:
+                hlt
+            .byte 0xFF",
+            &inst_info,
+        )
+        .unwrap()
+        .image;
+
+        assert_eq!(
+            texts(&disassembler.follow(&image, &[0x10])),
+            [
+                (0x10, "jsr $000024"),
+                (0x16, "biz $000027"),
+                (0x1C, "jmp $000010"),
+                (0x24, "rts"),
+                (0x27, "hlt"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reassemble() {
+        let inst_info = get_instructions();
+        let disassembler = Disassembler::new(&inst_info);
+        let image = assemble(include_str!("../../gen/hello_world.8do"), &inst_info).unwrap().image;
+
+        // Only the code, the string would decode into encodings the assembler never emits.
+        let lines = disassembler.follow(&image, &[0x200]);
+        let source: Vec<String> =
+            lines.iter().map(|line| format!(".org ${:06X}\n{}", line.address, line.text)).collect();
+        let reassembled = assemble(&source.join("\n"), &inst_info).unwrap().image;
+
+        assert_eq!(lines.len(), 12);
+
+        for line in lines {
+            let range = line.address as usize..line.address as usize + line.bytes.len();
+            assert_eq!(reassembled[range.clone()], image[range], "{}", line.text);
+        }
+    }
+}
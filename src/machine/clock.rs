@@ -0,0 +1,226 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+
+use super::Machine;
+
+/// How far the machine may fall behind wall-clock time before it stops trying to catch up, e.g. after sitting in
+/// the debugger or when the host is too slow for the frequency.
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+/// How [`Machine`] keeps the emulated clock in step with wall-clock time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClockSync {
+    /// Runs as fast as the host allows.
+    Unthrottled,
+    /// Sleeps whenever the machine gets a millisecond ahead.
+    Continuous,
+    /// Runs a whole frame of cycles at once and sleeps for the rest of it, at the given frames per second.
+    Frame(u32),
+}
+
+/// The target clock frequency of a [`Machine`] and how it is kept. Also measures the speed the machine
+/// actually ran at.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    frequency: u64,
+    sync: ClockSync,
+    // Wall-clock time and cycle counter the machine is compared against, moved forward when it falls behind.
+    epoch: Option<(Instant, u64)>,
+    next_sync: u64,
+    // First cycle the clock saw and the last one, for the effective speed.
+    started: Option<(Instant, u64)>,
+    latest: u64,
+}
+
+impl Default for Clock {
+    /// 1 MHz, unthrottled.
+    fn default() -> Self {
+        Self::new(1_000_000, ClockSync::Unthrottled)
+    }
+}
+
+impl Clock {
+    /// A clock running at `frequency` Hz.
+    pub fn new(frequency: u64, sync: ClockSync) -> Self {
+        Self {
+            frequency: frequency.max(1),
+            sync,
+            epoch: None,
+            next_sync: 0,
+            started: None,
+            latest: 0,
+        }
+    }
+
+    pub fn frequency(&self) -> u64 {
+        self.frequency
+    }
+
+    pub fn sync(&self) -> ClockSync {
+        self.sync
+    }
+
+    /// Cycles per second since the first cycle the clock saw, in MHz. `None` until some time passed.
+    pub fn effective_mhz(&self) -> Option<f64> {
+        let (start, cycles) = self.started?;
+        let elapsed = start.elapsed().as_secs_f64();
+
+        (elapsed > 0.0).then(|| self.latest.saturating_sub(cycles) as f64 / elapsed / 1_000_000.0)
+    }
+
+    // Cycles run between two checks against wall-clock time.
+    fn batch(&self) -> u64 {
+        match self.sync {
+            ClockSync::Unthrottled => u64::MAX,
+            ClockSync::Continuous => (self.frequency / 1000).max(1),
+            ClockSync::Frame(fps) => (self.frequency / fps.max(1) as u64).max(1),
+        }
+    }
+
+    /// Forgets the wall-clock time and cycle counter measured so far, measuring and throttling start over from the
+    /// next cycle. A restored state can move the counter forward as well, the clock would sleep for the jump.
+    pub(super) fn restart(&mut self) {
+        self.epoch = None;
+        self.next_sync = 0;
+        self.started = None;
+        self.latest = 0;
+    }
+
+    /// Called after every cycle with the CPU cycle counter, sleeps if the machine is ahead.
+    pub(super) fn after_cycle(&mut self, cycles: u64) {
+        if let Some(delay) = self.delay(cycles, Instant::now) {
+            thread::sleep(delay);
+        }
+    }
+
+    // How far the machine is ahead of wall-clock time after `cycles`, if it is. `now` is only asked for when the
+    // clock has to look at the time.
+    fn delay(&mut self, cycles: u64, now: impl Fn() -> Instant) -> Option<Duration> {
+        // Restoring a state can move the counter back, measuring and throttling start over from there.
+        if cycles < self.latest {
+            self.restart();
+        }
+
+        self.started.get_or_insert_with(|| (now(), cycles.saturating_sub(1)));
+        self.latest = cycles;
+
+        if cycles < self.next_sync {
+            return None;
+        }
+
+        self.next_sync = cycles.saturating_add(self.batch());
+
+        if self.sync == ClockSync::Unthrottled {
+            return None;
+        }
+
+        let now = now();
+        let (epoch, epoch_cycles) = *self.epoch.get_or_insert((now, cycles));
+        let target = epoch + Duration::from_secs_f64((cycles - epoch_cycles) as f64 / self.frequency as f64);
+
+        if target > now {
+            return Some(target - now);
+        }
+
+        if now - target > MAX_LAG {
+            self.epoch = Some((now, cycles));
+        }
+
+        None
+    }
+}
+
+impl Machine {
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    /// Replaces the clock, throttling starts from the next cycle on.
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::tests::machine;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_continuous() {
+        // One cycle per millisecond, checked after every cycle.
+        let mut clock = Clock::new(1_000, ClockSync::Continuous);
+        let start = Instant::now();
+
+        assert_eq!(clock.delay(1, || start), None);
+        assert_eq!(clock.delay(2, || start), Some(ms(1)));
+        assert_eq!(clock.delay(5, || start + ms(1)), Some(ms(3)));
+
+        // Behind, but less than the lag it catches up on.
+        assert_eq!(clock.delay(6, || start + ms(20)), None);
+        assert_eq!(clock.delay(7, || start + ms(20)), None);
+
+        // Too far behind, it starts over from now.
+        assert_eq!(clock.delay(8, || start + ms(500)), None);
+        assert_eq!(clock.delay(10, || start + ms(500)), Some(ms(2)));
+    }
+
+    #[test]
+    fn test_frame() {
+        // 100 cycles per frame, the time is only looked at once per frame.
+        let mut clock = Clock::new(2_000, ClockSync::Frame(20));
+        let start = Instant::now();
+
+        assert_eq!(clock.delay(1, || start), None);
+        assert_eq!(clock.delay(100, || panic!("Checked the time within a frame")), None);
+        assert_eq!(clock.delay(101, || start + ms(10)), Some(ms(40)));
+        assert_eq!(clock.delay(201, || start + ms(100)), None);
+    }
+
+    #[test]
+    fn test_rewind() {
+        let mut clock = Clock::new(1_000, ClockSync::Continuous);
+
+        clock.after_cycle(100);
+        clock.after_cycle(50);
+        assert_eq!(clock.started.unwrap().1, 49);
+
+        clock.after_cycle(51);
+        assert!(clock.effective_mhz().is_none_or(|mhz| mhz < 1.0));
+    }
+
+    #[test]
+    fn test_unthrottled() {
+        let mut clock = Clock::new(1, ClockSync::Unthrottled);
+        let start = Instant::now();
+
+        assert_eq!(clock.delay(1, || start), None);
+        assert_eq!(clock.delay(1_000_000, || start), None);
+        assert_eq!(clock.frequency(), 1);
+    }
+
+    // Depends on how busy the host is, run with `--ignored`.
+    #[test]
+    #[ignore]
+    fn test_throttled_run() {
+        let mut machine = machine(&[72, 0x00, 0x00, 0x00, 0x01, 0x00]); // jmp $000100
+        machine.set_clock(Clock::new(20_000, ClockSync::Continuous));
+
+        let start = Instant::now();
+        machine.run_for(2_000).unwrap();
+
+        assert!(start.elapsed() >= ms(90), "Took {:?}", start.elapsed());
+
+        let mhz = machine.clock().effective_mhz().unwrap();
+        assert!(mhz > 0.01 && mhz < 0.03, "Ran at {mhz} MHz");
+    }
+}
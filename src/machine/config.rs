@@ -0,0 +1,589 @@
+use std::{
+    fmt::Display,
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use serde::{de::Error, Deserialize, Deserializer};
+
+use super::{Clock, ClockSync, Machine};
+use crate::{
+    cpu::{EmuOptions, ExtendedAddress, ResetVector, CPU, FIRST_IRQ_VECTOR},
+    device::{Console, IOMappedDevice, Out, Pic, SerialFile, Timer, Uart, RAM, ROM},
+    info::InstructionInfoFile,
+    util::parse_number,
+};
+
+/// A board layout: the memory map, IO devices, clock and CPU options a [`Machine`] is built from. Files are JSON,
+/// addresses and ports can be written as numbers or as strings like `"$010000"` or `"0xA0"`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MachineConfig {
+    #[serde(default)]
+    pub memory: Vec<MemoryRegion>,
+    #[serde(default)]
+    pub io: Vec<IoDeviceConfig>,
+    #[serde(default)]
+    pub clock: Option<ClockConfig>,
+    #[serde(default)]
+    pub reset_vector: ResetVectorConfig,
+    #[serde(default)]
+    pub options: OptionsConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum MemoryRegion {
+    /// Read only memory filled from `file`, zero where the file is shorter. Without `end` the region is as large
+    /// as the file.
+    Rom {
+        #[serde(deserialize_with = "address")]
+        start: ExtendedAddress,
+        #[serde(default, deserialize_with = "optional_address")]
+        end: Option<ExtendedAddress>,
+        #[serde(default)]
+        file: Option<PathBuf>,
+    },
+    /// Memory that starts out zeroed, or with the contents of `file`.
+    Ram {
+        #[serde(deserialize_with = "address")]
+        start: ExtendedAddress,
+        #[serde(deserialize_with = "address")]
+        end: ExtendedAddress,
+        #[serde(default)]
+        file: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "device", rename_all = "lowercase", deny_unknown_fields)]
+pub enum IoDeviceConfig {
+    Out {
+        #[serde(deserialize_with = "port")]
+        port: u8,
+    },
+    /// A [`Timer`] on 6 ports from `port` on, requesting interrupt 5 unless `interrupt` says otherwise.
+    Timer {
+        #[serde(deserialize_with = "port")]
+        port: u8,
+        #[serde(default = "timer_interrupt")]
+        interrupt: u8,
+    },
+    /// A [`Pic`] all interrupts are routed through, a board can have one.
+    Pic {
+        #[serde(deserialize_with = "port")]
+        port: u8,
+        #[serde(default)]
+        vector_base: u8,
+    },
+    /// A [`Uart`] on 4 ports, requesting interrupt 6 unless `interrupt` says otherwise. It receives from the
+    /// `input` file and transmits into the `output` file, without either it is connected to stdin and stdout.
+    Uart {
+        #[serde(deserialize_with = "port")]
+        port: u8,
+        #[serde(default = "uart_interrupt")]
+        interrupt: u8,
+        #[serde(default)]
+        input: Option<PathBuf>,
+        #[serde(default)]
+        output: Option<PathBuf>,
+    },
+}
+
+fn timer_interrupt() -> u8 {
+    5
+}
+
+fn uart_interrupt() -> u8 {
+    6
+}
+
+impl IoDeviceConfig {
+    /// The first and last port of the device, an error if the last one would be past port 0xFF.
+    pub fn ports(&self) -> Result<(u8, u8), ConfigError> {
+        let (port, size, name) = match self {
+            IoDeviceConfig::Out { port } => (*port, Out::new(*port).io_size(), "Out device"),
+            IoDeviceConfig::Timer { port, .. } => (*port, Timer::new(*port, 0).io_size(), "timer"),
+            IoDeviceConfig::Pic { port, .. } => (*port, Pic::new(*port, 0).io_size(), "PIC"),
+            IoDeviceConfig::Uart { port, .. } => (*port, Uart::new(*port, 0, Console::default()).io_size(), "UART"),
+        };
+
+        match port.checked_add(size - 1) {
+            Some(last) => Ok((port, last)),
+            None => Err(ConfigError::Invalid(format!(
+                "The {name} on port {port:#04X} needs {size} ports and runs past port 0xFF"
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClockConfig {
+    /// In Hz.
+    pub frequency: u64,
+    #[serde(default = "continuous")]
+    pub sync: ClockSync,
+}
+
+fn continuous() -> ClockSync {
+    ClockSync::Continuous
+}
+
+/// Where the reset vector comes from, see [`ResetVector`].
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+pub enum ResetVectorConfig {
+    Memory(#[serde(deserialize_with = "address")] ExtendedAddress),
+    Fixed(#[serde(deserialize_with = "address")] ExtendedAddress),
+}
+
+impl Default for ResetVectorConfig {
+    fn default() -> Self {
+        ResetVectorConfig::Memory(ExtendedAddress::new_16bit_address(0x0000))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OptionsConfig {
+    pub exit_on_halt: bool,
+    pub trap_faults: bool,
+}
+
+impl Default for OptionsConfig {
+    fn default() -> Self {
+        Self {
+            exit_on_halt: true,
+            trap_faults: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error),
+    Format(serde_json::Error),
+    Invalid(String),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "Could not open {}: {err}", path.display()),
+            ConfigError::Format(err) => write!(f, "Invalid machine config: {err}"),
+            ConfigError::Invalid(message) => write!(f, "Invalid machine config: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<serde_json::Error> for ConfigError {
+    fn from(err: serde_json::Error) -> Self {
+        ConfigError::Format(err)
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, ConfigError> {
+    fs::read(path).map_err(|err| ConfigError::Io(path.to_path_buf(), err))
+}
+
+impl MachineConfig {
+    /// Reads a config file. Relative paths of backing files are resolved against the directory of the config.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let mut config: MachineConfig = serde_json::from_slice(&read(path)?)?;
+        let base = path.parent().unwrap_or(Path::new(""));
+
+        for region in &mut config.memory {
+            let (MemoryRegion::Rom { file, .. } | MemoryRegion::Ram { file, .. }) = region;
+
+            if let Some(file) = file {
+                *file = base.join(&*file);
+            }
+        }
+
+        for device in &mut config.io {
+            if let IoDeviceConfig::Uart { input, output, .. } = device {
+                for file in [input, output].into_iter().flatten() {
+                    *file = base.join(&*file);
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Whether a device is connected to stdin, which is a UART without an `input` or `output` file.
+    pub fn uses_stdin(&self) -> bool {
+        self.io
+            .iter()
+            .any(|device| matches!(device, IoDeviceConfig::Uart { input: None, output: None, .. }))
+    }
+
+    /// Builds the machine with the regions and devices in the order they are listed. Where regions overlap, the
+    /// one listed first is the one the CPU sees. Two devices on the same port, and interrupts that end up on a
+    /// vector reserved for the CPU, are rejected.
+    pub fn build(&self, inst_info: InstructionInfoFile) -> Result<Machine, ConfigError> {
+        self.check_vectors()?;
+
+        let mut options = EmuOptions::new();
+
+        if self.options.exit_on_halt {
+            options.set_exit_on_hlt();
+        }
+
+        if self.options.trap_faults {
+            options.set_trap_faults();
+        }
+
+        options.set_reset_vector(match self.reset_vector {
+            ResetVectorConfig::Memory(vector) => ResetVector::Memory(vector),
+            ResetVectorConfig::Fixed(address) => ResetVector::Fixed(address),
+        });
+
+        let mut machine = Machine::new(CPU::new(inst_info, Some(options)));
+
+        for region in &self.memory {
+            match region {
+                MemoryRegion::Rom { start, end, file } => {
+                    let data = match file {
+                        Some(file) => read(file)?,
+                        None => Vec::new(),
+                    };
+                    let end = match (end, file) {
+                        (Some(end), _) => *end,
+                        (None, None) => {
+                            return Err(ConfigError::Invalid(format!(
+                                "The ROM at ${:06X} needs an end or a file",
+                                u32::from(*start)
+                            )))
+                        }
+                        (None, Some(file)) if data.is_empty() => {
+                            return Err(ConfigError::Invalid(format!("{} is empty", file.display())))
+                        }
+                        (None, Some(file)) => {
+                            let end = u32::from(*start) as u64 + data.len() as u64 - 1;
+
+                            if end > 0xFF_FFFF {
+                                return Err(ConfigError::Invalid(format!(
+                                    "{} does not fit into memory when loaded at ${:06X}",
+                                    file.display(),
+                                    u32::from(*start)
+                                )));
+                            }
+
+                            ExtendedAddress::new_ext_address(end as u32)
+                        }
+                    };
+
+                    check_region(*start, end, data.len(), "ROM")?;
+                    machine.add_device(ROM::new(*start, end, data));
+                }
+                MemoryRegion::Ram { start, end, file } => {
+                    let data = match file {
+                        Some(file) => read(file)?,
+                        None => Vec::new(),
+                    };
+
+                    check_region(*start, *end, data.len(), "RAM")?;
+                    machine.add_device(RAM::new(*start, *end));
+
+                    for (address, byte) in (u32::from(*start)..).zip(data) {
+                        machine
+                            .cpu_mut()
+                            .poke(ExtendedAddress::new_ext_address(address), byte as u16, false);
+                    }
+                }
+            }
+        }
+
+        let mut ports: Vec<(u8, u8)> = Vec::new();
+
+        for device in &self.io {
+            let (first, last) = device.ports()?;
+
+            let overlap = ports.iter().find(|&&(other_first, other_last)| first <= other_last && other_first <= last);
+
+            if let Some(&(port, _)) = overlap {
+                return Err(ConfigError::Invalid(format!("Two devices on port {:#04X}", port.max(first))));
+            }
+
+            ports.push((first, last));
+
+            match device {
+                IoDeviceConfig::Out { port } => machine.add_io_device(Out::new(*port)),
+                IoDeviceConfig::Timer { port, interrupt } => {
+                    if *interrupt > 0xF {
+                        return Err(ConfigError::Invalid(format!("Interrupt {interrupt} of the timer is not 0-15")));
+                    }
+
+                    machine.add_io_device(Timer::new(*port, *interrupt));
+                }
+                IoDeviceConfig::Pic { port, vector_base } => {
+                    if machine.interrupt_controller().is_some() {
+                        return Err(ConfigError::Invalid("A board can only have one interrupt controller".into()));
+                    }
+
+                    if *vector_base > 0xF {
+                        return Err(ConfigError::Invalid(format!("Vector base {vector_base} of the PIC is not 0-15")));
+                    }
+
+                    machine.set_interrupt_controller(Pic::new(*port, *vector_base));
+                }
+                IoDeviceConfig::Uart {
+                    port,
+                    interrupt,
+                    input,
+                    output,
+                } => {
+                    if *interrupt > 0xF {
+                        return Err(ConfigError::Invalid(format!("Interrupt {interrupt} of the UART is not 0-15")));
+                    }
+
+                    if input.is_none() && output.is_none() {
+                        machine.add_io_device(Uart::new(*port, *interrupt, Console::default()));
+                        continue;
+                    }
+
+                    let data = match input {
+                        Some(input) => read(input)?,
+                        None => Vec::new(),
+                    };
+                    let output = output
+                        .as_ref()
+                        .map(|output| File::create(output).map_err(|err| ConfigError::Io(output.clone(), err)))
+                        .transpose()?;
+
+                    machine.add_io_device(Uart::new(*port, *interrupt, SerialFile::from_parts(data, output)));
+                }
+            }
+        }
+
+        if let Some(clock) = self.clock {
+            machine.set_clock(Clock::new(clock.frequency, clock.sync));
+        }
+
+        Ok(machine)
+    }
+
+    // The vectors before FIRST_IRQ_VECTOR belong to the CPU, an interrupt that ends up on one would run the
+    // handler of the reset, the NMI or an exception. Through a PIC the vector is its base plus the interrupt, and
+    // the interrupt has to be one of its 8 inputs.
+    fn check_vectors(&self) -> Result<(), ConfigError> {
+        let vector_base = self.io.iter().find_map(|device| match device {
+            IoDeviceConfig::Pic { vector_base, .. } => Some(*vector_base),
+            _ => None,
+        });
+
+        for device in &self.io {
+            let (name, interrupt) = match device {
+                IoDeviceConfig::Timer { interrupt, .. } => ("timer", *interrupt),
+                IoDeviceConfig::Uart { interrupt, .. } => ("UART", *interrupt),
+                IoDeviceConfig::Out { .. } | IoDeviceConfig::Pic { .. } => continue,
+            };
+
+            if vector_base.is_some() && interrupt >= 8 {
+                return Err(ConfigError::Invalid(format!(
+                    "Interrupt {interrupt} of the {name} is not an input of the PIC, it has inputs 0-7"
+                )));
+            }
+
+            let vector = vector_base.map_or(interrupt, |base| base.wrapping_add(interrupt) & 0xF);
+
+            if vector < FIRST_IRQ_VECTOR {
+                return Err(ConfigError::Invalid(format!(
+                    "Interrupt {interrupt} of the {name} uses vector {vector}, vectors 0-{} are reserved",
+                    FIRST_IRQ_VECTOR - 1
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn check_region(start: ExtendedAddress, end: ExtendedAddress, file_size: usize, kind: &str) -> Result<(), ConfigError> {
+    let (start, end) = (u32::from(start), u32::from(end));
+
+    if end < start {
+        return Err(ConfigError::Invalid(format!(
+            "The {kind} ${start:06X}-${end:06X} ends before it starts"
+        )));
+    }
+
+    if file_size > (end - start + 1) as usize {
+        return Err(ConfigError::Invalid(format!(
+            "The file of the {kind} at ${start:06X} is larger than the {kind}"
+        )));
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Number {
+    Number(u32),
+    Text(String),
+}
+
+impl Number {
+    fn value<E: Error>(self) -> Result<u32, E> {
+        match self {
+            Number::Number(value) => Ok(value),
+            Number::Text(text) => parse_number(&text).ok_or_else(|| E::custom(format!("invalid number {text}"))),
+        }
+    }
+}
+
+fn address<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ExtendedAddress, D::Error> {
+    let value = Number::deserialize(deserializer)?.value()?;
+
+    match value <= 0xFF_FFFF {
+        true => Ok(ExtendedAddress::new_ext_address(value)),
+        false => Err(D::Error::custom(format!("address {value:#X} is larger than 24 bits"))),
+    }
+}
+
+fn optional_address<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<ExtendedAddress>, D::Error> {
+    address(deserializer).map(Some)
+}
+
+fn port<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    let value = Number::deserialize(deserializer)?.value()?;
+    u8::try_from(value).map_err(|_| D::Error::custom(format!("port {value:#X} is larger than 8 bits")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::info::get_instructions;
+
+    const BOARD: &str = r#"{
+        "memory": [
+            { "type": "rom", "start": "$000000", "file": "program.bin" },
+            { "type": "ram", "start": "$010000", "end": "$01FFFF" }
+        ],
+        "io": [{ "device": "out", "port": "0xA0" }, { "device": "timer", "port": "0xB0", "interrupt": 6 }],
+        "clock": { "frequency": 2000000, "sync": { "frame": 60 } },
+        "reset_vector": { "memory": 0 },
+        "options": { "trap_faults": true }
+    }"#;
+
+    #[test]
+    fn test_load_and_build() {
+        let dir = std::env::temp_dir().join(format!("hexacore-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("board.json"), BOARD).unwrap();
+        fs::write(
+            dir.join("program.bin"),
+            [
+                0x00, 0x00, 0x03, // reset vector
+                59, 0x00, 0x00, 0x00, 0x2A, // mov ra, 0x002A
+                8, 0x00, 0x00, // hlt
+            ],
+        )
+        .unwrap();
+
+        let machine = MachineConfig::load(dir.join("board.json")).and_then(|config| config.build(get_instructions()));
+        fs::remove_dir_all(&dir).ok();
+
+        let mut machine = machine.unwrap();
+        assert_eq!(machine.clock().frequency(), 2_000_000);
+        assert_eq!(machine.clock().sync(), ClockSync::Frame(60));
+        assert_eq!(machine.cpu().device_count(), (2, 2));
+
+        machine.set_clock(Clock::default());
+        assert_eq!(machine.run(), Ok(0x002A));
+    }
+
+    #[test]
+    fn test_fixed_reset_vector() {
+        let config: MachineConfig = serde_json::from_str(
+            r#"{
+                "memory": [{ "type": "ram", "start": 0, "end": "0xFFFF" }],
+                "reset_vector": { "fixed": "$000200" }
+            }"#,
+        )
+        .unwrap();
+
+        let mut machine = config.build(get_instructions()).unwrap();
+        machine.step_instruction().unwrap();
+
+        assert_eq!(machine.cpu().pc(), 0x00_0200);
+    }
+
+    #[test]
+    fn test_invalid_configs() {
+        let build = |json: &str| {
+            serde_json::from_str::<MachineConfig>(json)
+                .unwrap()
+                .build(get_instructions())
+        };
+
+        assert!(matches!(
+            build(r#"{ "memory": [{ "type": "ram", "start": 16, "end": 15 }] }"#),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            build(r#"{ "io": [{ "device": "out", "port": 1 }, { "device": "out", "port": 1 }] }"#),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            build(r#"{ "io": [{ "device": "out", "port": "0xA3" }, { "device": "timer", "port": "0xA0" }] }"#),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            build(r#"{ "io": [{ "device": "timer", "port": "0xFE" }] }"#),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            build(r#"{ "io": [{ "device": "timer", "port": "0xB0", "interrupt": 16 }] }"#),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            build(r#"{ "io": [{ "device": "pic", "port": "0x20" }, { "device": "pic", "port": "0x30" }] }"#),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            build(r#"{ "memory": [{ "type": "rom", "start": 0 }] }"#),
+            Err(ConfigError::Invalid(_))
+        ));
+
+        assert!(matches!(
+            build(r#"{ "io": [{ "device": "timer", "port": "0xB0", "interrupt": 3 }] }"#),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            build(r#"{ "io": [{ "device": "uart", "port": "0xC0", "interrupt": 2, "input": "in.txt" }] }"#),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            build(r#"{ "io": [{ "device": "timer", "port": 1 }, { "device": "pic", "port": 8, "vector_base": 12 }] }"#),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            build(r#"{ "io": [{ "device": "timer", "port": 1, "interrupt": 9 }, { "device": "pic", "port": 8 }] }"#),
+            Err(ConfigError::Invalid(_))
+        ));
+
+        assert!(serde_json::from_str::<MachineConfig>(r#"{ "io": [{ "device": "out", "port": 256 }] }"#).is_err());
+        assert!(serde_json::from_str::<MachineConfig>(
+            r#"{ "memory": [{ "type": "ram", "start": "$1000000", "end": 0 }] }"#
+        )
+        .is_err());
+        assert!(serde_json::from_str::<MachineConfig>(r#"{ "speed": 1 }"#).is_err());
+    }
+
+    #[test]
+    fn test_uses_stdin() {
+        let config = |json: &str| serde_json::from_str::<MachineConfig>(json).unwrap();
+
+        assert!(!config(BOARD).uses_stdin());
+        assert!(!config(r#"{ "io": [{ "device": "uart", "port": "0xC0", "input": "input.txt" }] }"#).uses_stdin());
+        assert!(!config(r#"{ "io": [{ "device": "uart", "port": "0xC0", "output": "output.txt" }] }"#).uses_stdin());
+        assert!(config(r#"{ "io": [{ "device": "out", "port": 1 }, { "device": "uart", "port": 2 }] }"#).uses_stdin());
+    }
+}
@@ -0,0 +1,102 @@
+use std::{cell::RefCell, rc::Rc};
+
+use super::Machine;
+use crate::{cpu::DeviceId, device::Pic};
+
+impl Machine {
+    /// Adds `pic` as IO device and routes every device interrupt line through it: the interrupt number a device
+    /// asserts is the controller input it is wired to. From then on the controller drives the IRQ pins.
+    pub fn set_interrupt_controller(&mut self, pic: Pic) -> Rc<RefCell<Pic>> {
+        let pic = Rc::new(RefCell::new(pic));
+        self.cpu.add_shared_io_device(pic.clone());
+        self.interrupt_controller = Some(pic.clone());
+        pic
+    }
+
+    pub fn interrupt_controller(&self) -> Option<&Rc<RefCell<Pic>>> {
+        self.interrupt_controller.as_ref()
+    }
+
+    /// Ticks every device and drives the IRQ pins from their interrupt lines. The interrupt number on the pins
+    /// is only changed again once the CPU read the one it acknowledged.
+    pub(super) fn tick_devices(&mut self) {
+        let acknowledging = self.cpu.acknowledging_irq(&self.pins);
+        let requests = self.cpu.tick_devices(1);
+
+        match self.interrupt_controller.clone() {
+            Some(pic) => self.route_through(&mut pic.borrow_mut(), &requests, acknowledging),
+            None => self.route_directly(&requests, acknowledging),
+        }
+    }
+
+    // The device added first wins when several assert their line.
+    fn route_directly(&mut self, requests: &[(DeviceId, u8)], acknowledging: bool) {
+        if acknowledging {
+            if let Some(id) = self.irq_device {
+                self.cpu.acknowledge_device(id);
+            }
+
+            return;
+        }
+
+        match requests.first() {
+            Some(&(id, number)) => {
+                self.pins.irq.req = true;
+                self.pins.irq.data = number;
+                self.irq_device = Some(id);
+            }
+            None if self.irq_device.take().is_some() => self.pins.irq.req = false,
+            None => {}
+        }
+    }
+
+    // Interrupt numbers past the 8 inputs are wired to nothing, a board config rejects devices using them. Every
+    // device on the winning input is acknowledged. Without a request left to acknowledge (the line was
+    // released in the meantime), the interrupt number on the pins stays what it was.
+    fn route_through(&mut self, pic: &mut Pic, requests: &[(DeviceId, u8)], acknowledging: bool) {
+        let inputs = requests.iter().filter(|&&(_, input)| input < 8);
+        pic.set_inputs(inputs.fold(0, |inputs, &(_, input)| inputs | 1 << input));
+
+        if !acknowledging {
+            self.pins.irq.req = pic.requesting();
+            return;
+        }
+
+        if let Some((input, number)) = pic.acknowledge_request() {
+            self.pins.irq.data = number;
+
+            for &(id, _) in requests.iter().filter(|&&(_, line)| line == input) {
+                self.cpu.acknowledge_device(id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        device::{DeviceResult, IOMappedDevice, Timer, PIC_IN_SERVICE},
+        machine::tests::{interrupt_machine, Pulse},
+    };
+
+    #[test]
+    fn test_interrupt_controller() {
+        let mut machine = interrupt_machine(
+            "mov ra, 0x0001 ; Clear the expired flag of the timer
+            out ra, 0xB4
+            mov ra, 0x0020  ; End of interrupt
+            out ra, 0x23",
+        );
+        machine.add_io_device(Timer::new(0xB0, 5));
+
+        let acknowledged = Rc::new(RefCell::new(0));
+        machine.add_io_device(Pulse::new(150, acknowledged.clone()));
+        let pic = machine.set_interrupt_controller(Pic::new(0x20, 0));
+
+        assert_eq!(machine.run(), Ok(0x0020));
+        assert_eq!(machine.cpu().state().rd, 3);
+        assert_eq!(*acknowledged.borrow(), 2);
+        assert_eq!(pic.borrow_mut().io_read_register(PIC_IN_SERVICE), DeviceResult::Ok8(0));
+    }
+}
@@ -0,0 +1,480 @@
+mod clock;
+mod config;
+mod interrupts;
+mod snapshot;
+mod trace;
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    cpu::{CycleStatus, DeviceId, EmuError, Pins, CPU},
+    device::{AddressMappedDevice, IOMappedDevice, Pic},
+};
+pub use clock::{Clock, ClockSync};
+pub use config::{
+    ClockConfig, ConfigError, IoDeviceConfig, MachineConfig, MemoryRegion, OptionsConfig, ResetVectorConfig,
+};
+pub use snapshot::{Snapshot, SnapshotError, SNAPSHOT_VERSION};
+pub use trace::{TraceAccess, TraceFormat, TraceRecord, Tracer};
+
+/// A CPU together with its pins and devices. It runs the bus loop every embedder would otherwise have to
+/// write themselves: run a cycle, then service whatever the CPU put on the memory and IO bus.
+pub struct Machine {
+    cpu: CPU,
+    pins: Pins,
+    clock: Clock,
+    tracer: Option<Tracer>,
+    // The device driving the IRQ pin, so a request the host made itself is left alone.
+    irq_device: Option<DeviceId>,
+    interrupt_controller: Option<Rc<RefCell<Pic>>>,
+}
+
+impl Machine {
+    /// Creates a machine around `cpu` and resets it. Devices can be added to the CPU before or after.
+    pub fn new(cpu: CPU) -> Self {
+        let mut machine = Self {
+            cpu,
+            pins: Pins::default(),
+            clock: Clock::default(),
+            tracer: None,
+            irq_device: None,
+            interrupt_controller: None,
+        };
+
+        machine.reset();
+        machine
+    }
+
+    pub fn cpu(&self) -> &CPU {
+        &self.cpu
+    }
+
+    pub fn cpu_mut(&mut self) -> &mut CPU {
+        &mut self.cpu
+    }
+
+    pub fn pins(&self) -> &Pins {
+        &self.pins
+    }
+
+    pub fn pins_mut(&mut self) -> &mut Pins {
+        &mut self.pins
+    }
+
+    pub fn add_device<T: AddressMappedDevice + 'static>(&mut self, device: T) {
+        self.cpu.add_device(device);
+    }
+
+    pub fn add_shared_device<T: AddressMappedDevice + 'static>(&mut self, device: Rc<RefCell<T>>) {
+        self.cpu.add_shared_device(device);
+    }
+
+    pub fn add_io_device<T: IOMappedDevice + 'static>(&mut self, device: T) {
+        self.cpu.add_io_device(device);
+    }
+
+    pub fn add_shared_io_device<T: IOMappedDevice + 'static>(&mut self, device: Rc<RefCell<T>>) {
+        self.cpu.add_shared_io_device(device);
+    }
+
+    pub fn reset(&mut self) {
+        self.cpu.reset(&mut self.pins);
+    }
+
+    /// Runs a single CPU cycle and services the bus, then waits if the machine is ahead of its [`Clock`]. If the
+    /// bus access hit a pausing watchpoint, this reports [`CycleStatus::Watchpoint`] instead of
+    /// [`CycleStatus::Running`].
+    pub fn step_cycle(&mut self) -> Result<CycleStatus, EmuError> {
+        let result = self.cpu.cycle(&mut self.pins).and_then(|status| {
+            self.cpu.service_bus(&mut self.pins)?;
+            Ok(status)
+        });
+
+        self.tick_devices();
+        self.clock.after_cycle(self.cpu.cycles());
+
+        let status = match (result, &mut self.tracer) {
+            (Ok(status), Some(tracer)) => {
+                tracer.after_cycle(&self.cpu);
+                status
+            }
+            (Ok(status), None) => status,
+            (Err(err), tracer) => {
+                if let Some(tracer) = tracer {
+                    tracer.fault(&err);
+                }
+
+                return Err(err);
+            }
+        };
+
+        match (status, self.cpu.take_watch_hit()) {
+            (CycleStatus::Running, Some(access)) => Ok(CycleStatus::Watchpoint(access)),
+            (status, _) => Ok(status),
+        }
+    }
+
+    /// Runs cycles until the CPU is about to fetch the next instruction. The reset sequence and interrupt
+    /// entries count as part of the instruction they precede. A halted CPU only runs a single cycle, a
+    /// watchpoint stops in the middle of the instruction.
+    pub fn step_instruction(&mut self) -> Result<CycleStatus, EmuError> {
+        loop {
+            let status = self.step_cycle()?;
+
+            if status != CycleStatus::Running || self.cpu.at_instruction_boundary() || self.cpu.is_halted() {
+                return Ok(status);
+            }
+        }
+    }
+
+    /// Runs `cycles` cycles, stopping early if the CPU reports anything but [`CycleStatus::Running`].
+    pub fn run_for(&mut self, cycles: u64) -> Result<CycleStatus, EmuError> {
+        for _ in 0..cycles {
+            let status = self.step_cycle()?;
+
+            if status != CycleStatus::Running {
+                return Ok(status);
+            }
+        }
+
+        Ok(CycleStatus::Running)
+    }
+
+    /// Runs cycles until `predicate` returns true or the CPU reports anything but [`CycleStatus::Running`], the
+    /// predicate is checked after every cycle. Returns the number of cycles that were run.
+    pub fn run_until<F: FnMut(&Machine) -> bool>(&mut self, mut predicate: F) -> Result<u64, EmuError> {
+        let mut cycles = 0;
+
+        loop {
+            let status = self.step_cycle()?;
+            cycles += 1;
+
+            if status != CycleStatus::Running || predicate(self) {
+                return Ok(cycles);
+            }
+        }
+    }
+
+    /// Runs until the program halts and returns its exit code. The CPU needs `exit_on_hlt` set, otherwise
+    /// this only returns on an error. Pausing watchpoints are ignored, use a callback to see accesses here.
+    pub fn run(&mut self) -> Result<u16, EmuError> {
+        loop {
+            if let CycleStatus::Halted(code) = self.step_cycle()? {
+                return Ok(code);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asm::assemble,
+        cpu::{AddressSpace, BusAccess, EmuOptions, ExtendedAddress, ReadWrite, WatchAction, WatchKind},
+        device::{DeviceResult, Out, SerialBuffer, Timer, Uart, RAM, ROM, UART_CONTROL, UART_RX_IRQ_ENABLE},
+        info::get_instructions,
+    };
+
+    pub(super) fn machine(program: &[u8]) -> Machine {
+        let mut data = vec![0x00, 0x01, 0x00];
+        data.resize(0x100, 0);
+        data.extend_from_slice(program);
+
+        let mut machine = Machine::new(CPU::new(get_instructions(), None));
+        machine.add_device(ROM::new(
+            ExtendedAddress::new_ext_address(0x00_0000),
+            ExtendedAddress::new_ext_address(0x00_FFFF),
+            data,
+        ));
+        machine.add_device(RAM::new(
+            ExtendedAddress::new_ext_address(0x01_0000),
+            ExtendedAddress::new_ext_address(0x01_FFFF),
+        ));
+
+        machine
+    }
+
+    #[test]
+    fn test_step_instruction() {
+        let mut machine = machine(&[
+            210, 0x00, 0x00,                  // clc
+            72, 0x00, 0x00, 0x00, 0x01, 0x00, // jmp $000100
+        ]);
+
+        machine.step_instruction().unwrap();
+        assert_eq!(machine.cpu().pc(), 0x00_0100);
+
+        machine.step_instruction().unwrap();
+        assert_eq!(machine.cpu().pc(), 0x00_0103);
+
+        machine.step_instruction().unwrap();
+        assert_eq!(machine.cpu().pc(), 0x00_0100);
+    }
+
+    #[test]
+    fn test_run_until() {
+        let mut machine = machine(&[
+            210, 0x00, 0x00, // clc
+            210, 0x00, 0x00, // clc
+            8, 0x00, 0x00,   // hlt
+        ]);
+
+        let cycles = machine.run_until(|m| m.cpu().is_halted()).unwrap();
+
+        assert_eq!(cycles, 3 + 4 + 4 + 4);
+        assert_eq!(machine.cpu().pc(), 0x00_0109);
+
+        machine.step_instruction().unwrap();
+        assert!(machine.cpu().is_halted());
+    }
+
+    #[test]
+    fn test_run_for_error() {
+        let mut machine = machine(&[0x00, 0x00, 0x00]);
+
+        assert!(matches!(machine.run_for(100), Err(EmuError::UnknownOpcode(_))));
+    }
+
+    #[test]
+    fn test_run_exit_code() {
+        let mut machine = machine(&[
+            59, 0x00, 0x00, 0x00, 0x2A, // mov ra, 0x002A
+            8, 0x00, 0x00,              // hlt
+        ]);
+        machine.cpu_mut().options_mut().set_exit_on_hlt();
+
+        assert_eq!(machine.run(), Ok(0x2A));
+        assert_eq!(machine.step_cycle(), Ok(CycleStatus::Halted(0x2A)));
+    }
+
+    #[test]
+    fn test_halt_without_exit() {
+        let mut machine = machine(&[8, 0x00, 0x00]); // hlt
+
+        assert_eq!(machine.run_for(100), Ok(CycleStatus::Running));
+        assert!(machine.cpu().is_halted());
+    }
+
+    #[test]
+    fn test_watchpoint_pause() {
+        let mut machine = machine(&[
+            52, 0x00, 0x00, 0xBE, 0xEF, // psh 0xBEEF
+            8, 0x00, 0x00,              // hlt
+        ]);
+        machine.cpu_mut().options_mut().set_exit_on_hlt();
+        let id = machine.cpu_mut().add_watchpoint(AddressSpace::Memory, 0x01_0001..=0x01_0001, WatchKind::Write, WatchAction::Pause);
+
+        let access = BusAccess {
+            space: AddressSpace::Memory,
+            address: 0x01_0000,
+            rw: ReadWrite::Write,
+            data: 0xBEEF,
+            word: true,
+            pc: ExtendedAddress::new_ext_address(0x00_0100),
+            watchpoint: id,
+        };
+
+        // The word write covers the watched byte.
+        assert_eq!(machine.run_for(100), Ok(CycleStatus::Watchpoint(access)));
+        assert_eq!(machine.run(), Ok(0));
+    }
+
+    #[test]
+    fn test_watchpoint_callback() {
+        let mut machine = machine(&[
+            59, 0x00, 0x00, 0x00, 0x00, // mov ra, 0x0000
+            65, 0x00, 0x00, 0xA0,       // out ra, 0xA0
+            8, 0x00, 0x00,              // hlt
+        ]);
+        machine.add_io_device(Out::new(0xA0));
+        machine.cpu_mut().options_mut().set_exit_on_hlt();
+
+        let accesses = Rc::new(RefCell::new(Vec::new()));
+        let seen = accesses.clone();
+        machine.cpu_mut().add_watchpoint(
+            AddressSpace::Io,
+            0xA0..=0xA0,
+            WatchKind::Access,
+            WatchAction::Callback(Box::new(move |access| seen.borrow_mut().push((access.address, access.rw, access.data)))),
+        );
+
+        assert_eq!(machine.run(), Ok(0));
+        assert_eq!(*accesses.borrow(), [(0xA0, ReadWrite::Write, 0x00)]);
+    }
+
+    // Asserts its interrupt line every `period` cycles and releases it when the CPU acknowledges.
+    pub(super) struct Pulse {
+        period: u64,
+        elapsed: u64,
+        asserted: bool,
+        acknowledged: Rc<RefCell<u32>>,
+    }
+
+    impl Pulse {
+        pub(super) fn new(period: u64, acknowledged: Rc<RefCell<u32>>) -> Self {
+            Self {
+                period,
+                elapsed: 0,
+                asserted: false,
+                acknowledged,
+            }
+        }
+    }
+
+    impl IOMappedDevice for Pulse {
+        fn io_address(&self) -> u8 {
+            0xC0
+        }
+
+        fn io_read(&mut self) -> DeviceResult {
+            DeviceResult::WriteOnly
+        }
+
+        fn io_write(&mut self, _data: u8) -> DeviceResult {
+            DeviceResult::ReadOnly
+        }
+
+        fn io_name(&self) -> &str {
+            "Pulse"
+        }
+
+        fn tick(&mut self, cycles: u64) {
+            self.elapsed += cycles;
+
+            if self.elapsed >= self.period {
+                self.elapsed = 0;
+                self.asserted = true;
+            }
+        }
+
+        fn interrupt(&self) -> Option<u8> {
+            self.asserted.then_some(6)
+        }
+
+        fn acknowledge(&mut self) {
+            self.asserted = false;
+            *self.acknowledged.borrow_mut() += 1;
+        }
+    }
+
+    pub(super) fn interrupt_machine(handler: &str) -> Machine {
+        let source = format!(
+            "
+            .byte 0x00 .word $0100
+            .org $000F
+            .byte 0x00 .word handler ; Interrupt 5
+            .byte 0x00 .word handler ; Interrupt 6
+            .org $0100
+            main:
+                mov ra, 0x00C8       ; Reload 200
+                out ra, 0xB2
+                mov ra, 0x0007       ; Enabled, periodic, interrupts
+                out ra, 0xB0
+                cli
+            wait:
+                cmp rd, 0x0003
+                bnz wait
+                hlt
+            .org $0200
+            handler:
+                inc rd
+                {handler}
+                rti"
+        );
+        let image = assemble(&source, &get_instructions()).unwrap().image;
+
+        let mut machine = Machine::new(CPU::new(get_instructions(), Some(EmuOptions::new_value(1))));
+        machine.add_device(ROM::new(
+            ExtendedAddress::new_ext_address(0x00_0000),
+            ExtendedAddress::new_ext_address(0x00_FFFF),
+            image,
+        ));
+        machine.add_device(RAM::new(
+            ExtendedAddress::new_ext_address(0x01_0000),
+            ExtendedAddress::new_ext_address(0x01_FFFF),
+        ));
+
+        machine
+    }
+
+    #[test]
+    fn test_device_interrupts() {
+        // Without a timer on its ports, the setup writes go nowhere.
+        let mut machine = interrupt_machine("");
+        machine.add_io_device(Port(0xB0));
+
+        let acknowledged = Rc::new(RefCell::new(0));
+        machine.add_io_device(Pulse::new(150, acknowledged.clone()));
+
+        assert_eq!(machine.run(), Ok(0x0007));
+        assert_eq!(machine.cpu().state().rd, 3);
+        assert_eq!(*acknowledged.borrow(), 3);
+        assert!(!machine.pins().irq.req);
+    }
+
+    #[test]
+    fn test_timer_interrupts() {
+        let mut machine = interrupt_machine(
+            "mov ra, 0x0001 ; Clear the expired flag
+            out ra, 0xB4",
+        );
+        let timer = Rc::new(RefCell::new(Timer::new(0xB0, 5)));
+        machine.add_shared_io_device(timer.clone());
+
+        assert_eq!(machine.run(), Ok(0x0001));
+        assert_eq!(machine.cpu().state().rd, 3);
+        assert!((600..800).contains(&machine.cpu().cycles()), "Ran {} cycles", machine.cpu().cycles());
+        assert!(!machine.pins().irq.req);
+
+        // The last expiry was cleared, the timer no longer requests its interrupt.
+        assert_eq!(timer.borrow().interrupt(), None);
+    }
+
+    #[test]
+    fn test_uart_interrupts() {
+        let mut machine = interrupt_machine(
+            "in ra, 0xC0 ; Echo the received byte
+            out ra, 0xC0",
+        );
+        machine.add_io_device(Port(0xB0));
+
+        let buffer = SerialBuffer::default();
+        buffer.push_input(b"abc");
+        let mut uart = Uart::new(0xC0, 6, buffer.clone()).with_cycles_per_byte(50);
+        uart.io_write_register(UART_CONTROL, UART_RX_IRQ_ENABLE);
+        machine.add_io_device(uart);
+
+        assert_eq!(machine.run(), Ok(0x0063));
+        assert_eq!(machine.cpu().state().rd, 3);
+
+        // The last byte is still on its way when the program halts.
+        machine.cpu_mut().tick_devices(50);
+        assert_eq!(buffer.output(), b"abc");
+    }
+
+    // Accepts every write on its 6 ports.
+    struct Port(u8);
+
+    impl IOMappedDevice for Port {
+        fn io_address(&self) -> u8 {
+            self.0
+        }
+
+        fn io_read(&mut self) -> DeviceResult {
+            DeviceResult::Ok8(0)
+        }
+
+        fn io_write(&mut self, _data: u8) -> DeviceResult {
+            DeviceResult::Ok
+        }
+
+        fn io_name(&self) -> &str {
+            "Port"
+        }
+
+        fn io_size(&self) -> u8 {
+            6
+        }
+    }
+}
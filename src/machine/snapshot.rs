@@ -0,0 +1,223 @@
+use std::{fmt::Display, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::Machine;
+use crate::cpu::{CpuSnapshot, DeviceId, Pins};
+
+/// Bumped whenever the layout of [`Snapshot`] changes, older save states are rejected instead of being
+/// loaded into the wrong fields.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// A save state of a whole [`Machine`]. Device states are stored in the order the devices were added, so a
+/// snapshot can only be restored into a machine built with the same devices.
+///
+/// Left out are the parts that come from building the machine rather than from running it: the [`Clock`]
+/// (frequency, sync and speed measurement start over after a restore), the tracer and how devices are wired to
+/// the interrupt controller. Restoring into a machine built from another board config can't bring those back.
+///
+/// [`Clock`]: super::Clock
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    version: u32,
+    cpu: CpuSnapshot,
+    pins: Pins,
+    // The device whose request is on the IRQ pins, so it is the one acknowledged.
+    irq_device: Option<DeviceId>,
+    devices: Vec<Value>,
+    io_devices: Vec<Value>,
+}
+
+impl Snapshot {
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn cpu(&self) -> &CpuSnapshot {
+        &self.cpu
+    }
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(std::io::Error),
+    Format(serde_json::Error),
+    Version(u32),
+    DeviceMismatch { expected: (usize, usize), found: (usize, usize) },
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotError::Io(err) => write!(f, "Could not access the save state: {err}"),
+            SnapshotError::Format(err) => write!(f, "Invalid save state: {err}"),
+            SnapshotError::Version(version) => {
+                write!(f, "Save state version {version} is not supported (expected {SNAPSHOT_VERSION})")
+            }
+            SnapshotError::DeviceMismatch { expected, found } => write!(
+                f,
+                "Save state has {} memory and {} IO devices, the machine has {} and {}",
+                found.0, found.1, expected.0, expected.1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(err: std::io::Error) -> Self {
+        SnapshotError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(err: serde_json::Error) -> Self {
+        SnapshotError::Format(err)
+    }
+}
+
+impl Machine {
+    pub fn snapshot(&self) -> Snapshot {
+        let (devices, io_devices) = self.cpu.save_device_states();
+
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            cpu: self.cpu.snapshot(),
+            pins: self.pins.clone(),
+            irq_device: self.irq_device,
+            devices,
+            io_devices,
+        }
+    }
+
+    /// Restores the machine from `snapshot`. Nothing is changed if the version or devices don't match, a device
+    /// rejecting its state can leave the devices before it already restored.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), SnapshotError> {
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::Version(snapshot.version));
+        }
+
+        let expected = self.cpu.device_count();
+        let found = (snapshot.devices.len(), snapshot.io_devices.len());
+
+        if expected != found {
+            return Err(SnapshotError::DeviceMismatch { expected, found });
+        }
+
+        self.cpu.load_device_states(&snapshot.devices, &snapshot.io_devices)?;
+        self.cpu.restore(&snapshot.cpu);
+        self.pins = snapshot.pins.clone();
+        self.irq_device = snapshot.irq_device;
+        self.clock.restart();
+
+        Ok(())
+    }
+
+    pub fn save_snapshot<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError> {
+        fs::write(path, serde_json::to_vec(&self.snapshot())?)?;
+        Ok(())
+    }
+
+    pub fn load_snapshot<P: AsRef<Path>>(&mut self, path: P) -> Result<(), SnapshotError> {
+        let value: Value = serde_json::from_slice(&fs::read(path)?)?;
+
+        // Check the version first, a snapshot from another version most likely doesn't deserialize at all.
+        match value.get("version").and_then(Value::as_u64) {
+            Some(version) if version == SNAPSHOT_VERSION as u64 => {}
+            version => return Err(SnapshotError::Version(version.unwrap_or(0) as u32)),
+        }
+
+        self.restore(&serde_json::from_value(value)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        device::Timer,
+        machine::tests::{interrupt_machine, machine},
+    };
+
+    const PROGRAM: &[u8] = &[
+        59, 0x00, 0x00, 0x12, 0x34, // mov ra, 0x1234
+        61, 0x00, 0x00,             // psh ra
+        59, 0x00, 0x00, 0x00, 0x00, // mov ra, 0x0000
+        97, 0x00, 0x01,             // pop rb
+        8, 0x00, 0x00,              // hlt
+    ];
+
+    #[test]
+    fn test_restore_mid_instruction() {
+        let mut original = machine(PROGRAM);
+        original.step_instruction().unwrap();
+        original.step_instruction().unwrap();
+        original.run_for(2).unwrap();
+
+        let snapshot = original.snapshot();
+        original.run_until(|m| m.cpu().is_halted()).unwrap();
+
+        let mut restored = machine(PROGRAM);
+        restored.restore(&snapshot).unwrap();
+        assert_eq!(restored.cpu().state(), *snapshot.cpu().state());
+
+        restored.run_until(|m| m.cpu().is_halted()).unwrap();
+        assert_eq!(restored.cpu().state(), original.cpu().state());
+        assert_eq!(restored.cpu().state().rb, 0x1234);
+        assert_eq!(restored.snapshot().devices, original.snapshot().devices);
+    }
+
+    #[test]
+    fn test_snapshot_file() {
+        let path = std::env::temp_dir().join(format!("hexacore-snapshot-{}.json", std::process::id()));
+
+        let mut original = machine(PROGRAM);
+        original.step_instruction().unwrap();
+        original.step_instruction().unwrap();
+        original.save_snapshot(&path).unwrap();
+
+        let mut restored = machine(PROGRAM);
+        restored.load_snapshot(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(restored.cpu().state(), original.cpu().state());
+        assert_eq!(restored.snapshot().devices, original.snapshot().devices);
+    }
+
+    #[test]
+    fn test_restore_interrupt_request() {
+        let mut original = interrupt_machine("");
+        original.add_io_device(Timer::new(0xB0, 5));
+        original.run_until(|m| m.pins().irq.req).unwrap();
+
+        let snapshot = original.snapshot();
+        assert_eq!(snapshot.irq_device, Some(DeviceId::Io(0)));
+
+        let mut restored = interrupt_machine("");
+        restored.add_io_device(Timer::new(0xB0, 5));
+        restored.run_for(10).unwrap();
+        restored.restore(&snapshot).unwrap();
+
+        // The clock starts over at the restored cycle instead of sleeping for the ones in between.
+        assert_eq!(restored.snapshot().irq_device, Some(DeviceId::Io(0)));
+        assert!(restored.clock().effective_mhz().is_none());
+    }
+
+    #[test]
+    fn test_snapshot_mismatch() {
+        let mut snapshot = machine(PROGRAM).snapshot();
+        let mut target = machine(PROGRAM);
+
+        snapshot.version = SNAPSHOT_VERSION + 1;
+        assert!(matches!(target.restore(&snapshot), Err(SnapshotError::Version(_))));
+
+        snapshot.version = SNAPSHOT_VERSION;
+        snapshot.devices.pop();
+        assert!(matches!(
+            target.restore(&snapshot),
+            Err(SnapshotError::DeviceMismatch { expected: (2, 0), found: (1, 0) })
+        ));
+    }
+}
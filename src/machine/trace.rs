@@ -0,0 +1,416 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io::{self, Write},
+    ops::RangeInclusive,
+    rc::Rc,
+};
+
+use serde::Serialize;
+
+use super::Machine;
+use crate::{
+    cpu::{AddressSpace, BusAccess, CpuState, EmuError, ExtendedAddress, Flag, ReadWrite, WatchAction, WatchId, WatchKind, CPU},
+    device::DeviceResult,
+    disasm::Disassembler,
+    info::InstructionInfoFile,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// One aligned line per instruction.
+    Text,
+    /// One JSON object per line.
+    JsonLines,
+}
+
+/// A memory or IO access an instruction made, instruction fetches are not included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TraceAccess {
+    pub space: AddressSpace,
+    pub address: u32,
+    pub rw: ReadWrite,
+    pub data: u16,
+    pub word: bool,
+}
+
+/// One executed instruction. Interrupts entered after it are part of the record, like with
+/// [`Machine::step_instruction`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TraceRecord {
+    /// The CPU cycle counter once the instruction finished, see [`CPU::cycles`].
+    pub cycle: u64,
+    pub pc: u32,
+    pub bytes: Vec<u8>,
+    pub text: String,
+    /// The CPU state after the instruction.
+    pub state: CpuState,
+    pub accesses: Vec<TraceAccess>,
+}
+
+// The instruction currently executing.
+struct Pending {
+    pc: u32,
+    bytes: Vec<u8>,
+    text: String,
+}
+
+/// Writes a [`TraceRecord`] for every instruction the [`Machine`] runs while the tracer is attached, see
+/// [`Machine::attach_tracer`].
+pub struct Tracer {
+    output: Box<dyn Write>,
+    format: TraceFormat,
+    range: Option<RangeInclusive<u32>>,
+    // With a ring buffer records are only kept, and written once an error happens.
+    ring: Option<(usize, VecDeque<TraceRecord>)>,
+    disassembler: Disassembler,
+    pending: Option<Pending>,
+    accesses: Rc<RefCell<Vec<BusAccess>>>,
+    watchpoints: Vec<WatchId>,
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    pub fn new(output: impl Write + 'static, format: TraceFormat, inst_info: &InstructionInfoFile) -> Self {
+        Self {
+            output: Box::new(output),
+            format,
+            range: None,
+            ring: None,
+            disassembler: Disassembler::new(inst_info),
+            pending: None,
+            accesses: Rc::new(RefCell::new(Vec::new())),
+            watchpoints: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Only traces instructions whose address is in `range`.
+    pub fn with_range(mut self, range: RangeInclusive<u32>) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    /// Keeps the last `count` records instead of writing them, they are written once the machine reports an
+    /// error.
+    pub fn with_ring_buffer(mut self, count: usize) -> Self {
+        self.ring = Some((count, VecDeque::with_capacity(count)));
+        self
+    }
+
+    /// The error that stopped the tracer from writing. Tracing a machine never fails, the tracer stops writing
+    /// after the first error instead.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    /// Records accesses through internal watchpoint callbacks on the whole memory and IO space, they don't show up
+    /// among the watchpoints of the CPU.
+    fn attach(&mut self, cpu: &mut CPU) {
+        for (space, range) in [(AddressSpace::Memory, 0..=0xFF_FFFF), (AddressSpace::Io, 0..=0xFF)] {
+            let accesses = self.accesses.clone();
+            let callback = move |access: &BusAccess| accesses.borrow_mut().push(*access);
+
+            let action = WatchAction::Callback(Box::new(callback));
+
+            self.watchpoints.push(cpu.add_internal_watchpoint(space, range, WatchKind::Access, action));
+        }
+
+        if cpu.at_instruction_boundary() {
+            self.start(cpu);
+        }
+    }
+
+    fn detach(&mut self, cpu: &mut CPU) {
+        for id in self.watchpoints.drain(..) {
+            cpu.remove_internal_watchpoint(id);
+        }
+    }
+
+    pub(super) fn after_cycle(&mut self, cpu: &CPU) {
+        if cpu.at_instruction_boundary() || cpu.is_halted() {
+            if let Some(pending) = self.pending.take() {
+                let result = self.finish(pending, cpu.cycles(), cpu.state());
+                self.check(result);
+            }
+
+            if !cpu.is_halted() {
+                self.start(cpu);
+            }
+        }
+    }
+
+    pub(super) fn fault(&mut self, err: &EmuError) {
+        let result = self.write_fault(err);
+        self.check(result);
+    }
+
+    fn check(&mut self, result: io::Result<()>) {
+        if let Err(err) = result {
+            self.error.get_or_insert(err);
+        }
+    }
+
+    /// Writes out the ring buffer followed by the error.
+    fn write_fault(&mut self, err: &EmuError) -> io::Result<()> {
+        if self.error.is_some() {
+            return Ok(());
+        }
+
+        if let Some((_, records)) = &mut self.ring {
+            for record in std::mem::take(records) {
+                write_record(&mut self.output, self.format, &record)?;
+            }
+        }
+
+        match self.format {
+            TraceFormat::Text => writeln!(self.output, "Emulator Error: {err}")?,
+            TraceFormat::JsonLines => writeln!(self.output, "{}", serde_json::json!({ "error": err.to_string() }))?,
+        }
+
+        self.output.flush()
+    }
+
+    fn start(&mut self, cpu: &CPU) {
+        let pc = u32::from(cpu.pc());
+
+        // Read the whole instruction before it runs, in case it overwrites itself.
+        let bytes: Vec<u8> = (pc..pc + 6)
+            .map_while(|address| match cpu.peek(ExtendedAddress::new_ext_address(address), false) {
+                DeviceResult::Ok8(byte) => Some(byte),
+                _ => None,
+            })
+            .collect();
+
+        let (bytes, text) = match self.disassembler.decode(&bytes, 0) {
+            Some(line) => (line.bytes, line.text),
+            None => (Vec::new(), String::from("(no device)")),
+        };
+
+        self.accesses.borrow_mut().clear();
+        self.pending = Some(Pending { pc, bytes, text });
+    }
+
+    fn finish(&mut self, pending: Pending, cycle: u64, state: CpuState) -> io::Result<()> {
+        if self.error.is_some() || self.range.as_ref().is_some_and(|range| !range.contains(&pending.pc)) {
+            return Ok(());
+        }
+
+        let fetch = pending.pc..pending.pc + pending.bytes.len() as u32;
+        let accesses = self
+            .accesses
+            .borrow_mut()
+            .drain(..)
+            .filter(|access| {
+                !(access.space == AddressSpace::Memory && access.rw == ReadWrite::Read && fetch.contains(&access.address))
+            })
+            .map(|access| TraceAccess {
+                space: access.space,
+                address: access.address,
+                rw: access.rw,
+                data: access.data,
+                word: access.word,
+            })
+            .collect();
+
+        let record = TraceRecord {
+            cycle,
+            pc: pending.pc,
+            bytes: pending.bytes,
+            text: pending.text,
+            state,
+            accesses,
+        };
+
+        match &mut self.ring {
+            Some((count, records)) => {
+                if records.len() == *count {
+                    records.pop_front();
+                }
+
+                if *count > 0 {
+                    records.push_back(record);
+                }
+
+                Ok(())
+            }
+            None => write_record(&mut self.output, self.format, &record),
+        }
+    }
+}
+
+fn write_record(output: &mut dyn Write, format: TraceFormat, record: &TraceRecord) -> io::Result<()> {
+    if format == TraceFormat::JsonLines {
+        return writeln!(output, "{}", serde_json::to_string(record)?);
+    }
+
+    let bytes: Vec<String> = record.bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+    let state = &record.state;
+
+    write!(
+        output,
+        "{:>10} {:06X}: {:<17}  {:<24} ra={:04X} rb={:04X} rc={:04X} rd={:04X} sp=${:06X} flags={}",
+        record.cycle,
+        record.pc,
+        bytes.join(" "),
+        record.text,
+        state.ra,
+        state.rb,
+        state.rc,
+        state.rd,
+        u32::from(state.sp),
+        Flag::from_bits_retain(state.flags),
+    )?;
+
+    for access in &record.accesses {
+        let rw = match access.rw {
+            ReadWrite::Read => 'R',
+            ReadWrite::Write => 'W',
+        };
+        let data = match access.word {
+            true => format!("{:04X}", access.data),
+            false => format!("{:02X}", access.data),
+        };
+
+        match access.space {
+            AddressSpace::Memory => write!(output, " {rw} ${:06X}={data}", access.address)?,
+            AddressSpace::Io => write!(output, " {rw} io {:02X}={data}", access.address)?,
+        }
+    }
+
+    writeln!(output)
+}
+
+impl Machine {
+    /// Traces every instruction from now on, replacing the previous tracer.
+    pub fn attach_tracer(&mut self, mut tracer: Tracer) {
+        self.detach_tracer();
+
+        tracer.attach(&mut self.cpu);
+        self.tracer = Some(tracer);
+    }
+
+    /// Stops tracing and returns the tracer. The instruction that is executing right now is not written.
+    pub fn detach_tracer(&mut self) -> Option<Tracer> {
+        let mut tracer = self.tracer.take()?;
+        tracer.detach(&mut self.cpu);
+
+        Some(tracer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cpu::CycleStatus, info::get_instructions, machine::tests::machine};
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        fn lines(&self) -> Vec<String> {
+            String::from_utf8(self.0.borrow().clone()).unwrap().lines().map(String::from).collect()
+        }
+    }
+
+    const PROGRAM: [u8; 13] = [
+        59, 0x00, 0x00, 0x12, 0x34, // mov ra, 0x1234
+        232, 0x00, 0x00, 0x01, 0x00, 0x10, // st ra, $010010
+        0x00,                       // Unknown opcode
+        0x00,
+    ];
+
+    #[test]
+    fn test_text_trace() {
+        let buffer = SharedBuffer::default();
+        let mut machine = machine(&PROGRAM);
+        machine.attach_tracer(Tracer::new(buffer.clone(), TraceFormat::Text, &get_instructions()));
+
+        machine.step_instruction().unwrap(); // Reset
+        machine.step_instruction().unwrap();
+        machine.step_instruction().unwrap();
+
+        assert_eq!(
+            buffer.lines(),
+            [
+                "         8 000100: 3B 00 00 12 34     mov ra, 0x1234           ra=1234 rb=0000 rc=0000 rd=0000 sp=$010000 flags=-------",
+                "        15 000105: E8 00 00 01 00 10  st ra, $010010           ra=1234 rb=0000 rc=0000 rd=0000 sp=$010000 flags=------- W $010010=1234",
+            ]
+        );
+
+        machine.detach_tracer();
+        machine.step_instruction().unwrap_err();
+        assert_eq!(buffer.lines().len(), 2);
+    }
+
+    #[test]
+    fn test_json_trace_with_range() {
+        let buffer = SharedBuffer::default();
+        let mut machine = machine(&PROGRAM);
+        machine.attach_tracer(
+            Tracer::new(buffer.clone(), TraceFormat::JsonLines, &get_instructions()).with_range(0x105..=0x105),
+        );
+
+        machine.run_for(16).unwrap();
+
+        let lines = buffer.lines();
+        assert_eq!(lines.len(), 1);
+
+        let record: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(record["pc"], 0x105);
+        assert_eq!(record["state"]["ra"], 0x1234);
+        assert_eq!(
+            record["accesses"],
+            serde_json::json!([{ "space": "Memory", "address": 0x01_0010, "rw": "Write", "data": 0x1234, "word": true }])
+        );
+    }
+
+    #[test]
+    fn test_ring_buffer_dumps_on_error() {
+        let buffer = SharedBuffer::default();
+        let mut machine = machine(&PROGRAM);
+        machine.attach_tracer(Tracer::new(buffer.clone(), TraceFormat::Text, &get_instructions()).with_ring_buffer(1));
+
+        machine.run_for(15).unwrap();
+        assert!(buffer.lines().is_empty());
+
+        assert!(machine.run_for(100).is_err());
+
+        let lines = buffer.lines();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("st ra"));
+        assert!(lines[1].starts_with("Emulator Error: Unknown opcode"));
+    }
+
+    #[test]
+    fn test_watchpoints_stay_hidden() {
+        let buffer = SharedBuffer::default();
+        let mut machine = machine(&PROGRAM);
+        machine.attach_tracer(Tracer::new(buffer.clone(), TraceFormat::Text, &get_instructions()));
+
+        let cpu = machine.cpu_mut();
+        let id = cpu.add_watchpoint(AddressSpace::Memory, 0x01_0010..=0x01_0010, WatchKind::Write, WatchAction::Pause);
+        assert_eq!(cpu.watchpoints().len(), 1);
+        assert_eq!(cpu.watchpoints()[0].0, id);
+
+        // Neither the list nor removing every other id reaches the ones of the tracer.
+        for other in 0..id.0 {
+            assert!(!cpu.remove_watchpoint(WatchId(other)));
+        }
+
+        // The watchpoint still pauses the machine and the tracer still records the access.
+        assert!(matches!(machine.run_for(16), Ok(CycleStatus::Watchpoint(_))));
+        machine.step_instruction().unwrap();
+        assert!(buffer.lines()[1].ends_with("W $010010=1234"));
+    }
+}
@@ -1,80 +1,540 @@
+#![allow(non_snake_case)]
+
+use std::{
+    env,
+    fmt::Display,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+    process::exit,
+};
+
 use HexaCore::{
-    cpu::{EmuOptions, ExtendedAddress, Pins, ReadWrite, CPU},
-    device::{DeviceResult, Out, RAM, ROM},
-    info::*
+    asm::assemble,
+    cpu::{CycleStatus, ExtendedAddress},
+    debugger::{gdb, Debugger},
+    disasm::{Disassembler, Line},
+    info::*,
+    machine::{ClockConfig, ClockSync, ConfigError, IoDeviceConfig, MachineConfig, MemoryRegion, TraceFormat, Tracer},
+    util::parse_number,
 };
 
-use std::env;
+const USAGE: &str = "\
+Usage: HexaCore <command> [arguments]
+
+Commands:
+  run [image.bin] [options]      Run an image until it halts
+  debug [image.bin] [options]    Run an image in the debugger, [--gdb <port>] waits for GDB instead
+  asm <input.8do> [options]      Assemble a source file, [-o <output.bin>] [-l <listing.txt>]
+  disasm <image.bin> [options]   Disassemble an image, [--start <addr>] [--end <addr>] [--follow] [--entry <addr>]...
+  help                           Print this message
+
+Machine options (run and debug):
+  --config <board.json>          Build the machine from a board config, the image is optional then
+  --load <addr>                  Address the image is mapped at as ROM, defaults to $000000
+  --ram-size <size>              Bytes of RAM starting at $010000 (like 64K or 1M), defaults to the rest of memory.
+                                 Not allowed with --config
+  --device <name>@<port>         Attach an IO device, can be repeated. Defaults to out@0xA0 without --config,
+                                 devices: out, timer (6 ports, interrupt 5), pic (5 ports),
+                                 uart (4 ports, interrupt 6, on stdin and stdout)
+  --no-exit-on-halt              Keep the CPU halted after HLT instead of exiting
+  --trap-faults                  Deliver illegal instructions and bus faults to the program as exceptions
+
+Run options:
+  --max-cycles <count>           Stop after this many cycles (like 5000 or 2M)
+  --clock <frequency>            Run at this clock (like 8000, 500kHz or 2MHz) instead of as fast as possible or the
+                                 clock of the config
+  --frame <fps>                  Keep the clock in frames of 1/fps seconds instead of continuously
+  --trace <file>                 Write an instruction trace, - for stderr
+  --trace-format <text|json>     Format of the trace, defaults to text
+  --trace-last <count>           Only write the last instructions before an emulator error
+  --stats                        Print the cycles, instructions and speed to stderr
+
+Exit codes: the program's exit code (RA at HLT, clamped to 255) when it halts, 64 for invalid arguments,
+65 for invalid sources or images, 66 if a file can't be read, 70 for emulator and debugger errors, 73 if a file
+can't be written and 124 when --max-cycles ran out.";
+
+// Exit codes from sysexits.h, and the one `timeout` uses.
+const EXIT_USAGE: i32 = 64;
+const EXIT_DATA: i32 = 65;
+const EXIT_NO_INPUT: i32 = 66;
+const EXIT_SOFTWARE: i32 = 70;
+const EXIT_CANT_CREATE: i32 = 73;
+const EXIT_TIMEOUT: i32 = 124;
+
+enum CliError {
+    Usage(String),
+    Read(String, io::Error),
+    Write(String, io::Error),
+    Data(String),
+    Emulator(String),
+    Debugger(String),
+}
+
+impl CliError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Usage(_) => EXIT_USAGE,
+            CliError::Read(..) => EXIT_NO_INPUT,
+            CliError::Write(..) => EXIT_CANT_CREATE,
+            CliError::Data(_) => EXIT_DATA,
+            CliError::Emulator(_) | CliError::Debugger(_) => EXIT_SOFTWARE,
+        }
+    }
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::Usage(message) => write!(f, "{message}\nRun `HexaCore help` for usage"),
+            CliError::Read(path, err) => write!(f, "Could not read {path}: {err}"),
+            CliError::Write(path, err) => write!(f, "Could not write {path}: {err}"),
+            CliError::Data(message) => write!(f, "{message}"),
+            CliError::Emulator(message) => write!(f, "Emulator Error: {message}"),
+            CliError::Debugger(message) => write!(f, "Debugger Error: {message}"),
+        }
+    }
+}
 
 fn main() {
-    // let mut args: Vec<String> = env::args().collect();
-    // args.remove(0);
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    // if args.len() != 1 {
-    //     panic!("Missing ROM file argument.");
-    // }
+    let result = match args.split_first() {
+        Some((command, rest)) => match command.as_str() {
+            "run" => run(rest),
+            "debug" => debug(rest).map(|()| 0),
+            "asm" => asm(rest).map(|()| 0),
+            "disasm" => disasm(rest).map(|()| 0),
+            "help" | "--help" | "-h" => {
+                println!("{USAGE}");
+                Ok(0)
+            }
+            _ => Err(usage(format!("Unknown command {command}"))),
+        },
+        None => Err(usage("Missing command")),
+    };
 
-    let inst_info = get_instructions();
+    match result {
+        Ok(code) => exit(code),
+        Err(err) => {
+            eprintln!("{err}");
+            exit(err.exit_code());
+        }
+    }
+}
 
-    let rom = ROM::new_from_file(
-        ExtendedAddress::new_16bit_address(0x00_0000),
-        ExtendedAddress::new_ext_address(0x7F_FFFF),
-        ("gen/test.bin").into(), // TODO: Change back to &args[0] to return back to normal
-    );
-    let ram = RAM::new(
-        ExtendedAddress::new_ext_address(0x80_0000),
-        ExtendedAddress::new_ext_address(0xFF_FFFF),
-    );
-
-    let out = Out::new(0xA0);
-
-    let mut pins = Pins::default();
-    let mut cpu = CPU::new(inst_info, Some(EmuOptions::new_value(1)));
-    cpu.reset(&mut pins); 
-
-    cpu.add_device(ram);
-    cpu.add_device(rom);
-    cpu.add_io_device(out);
-
-    loop {
-        cpu.cycle(&mut pins);
-
-        if pins.bus_enable {
-            if pins.rw == ReadWrite::Read {
-                let res = cpu.read(pins.address);
-
-                match res {
-                    DeviceResult::Ok8(val) => pins.data = val as u16,
-                    DeviceResult::Ok16(val) => pins.data = val,
-                    _ => panic!("Device Error: {:?}", res),
-                }
-            } else {
-                let res = cpu.write(pins.address, pins.data);
+fn usage(message: impl Into<String>) -> CliError {
+    CliError::Usage(message.into())
+}
+
+// Takes the value following `flag` off the front of `rest`.
+fn value<'a>(flag: &str, rest: &mut &'a [String]) -> Result<&'a str, CliError> {
+    let (value, tail) = rest.split_first().ok_or_else(|| usage(format!("{flag} needs a value")))?;
+    *rest = tail;
+
+    Ok(value)
+}
 
-                if let DeviceResult::Ok = res {
-                } else {
-                    panic!("Device Error: {:?} for address 0x{:06x}", res, u32::from(pins.address));
+fn number(flag: &str, rest: &mut &[String]) -> Result<u32, CliError> {
+    let value = value(flag, rest)?;
+    parse_number(value).ok_or_else(|| usage(format!("Invalid number {value} for {flag}")))
+}
+
+// A number with an optional unit suffix, like `64K` or `2MHz`.
+fn scaled(flag: &str, rest: &mut &[String], units: &[(&str, u64)]) -> Result<u64, CliError> {
+    let value = value(flag, rest)?;
+    let (digits, scale) = units
+        .iter()
+        .find_map(|(unit, scale)| Some((value.strip_suffix(unit)?, *scale)))
+        .unwrap_or((value, 1));
+
+    parse_number(digits)
+        .and_then(|number| (number as u64).checked_mul(scale))
+        .ok_or_else(|| usage(format!("Invalid value {value} for {flag}")))
+}
+
+fn read(path: &str) -> Result<Vec<u8>, CliError> {
+    fs::read(path).map_err(|err| CliError::Read(path.into(), err))
+}
+
+impl From<ConfigError> for CliError {
+    fn from(err: ConfigError) -> Self {
+        match err {
+            ConfigError::Io(path, err) => CliError::Read(path.display().to_string(), err),
+            err => CliError::Data(err.to_string()),
+        }
+    }
+}
+
+/// Everything `run` and `debug` need to build the machine.
+struct MachineOptions {
+    config: Option<String>,
+    load: u32,
+    ram_size: Option<u32>,
+    devices: Vec<IoDeviceConfig>,
+    exit_on_halt: bool,
+    trap_faults: bool,
+}
+
+impl Default for MachineOptions {
+    fn default() -> Self {
+        Self {
+            config: None,
+            load: 0,
+            ram_size: None,
+            devices: Vec::new(),
+            exit_on_halt: true,
+            trap_faults: false,
+        }
+    }
+}
+
+impl MachineOptions {
+    /// Handles `flag` if it is a machine option, returns false if it is not.
+    fn parse(&mut self, flag: &str, rest: &mut &[String]) -> Result<bool, CliError> {
+        match flag {
+            "--config" => self.config = Some(value(flag, rest)?.into()),
+            "--load" => self.load = number(flag, rest)?,
+            "--ram-size" => {
+                let size = scaled(flag, rest, &[("K", 1024), ("M", 1024 * 1024)])?;
+
+                if size > 0xFF_0000 {
+                    return Err(usage("RAM can be at most 16320K, it starts at $010000"));
                 }
+
+                self.ram_size = Some(size as u32);
+            }
+            "--device" => {
+                let spec = value(flag, rest)?;
+                let (name, port) = spec
+                    .split_once('@')
+                    .ok_or_else(|| usage(format!("Devices are given as <name>@<port>, not {spec}")))?;
+                let port = parse_number(port)
+                    .and_then(|port| u8::try_from(port).ok())
+                    .ok_or_else(|| usage(format!("Invalid IO port {port}")))?;
+
+                self.devices.push(match name {
+                    "out" => IoDeviceConfig::Out { port },
+                    "timer" => IoDeviceConfig::Timer { port, interrupt: 5 },
+                    "pic" => IoDeviceConfig::Pic { port, vector_base: 0 },
+                    "uart" => IoDeviceConfig::Uart {
+                        port,
+                        interrupt: 6,
+                        input: None,
+                        output: None,
+                    },
+                    _ => return Err(usage(format!("Unknown device {name}"))),
+                });
             }
+            "--no-exit-on-halt" => self.exit_on_halt = false,
+            "--trap-faults" => self.trap_faults = true,
+            _ => return Ok(false),
         }
 
-        if pins.io_enable {
-            if pins.io_rw == ReadWrite::Read {
-                let res = cpu.read_io(pins.io_address);
+        Ok(true)
+    }
 
-                if let DeviceResult::Ok8(val) = res {
-                    pins.io_data = val;
-                } else {
-                    panic!("IO Device Error: {:?}", res);
+    /// The board from `--config`, or without one the default board: RAM from $010000 and an out device on $A0.
+    /// The image is mapped as ROM at the load address in front of the board's own memory, so it covers whatever
+    /// it overlaps.
+    fn config(&self, image: Option<&str>) -> Result<MachineConfig, CliError> {
+        let mut config = match &self.config {
+            Some(_) if self.ram_size.is_some() => {
+                return Err(usage("--ram-size can't be used with --config, the config declares the memory"))
+            }
+            Some(path) => MachineConfig::load(path)?,
+            None if image.is_none() => return Err(usage("An image is needed without --config")),
+            None => {
+                let mut config = MachineConfig::default();
+                let ram_size = self.ram_size.unwrap_or(0xFF_0000);
+
+                if ram_size > 0 {
+                    config.memory.push(MemoryRegion::Ram {
+                        start: ExtendedAddress::new_ext_address(0x01_0000),
+                        end: ExtendedAddress::new_ext_address(0x01_0000 + ram_size - 1),
+                        file: None,
+                    });
                 }
-            } else {
-                let res = cpu.write_io(pins.io_address, pins.io_data);
 
-                if let DeviceResult::Ok = res {
-                } else {
-                    panic!("IO Device Error: {:?}", res);
+                if self.devices.is_empty() {
+                    config.io.push(IoDeviceConfig::Out { port: 0xA0 });
                 }
+
+                config
             }
+        };
+
+        if let Some(image) = image {
+            config.memory.insert(
+                0,
+                MemoryRegion::Rom {
+                    start: ExtendedAddress::new_ext_address(self.load),
+                    end: None,
+                    file: Some(image.into()),
+                },
+            );
+        }
+
+        config.io.extend(self.devices.iter().cloned());
+
+        if !self.exit_on_halt {
+            config.options.exit_on_halt = false;
         }
+
+        if self.trap_faults {
+            config.options.trap_faults = true;
+        }
+
+        Ok(config)
     }
 }
+
+// The image is the first argument, unless it is left out for the one from a config.
+fn split_image(args: &[String]) -> (Option<&str>, &[String]) {
+    match args.split_first() {
+        Some((image, rest)) if !image.starts_with("--") => (Some(image), rest),
+        _ => (None, args),
+    }
+}
+
+// run [image.bin] [options], see USAGE.
+fn run(args: &[String]) -> Result<i32, CliError> {
+    let (image, mut rest) = split_image(args);
+
+    let mut options = MachineOptions::default();
+    let mut max_cycles = None;
+    let mut frequency = None;
+    let mut fps = None;
+    let mut trace = None;
+    let mut trace_format = TraceFormat::Text;
+    let mut trace_last = None;
+    let mut stats = false;
+
+    while let Some((flag, tail)) = rest.split_first() {
+        rest = tail;
+
+        if options.parse(flag, &mut rest)? {
+            continue;
+        }
+
+        match flag.as_str() {
+            "--max-cycles" => max_cycles = Some(scaled(flag, &mut rest, &[("K", 1000), ("M", 1_000_000)])?),
+            "--clock" => frequency = Some(scaled(flag, &mut rest, &[("kHz", 1000), ("MHz", 1_000_000), ("Hz", 1)])?),
+            "--frame" => fps = Some(number(flag, &mut rest)?),
+            "--trace" => trace = Some(value(flag, &mut rest)?),
+            "--trace-format" => {
+                trace_format = match value(flag, &mut rest)? {
+                    "text" => TraceFormat::Text,
+                    "json" => TraceFormat::JsonLines,
+                    format => return Err(usage(format!("Unknown trace format {format}"))),
+                }
+            }
+            "--trace-last" => trace_last = Some(number(flag, &mut rest)? as usize),
+            "--stats" => stats = true,
+            _ => return Err(usage(format!("Unknown option {flag} for run"))),
+        }
+    }
+
+    let mut config = options.config(image)?;
+    let sync = fps.map_or(ClockSync::Continuous, ClockSync::Frame);
+
+    match (frequency, &mut config.clock) {
+        (Some(frequency), clock) => *clock = Some(ClockConfig { frequency, sync }),
+        (None, Some(clock)) if fps.is_some() => clock.sync = sync,
+        (None, None) if fps.is_some() => return Err(usage("--frame needs a --clock")),
+        (None, _) => {}
+    }
+
+    let mut machine = config.build(get_instructions())?;
+
+    match (trace, trace_last) {
+        (Some(path), count) => {
+            let output: Box<dyn Write> = match path {
+                "-" => Box::new(io::stderr()),
+                path => Box::new(BufWriter::new(File::create(path).map_err(|err| CliError::Write(path.into(), err))?)),
+            };
+
+            let mut tracer = Tracer::new(output, trace_format, &get_instructions());
+
+            if let Some(count) = count {
+                tracer = tracer.with_ring_buffer(count);
+            }
+
+            machine.attach_tracer(tracer);
+        }
+        (None, Some(_)) => return Err(usage("--trace-last needs a --trace")),
+        (None, None) => {}
+    }
+
+    let result = match max_cycles {
+        Some(cycles) => machine.run_for(cycles),
+        None => machine.run().map(CycleStatus::Halted),
+    };
+
+    if stats {
+        let cpu = machine.cpu();
+        eprint!("Ran {} cycles and {} instructions", cpu.cycles(), cpu.instructions());
+
+        match machine.clock().effective_mhz() {
+            Some(mhz) => eprintln!(" at {mhz:.2} MHz"),
+            None => eprintln!(),
+        }
+    }
+
+    // Dropping the tracer flushes a buffered trace file, `exit` would skip that.
+    if let Some(err) = machine.detach_tracer().as_ref().and_then(Tracer::error) {
+        eprintln!("Could not write the trace: {err}");
+    }
+
+    match result {
+        // The exit code the program leaves in RA becomes the process exit status, clamped to what a process can
+        // report.
+        Ok(CycleStatus::Halted(code)) => Ok(code.min(0xFF) as i32),
+        Ok(_) => {
+            eprintln!("Stopped after {} cycles", machine.cpu().cycles());
+            Ok(EXIT_TIMEOUT)
+        }
+        Err(err) => Err(CliError::Emulator(err.to_string())),
+    }
+}
+
+// debug [image.bin] [--gdb <port>] [machine options], loads the image like a normal run and opens the debugger
+// prompt on stdin, or waits for GDB on the local port.
+fn debug(args: &[String]) -> Result<(), CliError> {
+    let (image, mut rest) = split_image(args);
+
+    let mut options = MachineOptions::default();
+    let mut gdb_port = None;
+
+    while let Some((flag, tail)) = rest.split_first() {
+        rest = tail;
+
+        if options.parse(flag, &mut rest)? {
+            continue;
+        }
+
+        match flag.as_str() {
+            "--gdb" => {
+                let port = value(flag, &mut rest)?;
+                gdb_port = Some(port.parse::<u16>().map_err(|_| usage(format!("Invalid port {port}")))?);
+            }
+            _ => return Err(usage(format!("Unknown option {flag} for debug"))),
+        }
+    }
+
+    let config = options.config(image)?;
+
+    // The prompt reads its commands from stdin, a UART on it would take some of them as its input.
+    if gdb_port.is_none() && config.uses_stdin() {
+        return Err(usage("A UART on stdin can't be used with the debugger prompt, give it files or use --gdb"));
+    }
+
+    let machine = config.build(get_instructions())?;
+    let mut debugger = Debugger::new(machine, get_instructions());
+
+    // Run the reset sequence, so the prompt starts on the first instruction of the program.
+    debugger.step_instructions(1).map_err(|err| CliError::Emulator(err.to_string()))?;
+
+    let result = match gdb_port {
+        Some(port) => {
+            println!("Waiting for GDB on 127.0.0.1:{port}");
+            gdb::listen(&mut debugger, ("127.0.0.1", port))
+        }
+        None => debugger.repl(io::stdin().lock(), io::stdout()),
+    };
+
+    result.map_err(|err| CliError::Debugger(err.to_string()))
+}
+
+// asm <input.8do> [-o <output.bin>] [-l <listing.txt>], the output defaults to the input with a .bin extension.
+fn asm(args: &[String]) -> Result<(), CliError> {
+    let Some((input, mut rest)) = args.split_first() else {
+        return Err(usage("asm needs a source file"));
+    };
+
+    let mut output = Path::new(input).with_extension("bin").display().to_string();
+    let mut listing = None;
+
+    while let Some((flag, tail)) = rest.split_first() {
+        rest = tail;
+
+        match flag.as_str() {
+            "-o" => output = value(flag, &mut rest)?.into(),
+            "-l" => listing = Some(value(flag, &mut rest)?),
+            _ => return Err(usage(format!("Unknown option {flag} for asm"))),
+        }
+    }
+
+    let source = fs::read_to_string(input).map_err(|err| CliError::Read(input.clone(), err))?;
+    let assembly = assemble(&source, &get_instructions()).map_err(|err| CliError::Data(format!("{input}: {err}")))?;
+
+    fs::write(&output, &assembly.image).map_err(|err| CliError::Write(output, err))?;
+
+    if let Some(listing) = listing {
+        fs::write(listing, assembly.listing_text(&source)).map_err(|err| CliError::Write(listing.into(), err))?;
+    }
+
+    Ok(())
+}
+
+// disasm <image.bin> [--start <addr>] [--end <addr>] [--follow] [--entry <addr>]...
+// Without --follow or --entry the range is decoded linearly, --follow alone starts at the reset vector.
+fn disasm(args: &[String]) -> Result<(), CliError> {
+    let Some((input, mut rest)) = args.split_first() else {
+        return Err(usage("disasm needs an image"));
+    };
+
+    let mut start = 0;
+    let mut end = None;
+    let mut follow = false;
+    let mut entries = Vec::new();
+
+    while let Some((flag, tail)) = rest.split_first() {
+        rest = tail;
+
+        match flag.as_str() {
+            "--follow" => follow = true,
+            "--start" => start = number(flag, &mut rest)?,
+            "--end" => end = Some(number(flag, &mut rest)?),
+            "--entry" => entries.push(number(flag, &mut rest)?),
+            _ => return Err(usage(format!("Unknown option {flag} for disasm"))),
+        }
+    }
+
+    let image = read(input)?;
+    let inst_info = get_instructions();
+    let disassembler = Disassembler::new(&inst_info);
+    let end = end.unwrap_or(image.len() as u32);
+
+    let lines = if follow || !entries.is_empty() {
+        if entries.is_empty() {
+            let vector = image
+                .get(..3)
+                .ok_or_else(|| CliError::Data(format!("{input} is too short to hold a reset vector")))?;
+
+            entries.push(u32::from_be_bytes([0, vector[0], vector[1], vector[2]]));
+        }
+
+        let mut lines = disassembler.follow(&image, &entries);
+        lines.retain(|line| (start..end).contains(&line.address));
+        lines
+    } else {
+        disassembler.linear(&image, start, end)
+    };
+
+    let mut next = None;
+
+    for Line { address, bytes, text } in lines {
+        // Separate blocks that are not next to each other.
+        if next.is_some_and(|next| next != address) {
+            println!();
+        }
+
+        let bytes: Vec<String> = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+        println!("{address:06X}: {:<17}  {text}", bytes.join(" "));
+
+        next = Some(address + bytes.len() as u32);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,23 @@
+//! Helpers shared by the command line, the board config and the debugger.
+
+/// Parses `$0200` and `0x0200` as hexadecimal and everything else as decimal.
+pub fn parse_number(value: &str) -> Option<u32> {
+    match value.strip_prefix('$').or_else(|| value.strip_prefix("0x")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("$0200"), Some(0x200));
+        assert_eq!(parse_number("0xA0"), Some(0xA0));
+        assert_eq!(parse_number("200"), Some(200));
+        assert_eq!(parse_number("0x"), None);
+        assert_eq!(parse_number("$12G"), None);
+    }
+}
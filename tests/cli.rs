@@ -0,0 +1,149 @@
+use std::{
+    fs,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+// A program that halts with 0x2A in RA.
+const HALT: &[u8] = &[
+    0x00, 0x00, 0x03,           // reset vector
+    59, 0x00, 0x00, 0x00, 0x2A, // mov ra, 0x002A
+    8, 0x00, 0x00,              // hlt
+];
+
+// A program that loops forever.
+const LOOP: &[u8] = &[
+    0x00, 0x00, 0x03,                 // reset vector
+    72, 0x00, 0x00, 0x00, 0x00, 0x03, // jmp $000003
+];
+
+// A program that stores RA into the last word of 64K of RAM and halts.
+const STORE_64K: &[u8] = &[
+    0x00, 0x00, 0x03,                  // reset vector
+    232, 0x00, 0x00, 0x01, 0xFF, 0xFE, // st ra, $01FFFE
+    8, 0x00, 0x00,                     // hlt
+];
+
+// A program that stores RA just past 64K of RAM.
+const STORE_PAST_64K: &[u8] = &[
+    0x00, 0x00, 0x03,                  // reset vector
+    232, 0x00, 0x00, 0x02, 0x00, 0x00, // st ra, $020000
+    8, 0x00, 0x00,                     // hlt
+];
+
+/// A directory for the files of one test, removed when it is dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("hexacore-cli-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        Self(dir)
+    }
+
+    fn file(&self, name: &str, contents: &[u8]) -> String {
+        let path = self.0.join(name);
+        fs::write(&path, contents).unwrap();
+
+        path.display().to_string()
+    }
+
+    fn path(&self, name: &str) -> String {
+        self.0.join(name).display().to_string()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.0).ok();
+    }
+}
+
+fn hexacore(args: &[&str]) -> i32 {
+    Command::new(env!("CARGO_BIN_EXE_HexaCore"))
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap()
+        .code()
+        .unwrap()
+}
+
+#[test]
+fn test_exit_codes() {
+    let dir = TempDir::new("exit-codes");
+    let halt = dir.file("halt.bin", HALT);
+    let looping = dir.file("loop.bin", LOOP);
+    let illegal = dir.file("illegal.bin", &[0x00, 0x00, 0x03, 0xFF, 0x00, 0x00]);
+    let source = dir.file("invalid.8do", b"mov ra, \n");
+
+    assert_eq!(hexacore(&["run", &halt]), 0x2A);
+    assert_eq!(hexacore(&["run", &looping, "--max-cycles", "1K"]), 124);
+
+    assert_eq!(hexacore(&[]), 64);
+    assert_eq!(hexacore(&["launch"]), 64);
+    assert_eq!(hexacore(&["run", &halt, "--speed", "1"]), 64);
+    assert_eq!(hexacore(&["run"]), 64);
+
+    assert_eq!(hexacore(&["asm", &source, "-o", &dir.path("invalid.bin")]), 65);
+    assert_eq!(hexacore(&["run", &dir.path("missing.bin")]), 66);
+    assert_eq!(hexacore(&["run", &illegal]), 70);
+    assert_eq!(hexacore(&["run", &halt, "--trace", &dir.path("missing/trace.txt")]), 73);
+}
+
+#[test]
+fn test_units() {
+    let dir = TempDir::new("units");
+    let halt = dir.file("halt.bin", HALT);
+    let looping = dir.file("loop.bin", LOOP);
+
+    assert_eq!(hexacore(&["run", &halt, "--clock", "2MHz"]), 0x2A);
+    assert_eq!(hexacore(&["run", &halt, "--clock", "500kHz"]), 0x2A);
+    assert_eq!(hexacore(&["run", &halt, "--clock", "8000"]), 0x2A);
+    assert_eq!(hexacore(&["run", &halt, "--clock", "2MHz", "--frame", "60"]), 0x2A);
+    assert_eq!(hexacore(&["run", &halt, "--clock", "2GHz"]), 64);
+    assert_eq!(hexacore(&["run", &halt, "--clock", "MHz"]), 64);
+
+    assert_eq!(hexacore(&["run", &looping, "--max-cycles", "2M"]), 124);
+    assert_eq!(hexacore(&["run", &looping, "--max-cycles", "5000"]), 124);
+    assert_eq!(hexacore(&["run", &looping, "--max-cycles", "5G"]), 64);
+}
+
+#[test]
+fn test_ram_size() {
+    let dir = TempDir::new("ram-size");
+    let store = dir.file("store.bin", STORE_64K);
+    let store_past = dir.file("store-past.bin", STORE_PAST_64K);
+    let config = dir.file("board.json", br#"{ "memory": [{ "type": "ram", "start": "$010000", "end": "$01FFFF" }] }"#);
+
+    assert_eq!(hexacore(&["run", &store, "--ram-size", "64K"]), 0);
+    assert_eq!(hexacore(&["run", &store, "--ram-size", "65536"]), 0);
+    assert_eq!(hexacore(&["run", &store_past, "--ram-size", "64K"]), 70);
+    assert_eq!(hexacore(&["run", &store_past, "--ram-size", "1M"]), 0);
+    assert_eq!(hexacore(&["run", &store, "--ram-size", "16320K"]), 0);
+
+    assert_eq!(hexacore(&["run", &store, "--ram-size", "16321K"]), 64);
+    assert_eq!(hexacore(&["run", &store, "--ram-size", "16M"]), 64);
+    assert_eq!(hexacore(&["run", &store, "--ram-size", "64KB"]), 64);
+    assert_eq!(hexacore(&["run", &store, "--config", &config, "--ram-size", "64K"]), 64);
+}
+
+#[test]
+fn test_options() {
+    let dir = TempDir::new("options");
+    let halt = dir.file("halt.bin", HALT);
+
+    assert_eq!(hexacore(&["run", &halt, "--device", "out@0xA0", "--device", "timer@0xB0"]), 0x2A);
+    assert_eq!(hexacore(&["run", &halt, "--device", "printer@0xA0"]), 64);
+    assert_eq!(hexacore(&["run", &halt, "--device", "out"]), 64);
+    assert_eq!(hexacore(&["run", &halt, "--device", "out@0x100"]), 64);
+    assert_eq!(hexacore(&["run", &halt, "--device", "out@0xA0", "--device", "out@0xA0"]), 65);
+    assert_eq!(hexacore(&["run", &halt, "--device", "out@0xA0", "--device", "timer@0x9E"]), 65);
+
+    assert_eq!(hexacore(&["run", &halt, "--frame", "60"]), 64);
+    assert_eq!(hexacore(&["run", &halt, "--trace-last", "10"]), 64);
+    assert_eq!(hexacore(&["run", &halt, "--trace-format", "xml", "--trace", "-"]), 64);
+}